@@ -0,0 +1,257 @@
+//! Grading many submissions at once, offline
+
+// std uses
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// external uses
+use anyhow::{bail, Context};
+
+// internal uses
+use crate::{Result, Error, Rubric, Submission};
+use crate::dropbox::{CsvOptions, ResultsFile};
+
+
+/// What happened when grading a directory of submissions.
+///
+/// One bad file doesn't stop the rest from being graded, it's
+/// just recorded in `errors`.
+#[derive(Debug)]
+pub struct BatchResults {
+    /// Each file that was graded, and the graded submission
+    pub graded: Vec<(PathBuf, Submission)>,
+    /// Each file that couldn't be graded, and why
+    pub errors: Vec<(PathBuf, Error)>,
+}
+
+/// Grades every `.json` file in a directory as a [`Submission`](crate::dropbox::Submission).
+///
+/// Each submission has its old grade cleared, is graded against the rubric, then written
+/// to the results file. The rubric's criteria are reset between each submission. Files
+/// are graded in alphabetical order, and anything that isn't a `.json` file is skipped.
+///
+/// If a file can't be read, parsed, or written to the results file, the error is
+/// collected in [`BatchResults::errors`] and grading moves on to the next file. This
+/// only returns an `Err` if the directory itself can't be read.
+///
+/// ```no_run
+/// use rubric::{Rubric, yaml};
+/// use rubric::dropbox::{self, ResultsFile};
+///
+/// let mut rubric = Rubric::from_yaml(yaml!("../../test_data/test_rubric.yml").unwrap()).unwrap();
+/// let mut results = ResultsFile::new_blank("submissions.csv").unwrap();
+///
+/// let batch = dropbox::grade_directory(&mut rubric, "collected/", &mut results).unwrap();
+/// for (path, err) in batch.errors {
+///     eprintln!("Couldn't grade {}: {}", path.display(), err);
+/// }
+/// ```
+pub fn grade_directory<P: AsRef<Path>>(rubric: &mut Rubric, dir: P, results: &mut ResultsFile) -> Result<BatchResults> {
+    let dir = dir.as_ref();
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .context(format!("Couldn't read submission directory {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().and_then(|e| e.to_str()) == Some("json"))
+        .collect();
+    files.sort();
+
+    let mut batch = BatchResults {
+        graded: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    for file in files {
+        match grade_file(rubric, &file, results) {
+            Ok(sub) => batch.graded.push((file, sub)),
+            Err(e) => batch.errors.push((file, e)),
+        }
+    }
+
+    Ok(batch)
+}
+
+// Grades a single submission file and writes it to the results file
+fn grade_file(rubric: &mut Rubric, file: &Path, results: &mut ResultsFile) -> Result<Submission> {
+    let content = fs::read_to_string(file)
+        .context(format!("Couldn't read {}", file.display()))?;
+    let mut sub: Submission = content.parse()
+        .context(format!("Couldn't parse a submission from {}", file.display()))?;
+
+    rubric.reset();
+    sub.grade_against(rubric);
+
+    results.write_submission(&sub)?;
+
+    Ok(sub)
+}
+
+/// Reads every `.csv` file in a directory as a results file, and puts all their
+/// submissions together. This is for combining the results of several dropboxes,
+/// like one for each lab section.
+///
+/// Every file has to have the same header, or this returns an `Err` naming the file
+/// that doesn't match. Files are read in alphabetical order, and empty files are skipped.
+/// Rows are read with the default [`CsvOptions`](crate::dropbox::CsvOptions), see
+/// [`Submission::from_csv_row`](crate::dropbox::Submission::from_csv_row) for what's read back.
+///
+/// ```no_run
+/// use std::path::Path;
+/// use rubric::dropbox;
+///
+/// let submissions = dropbox::load_submissions_from_dir(Path::new("sections/")).unwrap();
+/// println!("{} submissions from every section", submissions.len());
+/// ```
+pub fn load_submissions_from_dir<P: AsRef<Path>>(dir: P) -> Result<Vec<Submission>> {
+    let dir = dir.as_ref();
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .context(format!("Couldn't read results directory {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().and_then(|e| e.to_str()) == Some("csv"))
+        .collect();
+    files.sort();
+
+    let options = CsvOptions::default();
+    let mut first: Option<(PathBuf, Vec<String>)> = None;
+    let mut submissions = Vec::new();
+
+    for file in files {
+        let content = fs::read_to_string(&file)
+            .context(format!("Couldn't read {}", file.display()))?;
+        // Quoted values can have newlines, so rows aren't always one line
+        let mut rows = options.rows(&content).into_iter();
+        let header = match rows.next() {
+            Some((_, row)) => options.split(row),
+            None => continue
+        };
+
+        match &first {
+            Some((first_file, first_header)) if *first_header != header => bail!(
+                "The header in {} doesn't match the header in {}",
+                file.display(), first_file.display()
+            ),
+            Some(_) => {},
+            None => first = Some((file.clone(), header.clone())),
+        }
+
+        for (line, row) in rows.filter(|(_, row)| !row.is_empty()) {
+            let sub = Submission::from_csv_row(&header, &options.split(row), &options)
+                .context(format!("Couldn't read line {} of {}", line, file.display()))?;
+            submissions.push(sub);
+        }
+    }
+
+    Ok(submissions)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data, TestData};
+    use crate::rubric::Criterion;
+
+    fn rubric() -> Rubric {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Has name")
+            .worth(10)
+            .test(Box::new(|data: &TestData| data.contains_key("name")))
+            .build());
+        rubric
+    }
+
+    #[test]
+    fn test_grade_directory() {
+        let dir = tempdir::TempDir::new("grade_directory").unwrap();
+        let path = |name: &str| {
+            let mut p = dir.path().to_path_buf();
+            p.push(name);
+            p
+        };
+
+        let with_name = Submission::from_data(data! { "name" => "luke" });
+        let without_name = Submission::from_data(data! { "id" => "1234" });
+        fs::write(path("a.json"), serde_json::to_string(&with_name).unwrap()).unwrap();
+        fs::write(path("b.json"), serde_json::to_string(&without_name).unwrap()).unwrap();
+        fs::write(path("c.json"), "not a submission").unwrap();
+        fs::write(path("notes.txt"), "skipped").unwrap();
+
+        let mut results = ResultsFile::new_blank(path("results.csv")).unwrap();
+        let mut rubric = rubric();
+        let batch = grade_directory(&mut rubric, dir.path(), &mut results).unwrap();
+
+        assert_eq!(batch.graded.len(), 2);
+        assert_eq!(batch.graded[0].0, path("a.json"));
+        assert_eq!(batch.graded[0].1.grade, 10);
+        assert_eq!(batch.graded[1].1.grade, 0);
+
+        assert_eq!(batch.errors.len(), 1);
+        assert_eq!(batch.errors[0].0, path("c.json"));
+
+        // Header plus two rows
+        let written = fs::read_to_string(path("results.csv")).unwrap();
+        assert_eq!(written.lines().count(), 3);
+    }
+
+    // Writes the submissions to a results file, like a dropbox would
+    fn write_results(path: &Path, subs: &[Submission]) {
+        let mut results = ResultsFile::new_blank(path).unwrap();
+        for sub in subs {
+            results.write_submission(sub).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_load_submissions_from_dir() {
+        let dir = tempdir::TempDir::new("load_submissions").unwrap();
+
+        let mut luke = Submission::from_data(data! { "name" => "luke", "section" => "1" });
+        luke.grade = 10;
+        luke.passed = vec![String::from("Has name (+10)")];
+        luke.feedback = vec![String::from("Nice work,\nsee the comments")];
+        let leia = Submission::from_data(data! { "name" => "leia", "section" => "1" });
+        let mut han = Submission::from_data(data! { "name" => "han", "section" => "2" });
+        han.late = true;
+        han.failed = vec![String::from("Has name (+10)"), String::from("Tests")];
+
+        write_results(&dir.path().join("section1.csv"), &[luke, leia]);
+        write_results(&dir.path().join("section2.csv"), &[han]);
+        fs::write(dir.path().join("notes.txt"), "skipped").unwrap();
+
+        let subs = load_submissions_from_dir(dir.path()).unwrap();
+        assert_eq!(subs.len(), 3);
+
+        assert_eq!(subs[0].data["name"], "luke");
+        assert_eq!(subs[0].grade, 10);
+        assert_eq!(subs[0].passed, vec!["Has name (+10)"]);
+        // A newline in a quoted value doesn't end the row
+        assert_eq!(subs[0].feedback, vec!["Nice work,\nsee the comments"]);
+        assert!(subs[0].override_reason.is_none());
+
+        assert_eq!(subs[1].data["name"], "leia");
+        assert!(subs[1].passed.is_empty());
+
+        assert_eq!(subs[2].data["section"], "2");
+        assert!(subs[2].late);
+        assert_eq!(subs[2].failed, vec!["Has name (+10)", "Tests"]);
+    }
+
+    #[test]
+    fn test_load_submissions_mismatched_header() {
+        let dir = tempdir::TempDir::new("load_submissions_mismatch").unwrap();
+
+        write_results(&dir.path().join("a.csv"), &[Submission::from_data(data! { "name" => "luke" })]);
+        write_results(&dir.path().join("b.csv"), &[Submission::from_data(data! { "name" => "leia" })]);
+        write_results(&dir.path().join("c.csv"), &[Submission::from_data(data! { "id" => "1234" })]);
+
+        let err = load_submissions_from_dir(dir.path()).unwrap_err().to_string();
+        assert!(err.contains("c.csv"), "{}", err);
+        assert!(!err.contains("b.csv"), "{}", err);
+    }
+
+    #[test]
+    fn test_grade_directory_missing_dir() {
+        let mut results = ResultsFile::new_blank("test_data/missing_dir.csv").unwrap();
+        assert!(grade_directory(&mut rubric(), "doesntexist/", &mut results).is_err());
+        fs::remove_file("test_data/missing_dir.csv").ok();
+    }
+}
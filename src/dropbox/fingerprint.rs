@@ -7,7 +7,7 @@ use std::env;
 use serde::{Serialize, Deserialize};
 
 // internal uses
-use crate::dropbox::AsCsv;
+use crate::dropbox::{AsCsv, CsvOptions};
 
 
 /// A set of data to verify a Submission. This struct contains some system data, and
@@ -16,6 +16,7 @@ use crate::dropbox::AsCsv;
 /// You shouldn't create one of these directly, instead add a fingerprint to a submission
 /// with [`Submission::set_fingerprint`](crate::dropbox::submission::Submission::set_fingerprint).
 #[derive(Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Fingerprint {
     /// Any random string
     pub secret: String,
@@ -46,6 +47,14 @@ impl AsCsv for Fingerprint {
         format!("secret,platform")
     }
 
+    fn as_csv_with(&self, options: &CsvOptions) -> String {
+        options.row(&[&self.secret, &self.platform])
+    }
+
+    fn header_with(&self, options: &CsvOptions) -> String {
+        options.row(&["secret", "platform"])
+    }
+
 }
 
 
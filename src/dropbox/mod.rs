@@ -1,7 +1,8 @@
 //! A dropbox to collect submissions.
 //! 
 //! This is a webserver that accepts Submissions in JSON format
-//! and writes them to a CSV file.
+//! and writes them to a CSV file. It also accepts urlencoded forms, see
+//! [`helpers::web::post_form`](crate::helpers::web::post_form).
 //! 
 //! Note that this has no relation to [Dropbox](https://www.dropbox.com/), and doesn't 
 //! function the same at all.
@@ -9,28 +10,38 @@
 //! You should run this on a publicly available server and be sure 
 //! the correct ports are open. You can run this on whatever port you'd like,
 //! as long as you have permission. The `/submit` route is meant to accept a Submission.
-//! See the [`helpers::web::post_json`](helpers::web::post_json) function for more info on
+//! See the [`helpers::web::post_json_ok`](helpers::web::post_json_ok) function for more info on
 //! how to send a submission to the dropbox.
 
 // Re exports to be available from this module
 pub mod results_file;
 pub mod submission;
 pub mod fingerprint;
+pub mod batch;
+pub mod server;
 
-pub use results_file::{AsCsv, ResultsFile};
-pub use submission::{Submission, TestData};
+pub use results_file::{AsCsv, CsvOptions, ResultsFile, ResultsFileLock, ResultsFormat};
+pub use batch::{grade_directory, load_submissions_from_dir, BatchResults};
+pub use server::ServerConfig;
+pub use submission::{read_submissions, CriterionResult, DataLimits, FromPairs, QueryString, Submission, SubmissionDiff, TestData, TestDataExt};
 
 
 // std uses
 use std::env;
+use std::path::Path;
 use std::sync::Mutex;
 
+// internal uses
+use crate::Result;
+
 // external uses
 use rocket::{Rocket, Config, State};
 use rocket::http::Status;
+use rocket::response::status::Custom;
 use rocket::error::LaunchError;
 use rocket::config::Environment;
 use rocket_contrib::json::Json;
+use anyhow::{anyhow, Context};
 
 
 /// A ResultsFile wrapped in a Mutex for thread locking.
@@ -46,33 +57,88 @@ fn return_ok() -> Status {
 
 /// Accepts a submission and writes it to the results file
 #[post("/submit", format = "application/json", data = "<submission>")]
-fn accept_submission(state: State<SharedResultsFile>, submission: Json<Submission>) -> Status {
-    // Retrieve the submission
-    let sub = submission.into_inner();
+fn accept_submission(state: State<SharedResultsFile>, limits: State<DataLimits>, submission: Json<Submission>) -> std::result::Result<Status, Custom<String>> {
+    let submission = submission.into_inner();
+    within_limits(&submission, &limits)?;
+    // Submissions sent as JSON should have been graded before they were sent.
+    // It's still recorded so nothing is lost, but its grade doesn't mean anything.
+    // Older versions of the crate don't send whether it was graded at all.
+    if !submission.is_graded() {
+        eprintln!("Warning: recording a submission that wasn't graded");
+    }
+    Ok(record(state, submission))
+}
+
+/// Accepts a urlencoded form and writes it to the results file.
+///
+/// The form data becomes the submission's `data`.
+#[post("/submit", format = "application/x-www-form-urlencoded", data = "<form>")]
+fn accept_form(state: State<SharedResultsFile>, limits: State<DataLimits>, form: String) -> std::result::Result<Status, Custom<String>> {
+    let submission = submission_from_form(&form);
+    within_limits(&submission, &limits)?;
+    Ok(record(state, submission))
+}
+
+// Rejects a submission that's over the limits with a 413 saying why
+fn within_limits(submission: &Submission, limits: &DataLimits) -> std::result::Result<(), Custom<String>> {
+    submission.check_limits(limits).map_err(|e| {
+        eprintln!("Rejected a submission: {}", e);
+        Custom(Status::PayloadTooLarge, e.to_string())
+    })
+}
+
+// Builds a submission out of a urlencoded form
+fn submission_from_form(form: &str) -> Submission {
+    Submission::from_data(TestData::from_query_string(form))
+}
+
+// Marks a submission graded and writes it to the results file
+fn record(state: State<SharedResultsFile>, mut sub: Submission) -> Status {
+    // Whatever grade it has is the one that's recorded
+    sub.graded = true;
 
     // Lock the results file until we're done with it
     let shared_rf: &SharedResultsFile = state.inner();
     let mut lock = shared_rf.0.lock().expect("Lock shared results file");
 
-    // Write the header based on first submission
-    if lock.length() == 0 {
-        if lock.append(&sub.header()).is_err() {
-            eprintln!("Error! Could not write csv file header. File is likely locked by another process");
-            return Status::InternalServerError;
-        };
-    }
-
-    if lock.write_csv(&sub).is_ok() {
-        return Status::Accepted;
-    } else {
-        eprintln!("Error: Could not write following submission");
+    // Writes the header too, if this is the first submission
+    if let Err(e) = lock.write_submission(&sub) {
+        eprintln!("Error: Could not write following submission: {}", e);
         eprintln!("{:#?}", sub);
         return Status::InternalServerError;
     }
+    Status::Accepted
 }
 
-/// Builds a rocket instance to launch
-fn new_rocket(port: u16) -> Rocket {
+/// Builds a rocket instance to launch.
+///
+/// If `rotate_keep` is `Some`, an existing results file is rotated before
+/// accepting submissions, keeping that many archives. Submissions are stored
+/// in `submissions.csv`, or `submissions.json` for a JSON array.
+fn new_rocket(port: u16, rotate_keep: Option<usize>, format: ResultsFormat, limits: DataLimits) -> Rocket {
+    let config = server_config(port).expect("Could not build dropbox server");
+
+    // Create a results file wrapped in a Mutex
+    // The muted is necessary because route handling is asyncronous.
+    // This allows mutliple submissions to be submitted at once. It also will
+    // crash when the instructor opens the dropbox if the file is already in use.
+    let path = match format {
+        ResultsFormat::Csv => "submissions.csv",
+        ResultsFormat::JsonArray => "submissions.json",
+    };
+    let results_file = open_results_file(Path::new(path), format, rotate_keep)
+        .expect("Couldn't open results file");
+    let shared_results_file = SharedResultsFile(Mutex::new(results_file));
+
+    println!("Dropbox is open! accepting POST requests to /submit");
+    return rocket::custom(config)
+        .manage(shared_results_file)
+        .manage(limits)
+        .mount("/", routes![return_ok, accept_submission, accept_form]);
+}
+
+// The rocket config for a server on the given port
+fn server_config(port: u16) -> Result<Config> {
     // If debug
     #[cfg(debug_assertions)]
     let builder = Config::build(Environment::Development);
@@ -80,33 +146,58 @@ fn new_rocket(port: u16) -> Rocket {
     #[cfg(not(debug_assertions))]
     let builder = Config::build(Environment::Production);
 
-    let config = builder
+    builder
         .address("0.0.0.0")
         .port(port)
         .finalize()
-        .expect("Could not build dropbox server");
-
-    // Create a results file wrapped in a Mutex
-    // The muted is necessary because route handling is asyncronous.
-    // This allows mutliple submissions to be submitted at once. It also will
-    // crash when the instructor opens the dropbox if the file is already in use.
-    let shared_results_file = SharedResultsFile(
-        Mutex::new(
-            ResultsFile::new_blank("submissions.csv").expect("Couldn't open results file")
-        )
-    );
+        .map_err(|e| anyhow!("Couldn't configure the server: {}", e))
+}
 
-    println!("Dropbox is open! accepting POST requests to /submit");
-    return rocket::custom(config)
-        .manage(shared_results_file)
-        .mount("/", routes![return_ok, accept_submission]);
+// Opens a results file, rotating it first if `rotate_keep` is set
+fn open_results_file(path: &Path, format: ResultsFormat, rotate_keep: Option<usize>) -> Result<ResultsFile> {
+    let mut results_file = match format {
+        ResultsFormat::Csv => ResultsFile::new_blank(path),
+        ResultsFormat::JsonArray => ResultsFile::json_array(path),
+    }.context(format!("Couldn't open results file {}", path.display()))?;
+    if let Some(keep) = rotate_keep {
+        if let Some(archive) = results_file.rotate(keep).context("Couldn't rotate results file")? {
+            println!("Archived old submissions to {}", archive.display());
+        }
+    }
+    Ok(results_file)
 }
 
 /// Opens the dropbox for submissions on the given port.
 /// 
 /// You should probably use [`open_with_arg()`](crate::dropbox::open_with_arg).
+///
+/// Submissions over the default [`DataLimits`](crate::dropbox::DataLimits) are rejected.
 pub fn open(port: u16) -> LaunchError {
-    new_rocket(port).launch()
+    new_rocket(port, None, ResultsFormat::Csv, DataLimits::default()).launch()
+}
+
+/// Same as [`open()`](crate::dropbox::open), but submissions over your own limits
+/// are rejected with a `413 Payload Too Large` saying what was too big.
+pub fn open_with_limits(port: u16, limits: DataLimits) -> LaunchError {
+    new_rocket(port, None, ResultsFormat::Csv, limits).launch()
+}
+
+/// Same as [`open()`](crate::dropbox::open), but submissions are stored in
+/// the format you choose.
+///
+/// A [`JsonArray`](crate::dropbox::ResultsFormat::JsonArray) is stored in
+/// `submissions.json` instead of `submissions.csv`.
+pub fn open_with_format(port: u16, format: ResultsFormat) -> LaunchError {
+    new_rocket(port, None, format, DataLimits::default()).launch()
+}
+
+/// Same as [`open()`](crate::dropbox::open), but archives the existing
+/// results file first so the dropbox starts with a fresh one.
+///
+/// Only the newest `keep` archives are kept.
+/// See [`ResultsFile::rotate`](crate::dropbox::ResultsFile::rotate).
+pub fn open_and_rotate(port: u16, keep: usize) -> LaunchError {
+    new_rocket(port, Some(keep), ResultsFormat::Csv, DataLimits::default()).launch()
 }
 
 /// This is the same as [`open()`](crate::dropbox::open), but it will
@@ -138,7 +229,7 @@ mod tests {
     use rocket::http::Header;
 
     fn client() -> Client {
-        let rocket = new_rocket(8080);
+        let rocket = new_rocket(8080, None, ResultsFormat::Csv, DataLimits::default());
         Client::new(rocket).expect("valid rocket instance")
     }
 
@@ -183,4 +274,45 @@ mod tests {
 
         assert_eq!(req.status(), Status::Accepted);
     }
+
+    #[test]
+    fn test_accept_form() {
+        let client = client();
+        let req = client.post("/submit")
+            .body("name=Luke+Skywalker&id=1234")
+            .header(Header::new("Content-Type", "application/x-www-form-urlencoded"))
+            .dispatch();
+
+        assert_eq!(req.status(), Status::Accepted);
+    }
+
+    #[test]
+    fn test_oversized_submissions() {
+        let limits = DataLimits { max_keys: 2, max_value_len: 10, ..DataLimits::default() };
+        let client = Client::new(new_rocket(8080, None, ResultsFormat::Csv, limits)).unwrap();
+
+        let sub = Submission::from_data(crate::data! { "name" => "x".repeat(11) });
+        let mut resp = client.post("/submit")
+            .body(serde_json::to_string(&sub).unwrap())
+            .header(Header::new("Content-Type", "application/json"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PayloadTooLarge);
+        assert_eq!(resp.body_string().unwrap(), "The value of `name` is 11 bytes, the limit is 10");
+
+        let resp = client.post("/submit")
+            .body("a=1&b=2&c=3")
+            .header(Header::new("Content-Type", "application/x-www-form-urlencoded"))
+            .dispatch();
+        assert_eq!(resp.status(), Status::PayloadTooLarge);
+    }
+
+    #[test]
+    fn test_submission_from_form() {
+        let sub = submission_from_form("name=Luke+Skywalker&id=1234&lab=%232");
+
+        assert_eq!(sub.data.len(), 3);
+        assert_eq!(sub.data["name"], "Luke Skywalker");
+        assert_eq!(sub.data["lab"], "#2");
+        assert_eq!(sub.grade, 0);
+    }
 }
@@ -1,12 +1,29 @@
 // std uses
 use std::path::{PathBuf, Path};
-use std::fs::{File, canonicalize, OpenOptions, metadata};
-use std::io::Write;
+use std::fs::{self, File, canonicalize, OpenOptions, metadata};
+use std::io::{ErrorKind, Write};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 // External uses
+use chrono::{DateTime, Local, NaiveDateTime};
+use serde_json::Value;
 // For error handling
-use crate::{Result, Error};
-use anyhow::Context;
+use crate::{Result, Error, Submission};
+use anyhow::{anyhow, Context};
+
+// Timestamp used in the name of rotated files.
+// Sorts correctly as a string, and includes milliseconds so that
+// two rotations in the same second don't collide.
+const ROTATE_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+
+/// How long [`ResultsFile::lock`](crate::dropbox::ResultsFile::lock) waits for
+/// another writer before giving up.
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How often to check if the lock is free
+const LOCK_POLL: Duration = Duration::from_millis(5);
 
 
 /// Trait to convert a struct to csv (comma separated values).
@@ -51,13 +68,369 @@ pub trait AsCsv {
     /// The header for the csv file. Should match the fields
     /// in `as_csv()`
     fn header(&self) -> String;
+    /// The item in CSV format, written with the given options.
+    ///
+    /// By default this ignores the options and calls `as_csv()`. Types that
+    /// can have a delimiter in their values should use
+    /// [`CsvOptions::row`](crate::dropbox::CsvOptions::row) instead.
+    fn as_csv_with(&self, _options: &CsvOptions) -> String {
+        self.as_csv()
+    }
+    /// The header, written with the given options. Should match `as_csv_with()`.
+    ///
+    /// By default this ignores the options and calls `header()`.
+    fn header_with(&self, _options: &CsvOptions) -> String {
+        self.header()
+    }
+}
+
+/// How values are written to CSV.
+///
+/// The default is what's always been written: commas between fields, no quoting,
+/// and `;` between the names in a submission's `passed` and `failed` lists. Without
+/// quoting, a delimiter in a value is replaced with the `passed_failed_separator`
+/// so it can't break the row. Turn quoting on with [`quote`](crate::dropbox::CsvOptions::quote)
+/// to keep values as they are.
+///
+/// ```rust
+/// use rubric::dropbox::CsvOptions;
+///
+/// let options = CsvOptions::new()
+///     .delimiter(';')
+///     .quote('"')
+///     .passed_failed_separator("|");
+///
+/// let row = options.row(&["Luke", "a; b", "say \"hi\""]);
+/// assert_eq!(row, r#"Luke;"a; b";"say ""hi""""#);
+/// assert_eq!(options.split(&row), vec!["Luke", "a; b", "say \"hi\""]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    /// Goes between fields. Defaults to `,`.
+    pub delimiter: char,
+    /// If set, values with the delimiter, the quote, or a newline in them are
+    /// wrapped in this, and quotes inside them are doubled. Defaults to `None`.
+    pub quote: Option<char>,
+    /// Goes between names in a submission's `passed` and `failed` lists.
+    /// Defaults to `;`.
+    pub passed_failed_separator: String,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: ',',
+            quote: None,
+            passed_failed_separator: String::from(";"),
+        }
+    }
+}
+
+impl CsvOptions {
+    /// The default options. See [`CsvOptions`](crate::dropbox::CsvOptions).
+    pub fn new() -> Self {
+        CsvOptions::default()
+    }
+
+    /// Sets the delimiter between fields
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Quotes values that need it with the given character
+    pub fn quote(mut self, quote: char) -> Self {
+        self.quote = Some(quote);
+        self
+    }
+
+    /// Sets the separator between names in the `passed` and `failed` lists
+    pub fn passed_failed_separator(mut self, separator: &str) -> Self {
+        self.passed_failed_separator = String::from(separator);
+        self
+    }
+
+    /// Returns an `Err` if the options would write rows that can't be read back,
+    /// ie. the separator is empty or has the delimiter in it, or the quote is the
+    /// same as the delimiter.
+    pub fn check(&self) -> Result<()> {
+        if self.passed_failed_separator.is_empty() {
+            return Err(anyhow!("The passed/failed separator can't be empty"));
+        }
+        if self.passed_failed_separator.contains(self.delimiter) {
+            return Err(anyhow!(
+                "The passed/failed separator `{}` can't contain the delimiter `{}`",
+                self.passed_failed_separator, self.delimiter
+            ));
+        }
+        if self.quote == Some(self.delimiter) {
+            return Err(anyhow!("The quote and the delimiter can't both be `{}`", self.delimiter));
+        }
+        Ok(())
+    }
+
+    /// Makes one value safe to put in a row. It's quoted if it needs to be,
+    /// or without quoting, the delimiter is replaced.
+    pub fn field(&self, value: &str) -> String {
+        match self.quote {
+            Some(q) => {
+                let needs_quotes = value.contains(self.delimiter)
+                    || value.contains(q)
+                    || value.contains('\n')
+                    || value.contains('\r');
+                if needs_quotes {
+                    let doubled = value.replace(q, &format!("{}{}", q, q));
+                    format!("{}{}{}", q, doubled, q)
+                } else {
+                    value.to_owned()
+                }
+            },
+            None => value.replace(self.delimiter, &self.passed_failed_separator)
+        }
+    }
+
+    /// Quotes a value whether it needs it or not, even if these options don't
+    /// quote. Without a `quote`, it's quoted with `"`.
+    ///
+    /// ```rust
+    /// # use rubric::dropbox::CsvOptions;
+    /// let options = CsvOptions::new();
+    /// assert_eq!(options.quoted_field("fast, clean"), r#""fast, clean""#);
+    /// assert_eq!(options.split(r#"1,"fast, clean""#), vec!["1", "fast, clean"]);
+    /// ```
+    pub fn quoted_field(&self, value: &str) -> String {
+        let q = self.quote.unwrap_or('"');
+        let doubled = value.replace(q, &format!("{}{}", q, q));
+        format!("{}{}{}", q, doubled, q)
+    }
+
+    /// Joins the values into a row, with each made safe by
+    /// [`field`](crate::dropbox::CsvOptions::field). There's no trailing newline.
+    pub fn row<S: AsRef<str>>(&self, values: &[S]) -> String {
+        values.iter()
+            .map(|v| self.field(v.as_ref()))
+            .collect::<Vec<_>>()
+            .join(&self.delimiter.to_string())
+    }
+
+    /// Joins names into a list with the `passed_failed_separator`.
+    ///
+    /// A separator in a name is escaped with a `\` (and so is a `\`),
+    /// so [`split_list`](crate::dropbox::CsvOptions::split_list) gets the same names back.
+    ///
+    /// ```rust
+    /// # use rubric::dropbox::CsvOptions;
+    /// let options = CsvOptions::new();
+    /// let list = options.list(&["Great; clean", "Tests"]);
+    /// assert_eq!(list, r"Great\; clean;Tests");
+    /// assert_eq!(options.split_list(&list), vec!["Great; clean", "Tests"]);
+    /// ```
+    pub fn list<S: AsRef<str>>(&self, names: &[S]) -> String {
+        let escaped_separator = format!("\\{}", self.passed_failed_separator);
+        names.iter()
+            .map(|n| n.as_ref().replace('\\', "\\\\").replace(&self.passed_failed_separator, &escaped_separator))
+            .collect::<Vec<_>>()
+            .join(&self.passed_failed_separator)
+    }
+
+    /// Splits a list written by [`list`](crate::dropbox::CsvOptions::list) back
+    /// into its names. An empty value is an empty list.
+    pub fn split_list(&self, value: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        if value.is_empty() {
+            return names;
+        }
+        let separator = self.passed_failed_separator.as_str();
+        let mut current = String::new();
+        let mut rest = value;
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('\\') {
+                // Whatever's escaped is kept as it is
+                if let Some(after_sep) = after.strip_prefix(separator) {
+                    current.push_str(separator);
+                    rest = after_sep;
+                } else {
+                    let c = after.chars().next().unwrap_or('\\');
+                    current.push(c);
+                    rest = &after[c.len_utf8().min(after.len())..];
+                }
+            } else if let Some(after_sep) = rest.strip_prefix(separator) {
+                names.push(std::mem::take(&mut current));
+                rest = after_sep;
+            } else {
+                let c = rest.chars().next().unwrap();
+                current.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        names.push(current);
+        names
+    }
+
+    /// Splits a row written with these options back into its values,
+    /// undoing any quoting.
+    ///
+    /// A value that starts with the quote (or `"`, if these options don't quote)
+    /// is read as quoted, so values from
+    /// [`quoted_field`](crate::dropbox::CsvOptions::quoted_field) come back too.
+    pub fn split(&self, row: &str) -> Vec<String> {
+        let quote = self.quote.unwrap_or('"');
+        let mut values = Vec::new();
+        let mut current = String::new();
+        let mut in_quotes = false;
+        let mut at_start = true;
+        let mut chars = row.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if in_quotes {
+                if c != quote {
+                    current.push(c);
+                } else if chars.peek() == Some(&quote) {
+                    // A doubled quote is a literal quote
+                    current.push(c);
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            } else if c == quote && at_start {
+                in_quotes = true;
+            } else if c == self.delimiter {
+                values.push(std::mem::take(&mut current));
+                at_start = true;
+                continue;
+            } else {
+                current.push(c);
+            }
+            at_start = false;
+        }
+        values.push(current);
+        values
+    }
+
+    /// Splits CSV text into its rows, each with the line it starts on, counting
+    /// from 1. A newline inside a quoted value is part of the value, so a row can
+    /// be more than one line. Quotes are read the same way as in
+    /// [`split`](crate::dropbox::CsvOptions::split), and line endings are left off.
+    ///
+    /// ```rust
+    /// # use rubric::dropbox::CsvOptions;
+    /// let options = CsvOptions::new();
+    /// let rows = options.rows("name,notes\nluke,\"two\nlines\"\nleia,one\n");
+    /// assert_eq!(rows, vec![(1, "name,notes"), (2, "luke,\"two\nlines\""), (4, "leia,one")]);
+    /// ```
+    pub fn rows<'a>(&self, content: &'a str) -> Vec<(usize, &'a str)> {
+        let quote = self.quote.unwrap_or('"');
+        let mut rows = Vec::new();
+        let (mut start, mut start_line, mut line) = (0, 1, 1);
+        let mut in_quotes = false;
+        let mut at_start = true;
+        let mut chars = content.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            if in_quotes {
+                if c == quote {
+                    // A doubled quote is a literal quote
+                    if chars.peek().map(|(_, next)| *next) == Some(quote) {
+                        chars.next();
+                    } else {
+                        in_quotes = false;
+                    }
+                } else if c == '\n' {
+                    line += 1;
+                }
+            } else if c == quote && at_start {
+                in_quotes = true;
+            } else if c == '\n' {
+                rows.push((start_line, content[start..i].trim_end_matches('\r')));
+                start = i + 1;
+                line += 1;
+                start_line = line;
+                at_start = true;
+                continue;
+            }
+            at_start = !in_quotes && c == self.delimiter;
+        }
+        if start < content.len() {
+            rows.push((start_line, content[start..].trim_end_matches('\r')));
+        }
+        rows
+    }
 }
 
-/// A CSV results file containing the results of the grading process.
+/// How a [`ResultsFile`](crate::dropbox::ResultsFile) stores submissions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResultsFormat {
+    /// One submission per line, in CSV. This is the default.
+    Csv,
+    /// A single, pretty-printed JSON array of submissions.
+    ///
+    /// The whole file is rewritten every time a submission is written, with
+    /// the submissions sorted by the `id` in their data, then by time. Keys are
+    /// always in the same (alphabetical) order. It's slower than CSV for big
+    /// files, but it's valid JSON and gives clean diffs in git.
+    JsonArray,
+}
+
+/// Held while writing to a [`ResultsFile`](crate::dropbox::ResultsFile), so
+/// only one writer uses the file at a time. The lock is released when this is dropped.
+///
+/// See [`ResultsFile::lock`](crate::dropbox::ResultsFile::lock).
+#[derive(Debug)]
+pub struct ResultsFileLock {
+    path: PathBuf,
+    // What we wrote in the lock file, so we don't remove a lock
+    // someone else took over
+    owner: String,
+}
+
+impl Drop for ResultsFileLock {
+    fn drop(&mut self) {
+        if fs::read_to_string(&self.path).ok().as_deref() == Some(self.owner.as_str()) {
+            fs::remove_file(&self.path).ok();
+        }
+    }
+}
+
+// What goes in a lock file: the pid of the process holding it, and when
+// it was taken in milliseconds since the epoch, so each lock is its own
+fn lock_owner() -> String {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    format!("{} {}", process::id(), millis)
+}
+
+// Whether a lock file was left behind by a process that's gone. A lock
+// that's only old might still be in use, so it isn't stale until we
+// know its owner is dead.
+fn is_stale(contents: &str) -> bool {
+    let pid = contents.split_whitespace().next().and_then(|p| p.parse::<u32>().ok());
+    pid.and_then(process_alive) == Some(false)
+}
+
+// Whether a process is running, if we can tell. Only Linux is checked,
+// everywhere else a lock is waited on until it's released.
+fn process_alive(pid: u32) -> Option<bool> {
+    let proc = Path::new("/proc");
+    if cfg!(target_os = "linux") && proc.is_dir() {
+        Some(proc.join(pid.to_string()).exists())
+    } else {
+        None
+    }
+}
+
+/// A results file containing the results of the grading process.
+///
+/// This is CSV unless it's opened with
+/// [`json_array`](crate::dropbox::ResultsFile::json_array).
 #[derive(Debug)]
 pub struct ResultsFile {
     pub path: PathBuf,
-    handle: File
+    handle: File,
+    format: ResultsFormat,
+    csv: CsvOptions,
+    // Whether the file has anything in it yet. Checked once when the file is
+    // opened, so writing a CSV row doesn't have to look at the file first.
+    has_header: bool
 }
 
 impl ResultsFile {
@@ -100,7 +473,10 @@ impl ResultsFile {
 
         let mut rf = ResultsFile {
             path: full_path,
-            handle
+            handle,
+            format: ResultsFormat::Csv,
+            csv: CsvOptions::default(),
+            has_header: true
         };
         if rf.length() == 0 {
             rf.append(&header.as_ref()).context(
@@ -114,10 +490,173 @@ impl ResultsFile {
     pub fn new_blank<P: AsRef<Path>>(path: P) -> Result<ResultsFile> {
         let handle = OpenOptions::new().append(true).create(true).open(&path)?;
         let full_path = canonicalize(path)?;
-        Ok(ResultsFile {
+        let mut rf = ResultsFile {
             path: full_path,
-            handle
-        })
+            handle,
+            format: ResultsFormat::Csv,
+            csv: CsvOptions::default(),
+            has_header: false
+        };
+        rf.has_header = rf.length() > 0;
+        Ok(rf)
+    }
+
+    /// Opens a results file that stores submissions as a JSON array.
+    ///
+    /// See [`ResultsFormat::JsonArray`](crate::dropbox::ResultsFormat::JsonArray).
+    /// The file is created if it doesn't exist. If it does, it has to already be
+    /// a JSON array (or empty) before you can write to it.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use rubric::Submission;
+    /// # use rubric::dropbox::ResultsFile;
+    /// let mut rf = ResultsFile::json_array("json_array_doc.json").unwrap();
+    /// rf.write_submission(&Submission::new()).unwrap();
+    ///
+    /// let written = std::fs::read_to_string("json_array_doc.json").unwrap();
+    /// assert!(written.starts_with("["));
+    /// # std::fs::remove_file("json_array_doc.json").unwrap();
+    /// ```
+    pub fn json_array<P: AsRef<Path>>(path: P) -> Result<ResultsFile> {
+        let mut rf = ResultsFile::new_blank(path)?;
+        rf.format = ResultsFormat::JsonArray;
+        Ok(rf)
+    }
+
+    /// Takes a lock on the file, waiting up to [`LOCK_TIMEOUT`](crate::dropbox::results_file::LOCK_TIMEOUT)
+    /// for any other writer to finish. Writes are only safe from each other while the lock is held.
+    ///
+    /// The lock is a file next to this one, named like `submissions.csv.lock`, so it works
+    /// between processes as well as threads, as long as they all use a `ResultsFile`.
+    /// [`write_submission`](crate::dropbox::ResultsFile::write_submission) and
+    /// [`rotate`](crate::dropbox::ResultsFile::rotate) take the lock themselves, so the
+    /// dropbox and anything else writing submissions doesn't need to.
+    ///
+    /// The lock file holds the pid of the process that took it and when. If that process
+    /// has died (only checked on Linux), the lock was left behind and is taken over instead
+    /// of waited on. A lock that's still held is never taken, however long it's been held,
+    /// so this returns an `Err` after the timeout instead.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use rubric::dropbox::ResultsFile;
+    /// let mut rf = ResultsFile::new("lock_doc.csv", "x,y").unwrap();
+    /// {
+    ///     let _lock = rf.lock().unwrap();
+    ///     rf.append("1,2").unwrap();
+    ///     rf.append("3,4").unwrap();
+    /// }
+    /// # std::fs::remove_file("lock_doc.csv").unwrap();
+    /// ```
+    pub fn lock(&self) -> Result<ResultsFileLock> {
+        self.lock_timeout(LOCK_TIMEOUT)
+    }
+
+    /// Same as [`lock`](crate::dropbox::ResultsFile::lock), but waits up to `timeout`.
+    pub fn lock_timeout(&self, timeout: Duration) -> Result<ResultsFileLock> {
+        let mut name = self.path.clone().into_os_string();
+        name.push(".lock");
+        let path = PathBuf::from(name);
+
+        let start = Instant::now();
+        loop {
+            // Only one writer can create it
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let owner = lock_owner();
+                    let lock = ResultsFileLock { path, owner };
+                    file.write_all(lock.owner.as_bytes()).context(
+                        format!("Couldn't write lock file {}", lock.path.display())
+                    )?;
+                    return Ok(lock);
+                },
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if self.take_over_stale(&path)? {
+                        continue;
+                    }
+                    if start.elapsed() >= timeout {
+                        return Err(anyhow!(
+                            "Timed out waiting for the lock on {}. If nothing else is writing to it, delete {}",
+                            self.path.display(), path.display()
+                        ));
+                    }
+                    thread::sleep(LOCK_POLL);
+                },
+                Err(e) => return Err(Error::new(e)
+                    .context(format!("Couldn't create lock file {}", path.display())))
+            }
+        }
+    }
+
+    // Removes the lock file at `path` if it's stale, returning whether it did.
+    //
+    // The lock is moved out of the way before it's removed, and put back if it
+    // turns out someone else took it over between reading and moving it.
+    fn take_over_stale(&self, path: &Path) -> Result<bool> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            // Released while we were looking
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(Error::new(e)
+                .context(format!("Couldn't read lock file {}", path.display())))
+        };
+        if !is_stale(&contents) {
+            return Ok(false);
+        }
+
+        let mut name = path.to_path_buf().into_os_string();
+        name.push(format!(".{}", process::id()));
+        let moved = PathBuf::from(name);
+        if fs::rename(path, &moved).is_err() {
+            // Someone else got to it first
+            return Ok(false);
+        }
+        if fs::read_to_string(&moved).ok().as_deref() != Some(contents.as_str()) {
+            // Not the one we looked at. Put it back, unless there's a new one already
+            fs::hard_link(&moved, path).ok();
+            fs::remove_file(&moved).ok();
+            return Ok(false);
+        }
+        fs::remove_file(&moved).context(
+            format!("Couldn't remove stale lock file {}", path.display())
+        )?;
+        Ok(true)
+    }
+
+    /// Returns how this file stores submissions
+    pub fn format(&self) -> ResultsFormat {
+        self.format
+    }
+
+    /// Sets how CSV is written to this file, like the delimiter.
+    ///
+    /// Returns an `Err` if the options don't pass
+    /// [`CsvOptions::check`](crate::dropbox::CsvOptions::check).
+    /// Set these before writing anything, the header and rows should match.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use rubric::Submission;
+    /// # use rubric::dropbox::{CsvOptions, ResultsFile};
+    /// # let dir = tempdir::TempDir::new("csv_options").unwrap();
+    /// # let path = dir.path().join("semicolons.csv");
+    /// let mut rf = ResultsFile::new_blank(&path).unwrap();
+    /// rf.set_csv_options(CsvOptions::new().delimiter(';').passed_failed_separator("|")).unwrap();
+    /// rf.write_submission(&Submission::new()).unwrap();
+    ///
+    /// let written = std::fs::read_to_string(&path).unwrap();
+    /// assert!(written.starts_with("time;late;grade;"));
+    /// ```
+    pub fn set_csv_options(&mut self, options: CsvOptions) -> Result<()> {
+        options.check()?;
+        self.csv = options;
+        Ok(())
+    }
+
+    /// Returns how CSV is written to this file
+    pub fn csv_options(&self) -> &CsvOptions {
+        &self.csv
     }
 
     /// Creates a new results file meant for an item that implements AsCsv.
@@ -204,7 +743,177 @@ impl ResultsFile {
     /// # remove_file(point.filename()).unwrap()
     /// ```
     pub fn write_csv<R: AsCsv>(&mut self, record: &R) -> Result<usize> {
-        self.append(&format!("{}", record.as_csv()))
+        let row = record.as_csv_with(&self.csv);
+        self.append(&row)
+    }
+
+    /// Writes a submission in whatever [`format`](crate::dropbox::ResultsFormat) this file uses.
+    ///
+    /// For CSV, the submission is appended as one row, and nothing else in the file
+    /// is read or written. The header is written first if the file was empty when it
+    /// was opened. For a JSON array, the array is read, the submission is added, and
+    /// the file is rewritten, so only use that if you need it.
+    ///
+    /// The file is [locked](crate::dropbox::ResultsFile::lock) while it's written,
+    /// so other writers to the same file wait their turn.
+    pub fn write_submission(&mut self, submission: &Submission) -> Result<()> {
+        let _lock = self.lock()?;
+        match self.format {
+            ResultsFormat::Csv => {
+                // Another writer could have written the header since this was opened
+                if !self.has_header {
+                    self.has_header = self.length() > 0;
+                }
+                if !self.has_header {
+                    let header = submission.header_with(&self.csv);
+                    self.append(&header)
+                        .context("Couldn't write the csv header")?;
+                    self.has_header = true;
+                }
+                self.write_csv(submission)?;
+            },
+            ResultsFormat::JsonArray => self.write_json_array(submission)?
+        }
+        Ok(())
+    }
+
+    fn write_json_array(&mut self, submission: &Submission) -> Result<()> {
+        let content = fs::read_to_string(&self.path)
+            .context(format!("Couldn't read ResultsFile at {}", self.path.display()))?;
+        let mut submissions: Vec<Value> = if content.trim().is_empty() {
+            Vec::new()
+        } else {
+            serde_json::from_str(&content)
+                .map_err(|e| anyhow!("ResultsFile at {} isn't a JSON array: {}", self.path.display(), e))?
+        };
+        submissions.push(serde_json::to_value(submission)?);
+
+        // Sorted by student id, then time. Ones without an id go first.
+        submissions.sort_by_key(|sub| {
+            let id = sub["data"]["id"].as_str().map(String::from);
+            let time = sub["time"].as_str().and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+            (id, time)
+        });
+
+        let json = serde_json::to_string_pretty(&submissions)?;
+        fs::write(&self.path, format!("{}\n", json))
+            .context(format!("Couldn't write ResultsFile at {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Archives the current file and starts a fresh one at the same path.
+    ///
+    /// The current file is renamed to `<name>-<timestamp>.<ext>`, ie.
+    /// `submissions-20200501-221500-123.csv`. For CSV, the first line of the old
+    /// file (the header) is copied into the new one. A JSON array starts empty. Only the newest `keep` archives
+    /// are kept, older ones are deleted.
+    ///
+    /// Returns the path of the archive. If the file is empty, nothing happens
+    /// and this returns `None`.
+    ///
+    /// The file is renamed and reopened while you hold `&mut self`, so as long as
+    /// all writes go through this `ResultsFile` (like the dropbox does, behind a `Mutex`),
+    /// no writes are lost.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use rubric::dropbox::ResultsFile;
+    /// let mut rf = ResultsFile::new("rotate_doc.csv", "x,y").unwrap();
+    /// rf.append("1,2").unwrap();
+    ///
+    /// let archive = rf.rotate(5).unwrap().unwrap();
+    /// assert!(archive.exists());
+    /// assert_eq!(rf.length(), 4);
+    /// # std::fs::remove_file(archive).unwrap();
+    /// # std::fs::remove_file("rotate_doc.csv").unwrap();
+    /// ```
+    pub fn rotate(&mut self, keep: usize) -> Result<Option<PathBuf>> {
+        let _lock = self.lock()?;
+        if self.length() == 0 {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&self.path)
+            .context(format!("Couldn't read ResultsFile at {}", self.path.display()))?;
+        let header = content.lines().next().unwrap_or("").to_owned();
+
+        let archive = self.archive_path();
+        fs::rename(&self.path, &archive)
+            .context(format!("Couldn't archive ResultsFile to {}", archive.display()))?;
+
+        self.handle = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .context(format!("Couldn't reopen ResultsFile at {}", self.path.display()))?;
+        self.has_header = false;
+        if !header.is_empty() && self.format == ResultsFormat::Csv {
+            self.append(&header)?;
+            self.has_header = true;
+        }
+
+        self.prune_archives(keep)?;
+        Ok(Some(archive))
+    }
+
+    /// Calls [`rotate`](crate::dropbox::ResultsFile::rotate) only if the
+    /// file is larger than `max_bytes`.
+    pub fn rotate_if_larger(&mut self, max_bytes: u64, keep: usize) -> Result<Option<PathBuf>> {
+        if self.length() > max_bytes {
+            return self.rotate(keep);
+        }
+        Ok(None)
+    }
+
+    /// Returns all the archives made by rotating this file, oldest first.
+    ///
+    /// Only files named like an archive, with the timestamp rotating gives it,
+    /// are included. Other files that happen to start with this one's name,
+    /// like `submissions-section2.csv` next to `submissions.csv`, aren't.
+    pub fn archives(&self) -> Result<Vec<PathBuf>> {
+        let (stem, ext) = self.stem_and_ext();
+        let prefix = format!("{}-", stem);
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut archives: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                name.strip_prefix(&prefix)
+                    .and_then(|rest| rest.strip_suffix(&ext))
+                    .and_then(|stamp| NaiveDateTime::parse_from_str(stamp, ROTATE_TIMESTAMP_FORMAT).ok())
+                    .is_some()
+            })
+            .collect();
+        archives.sort();
+        Ok(archives)
+    }
+
+    // Splits the file name into a stem and an extension (with the dot).
+    // The extension is empty if there isn't one.
+    fn stem_and_ext(&self) -> (String, String) {
+        let stem = self.path.file_stem().and_then(|s| s.to_str()).unwrap_or("results").to_owned();
+        let ext = match self.path.extension().and_then(|e| e.to_str()) {
+            Some(e) => format!(".{}", e),
+            None => String::new()
+        };
+        (stem, ext)
+    }
+
+    fn archive_path(&self) -> PathBuf {
+        let (stem, ext) = self.stem_and_ext();
+        let name = format!("{}-{}{}", stem, Local::now().format(ROTATE_TIMESTAMP_FORMAT), ext);
+        self.path.with_file_name(name)
+    }
+
+    fn prune_archives(&self, keep: usize) -> Result<()> {
+        let archives = self.archives()?;
+        if archives.len() > keep {
+            for old in &archives[..archives.len() - keep] {
+                fs::remove_file(old).context(format!("Couldn't remove old archive {}", old.display()))?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -342,6 +1051,68 @@ mod tests {
         delete(&file);
     }
 
+    #[test]
+    fn test_rotate_past_threshold() {
+        let dir = tempdir::TempDir::new("results_file_rotate").unwrap();
+        let mut file = dir.path().to_path_buf();
+        file.push("submissions.csv");
+
+        let mut rf = ResultsFile::new(&file, header()).unwrap();
+        // Under the threshold, nothing happens
+        assert!(rf.rotate_if_larger(100, 3).unwrap().is_none());
+        assert!(rf.archives().unwrap().is_empty());
+
+        for _ in 0..20 {
+            rf.append("1,2").unwrap();
+        }
+        let archive = rf.rotate_if_larger(50, 3).unwrap().expect("Should have rotated");
+
+        assert!(archive.exists());
+        assert!(archive.file_name().unwrap().to_str().unwrap().starts_with("submissions-"));
+        assert_eq!(rf.archives().unwrap(), vec![archive.clone()]);
+        // Header is kept in the new file, and it's written to
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "x,y\n");
+        rf.append("3,4").unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "x,y\n3,4\n");
+        assert!(std::fs::read_to_string(&archive).unwrap().contains("1,2"));
+    }
+
+    #[test]
+    fn test_rotate_keeps_newest() {
+        let dir = tempdir::TempDir::new("results_file_rotate_keep").unwrap();
+        let mut file = dir.path().to_path_buf();
+        file.push("results.csv");
+
+        let mut rf = ResultsFile::new(&file, header()).unwrap();
+        let mut made = Vec::new();
+        for _ in 0..4 {
+            made.push(rf.rotate(2).unwrap().unwrap());
+            // Make sure the timestamps differ
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        assert_eq!(rf.archives().unwrap(), made[2..].to_vec());
+    }
+
+    #[test]
+    fn test_rotate_leaves_other_files() {
+        let dir = tempdir::TempDir::new("results_file_rotate_others").unwrap();
+        let file = dir.path().join("submissions.csv");
+        let section = dir.path().join("submissions-section2.csv");
+        let almost = dir.path().join("submissions-20200101-120000.csv");
+        fs::write(&section, "x,y\n1,2\n").unwrap();
+        fs::write(&almost, "x,y\n1,2\n").unwrap();
+
+        let mut rf = ResultsFile::new(&file, header()).unwrap();
+        let archive = rf.rotate(0).unwrap().unwrap();
+        assert!(!archive.exists());
+        let archive = rf.rotate(1).unwrap().unwrap();
+        assert_eq!(rf.archives().unwrap(), vec![archive]);
+
+        assert!(section.exists());
+        assert!(almost.exists());
+    }
+
     #[test]
     fn test_results_file_for_csv_item() {
         let point = Point { x: 32, y: 37 };
@@ -349,4 +1120,267 @@ mod tests {
         assert!(format!("{}", rf.path.display()).contains(&point.filename()));
         delete(point.filename());
     }
+
+    #[test]
+    fn test_write_submission_csv() {
+        let dir = tempdir::TempDir::new("results_file_write_csv").unwrap();
+        let file = dir.path().join("submissions.csv");
+        let mut rf = ResultsFile::new_blank(&file).unwrap();
+        assert_eq!(rf.format(), ResultsFormat::Csv);
+
+        let sub = Submission::from_data(crate::data! { "id" => "1" });
+        rf.write_submission(&sub).unwrap();
+        rf.write_submission(&sub).unwrap();
+
+        let written = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(written.lines().count(), 3);
+        assert_eq!(written.lines().next().unwrap(), sub.header());
+    }
+
+    #[test]
+    fn test_write_submission_csv_header_once() {
+        let dir = tempdir::TempDir::new("results_file_header_once").unwrap();
+        let file = dir.path().join("submissions.csv");
+        let sub = Submission::from_data(crate::data! { "id" => "1" });
+
+        let mut rf = ResultsFile::new_blank(&file).unwrap();
+        for _ in 0..50 {
+            rf.write_submission(&sub).unwrap();
+        }
+        // Opening it again doesn't add another header
+        let mut reopened = ResultsFile::new_blank(&file).unwrap();
+        reopened.write_submission(&sub).unwrap();
+
+        let written = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(written.lines().count(), 52);
+        assert_eq!(written.lines().filter(|l| *l == sub.header()).count(), 1);
+    }
+
+    #[test]
+    fn test_write_submission_csv_only_appends() {
+        let dir = tempdir::TempDir::new("results_file_only_appends").unwrap();
+        let file = dir.path().join("submissions.csv");
+        let sub = Submission::from_data(crate::data! { "id" => "1" });
+
+        let mut rf = ResultsFile::new_blank(&file).unwrap();
+        rf.write_submission(&sub).unwrap();
+
+        // Once the header is written, the file isn't read again. If it were,
+        // emptying the file would make it write the header again.
+        std::fs::write(&file, "").unwrap();
+        rf.write_submission(&sub).unwrap();
+        let written = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(written, format!("{}\n", sub.as_csv()));
+    }
+
+    #[test]
+    fn test_rotate_then_write_submission() {
+        let dir = tempdir::TempDir::new("results_file_rotate_write").unwrap();
+        let file = dir.path().join("submissions.csv");
+        let sub = Submission::from_data(crate::data! { "id" => "1" });
+
+        let mut rf = ResultsFile::new_blank(&file).unwrap();
+        rf.write_submission(&sub).unwrap();
+        rf.rotate(1).unwrap();
+        rf.write_submission(&sub).unwrap();
+
+        let written = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(written, format!("{}\n{}\n", sub.header(), sub.as_csv()));
+    }
+
+    #[test]
+    fn test_csv_semicolon_round_trip() {
+        let dir = tempdir::TempDir::new("results_file_semicolons").unwrap();
+        let file = dir.path().join("submissions.csv");
+        let options = CsvOptions::new().delimiter(';').quote('"').passed_failed_separator("|");
+
+        let mut sub = Submission::from_data(crate::data! {
+            "id" => "1",
+            "notes" => "fast; clean",
+            "quote" => "said \"hi\""
+        });
+        sub.passed = vec![String::from("Compiles, runs"), String::from("Tests")];
+        sub.failed = vec![String::from("Docs")];
+
+        let mut rf = ResultsFile::new_blank(&file).unwrap();
+        rf.set_csv_options(options.clone()).unwrap();
+        rf.write_submission(&sub).unwrap();
+
+        let written = std::fs::read_to_string(&file).unwrap();
+        let mut lines = written.lines();
+        let header = options.split(lines.next().unwrap());
+        let row = options.split(lines.next().unwrap());
+        assert_eq!(header.len(), row.len());
+
+        let parsed: std::collections::HashMap<_, _> = header.into_iter().zip(row).collect();
+        assert_eq!(parsed["grade"], "0");
+        assert_eq!(parsed["passed"], "Compiles, runs|Tests");
+        assert_eq!(parsed["failed"], "Docs");
+        assert_eq!(parsed["notes"], "fast; clean");
+        assert_eq!(parsed["quote"], "said \"hi\"");
+        assert_eq!(parsed["id"], "1");
+    }
+
+    #[test]
+    fn test_csv_default_replaces_delimiter_in_names() {
+        let mut sub = Submission::new();
+        sub.passed = vec![String::from("Compiles, runs"), String::from("Tests")];
+
+        let row = sub.as_csv();
+        assert!(row.contains(",Compiles; runs;Tests,"));
+        assert_eq!(row.split(',').count(), sub.header().split(',').count());
+    }
+
+    #[test]
+    fn test_csv_quote_keeps_delimiter_in_names() {
+        let mut sub = Submission::new();
+        sub.passed = vec![String::from("Compiles, runs"), String::from("Tests")];
+
+        let options = CsvOptions::new().quote('"');
+        let row = sub.as_csv_with(&options);
+        assert!(row.contains(",\"Compiles, runs;Tests\","));
+
+        let read = Submission::from_csv_row(&options.split(&sub.header_with(&options)), &options.split(&row), &options).unwrap();
+        assert_eq!(read.passed, sub.passed);
+    }
+
+    #[test]
+    fn test_csv_options_check() {
+        assert!(CsvOptions::new().check().is_ok());
+        assert!(CsvOptions::new().delimiter(';').check().is_err());
+        assert!(CsvOptions::new().delimiter(';').passed_failed_separator("|").check().is_ok());
+        assert!(CsvOptions::new().passed_failed_separator("").check().is_err());
+        assert!(CsvOptions::new().quote(',').check().is_err());
+
+        let dir = tempdir::TempDir::new("results_file_bad_options").unwrap();
+        let mut rf = ResultsFile::new_blank(dir.path().join("bad_options.csv")).unwrap();
+        assert!(rf.set_csv_options(CsvOptions::new().delimiter(';')).is_err());
+        assert_eq!(rf.csv_options(), &CsvOptions::default());
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let dir = tempdir::TempDir::new("results_file_concurrent").unwrap();
+        let file = dir.path().join("submissions.csv");
+        // Both open it while it's empty, like two graders starting at once
+        let writers: Vec<ResultsFile> = (0..2).map(|_| ResultsFile::new_blank(&file).unwrap()).collect();
+
+        let handles: Vec<_> = writers.into_iter().enumerate().map(|(w, mut rf)| {
+            thread::spawn(move || {
+                for i in 0..100 {
+                    let mut sub = Submission::new();
+                    sub.data.insert(String::from("id"), format!("{}-{}", w, i));
+                    sub.data.insert(String::from("padding"), "x".repeat(2000));
+                    rf.write_submission(&sub).unwrap();
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let content = fs::read_to_string(&file).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 201);
+        let header = CsvOptions::default().split(lines[0]);
+        assert_eq!(content.matches(lines[0]).count(), 1);
+        for line in &lines[1..] {
+            assert_eq!(CsvOptions::default().split(line).len(), header.len());
+        }
+        for w in 0..2 {
+            for i in 0..100 {
+                assert!(content.contains(&format!("{}-{}", w, i)));
+            }
+        }
+        // The lock is gone when it's done
+        assert!(!dir.path().join("submissions.csv.lock").exists());
+    }
+
+    #[test]
+    fn test_held_lock_times_out() {
+        let dir = tempdir::TempDir::new("results_file_held_lock").unwrap();
+        let file = dir.path().join("submissions.csv");
+        let lock_path = dir.path().join("submissions.csv.lock");
+        let rf = ResultsFile::new_blank(&file).unwrap();
+
+        let held = rf.lock().unwrap();
+        let start = Instant::now();
+        assert!(rf.lock_timeout(Duration::from_millis(30)).is_err());
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        // Still ours, until it's released
+        assert!(lock_path.exists());
+        drop(held);
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn test_stale_lock_taken_over() {
+        let dir = tempdir::TempDir::new("results_file_stale_lock").unwrap();
+        let file = dir.path().join("submissions.csv");
+        let lock_path = dir.path().join("submissions.csv.lock");
+        let rf = ResultsFile::new_blank(&file).unwrap();
+
+        // Taken by a process that isn't running
+        if cfg!(target_os = "linux") {
+            fs::write(&lock_path, lock_owner().replacen(&process::id().to_string(), &u32::MAX.to_string(), 1)).unwrap();
+            let start = Instant::now();
+            drop(rf.lock().unwrap());
+            assert!(start.elapsed() < LOCK_TIMEOUT);
+            assert!(!lock_path.exists());
+        }
+
+        // Taken long ago, by a process that's still running
+        fs::write(&lock_path, format!("{} 0", process::id())).unwrap();
+        assert!(rf.lock_timeout(Duration::from_millis(30)).is_err());
+        assert!(lock_path.exists());
+
+        // From an older version, without an owner
+        fs::write(&lock_path, "").unwrap();
+        assert!(!is_stale(""));
+        assert!(rf.lock_timeout(Duration::from_millis(30)).is_err());
+        fs::remove_file(&lock_path).unwrap();
+    }
+
+    #[test]
+    fn test_write_submission_json_array() {
+        let dir = tempdir::TempDir::new("results_file_write_json").unwrap();
+        let file = dir.path().join("submissions.json");
+        let mut rf = ResultsFile::json_array(&file).unwrap();
+        assert_eq!(rf.format(), ResultsFormat::JsonArray);
+
+        let mut second = Submission::from_data(crate::data! { "id" => "2", "name" => "leia" });
+        second.grade = 80;
+        let mut first_late = Submission::from_data(crate::data! { "id" => "1", "name" => "luke" });
+        first_late.time = first_late.time + chrono::Duration::hours(1);
+        let first_early = Submission::from_data(crate::data! { "id" => "1", "name" => "luke" });
+
+        rf.write_submission(&second).unwrap();
+        rf.write_submission(&first_late).unwrap();
+        rf.write_submission(&first_early).unwrap();
+
+        let written = std::fs::read_to_string(&file).unwrap();
+        // Pretty printed
+        assert!(written.starts_with("[\n  {"));
+        let parsed: Vec<Submission> = serde_json::from_str(&written).unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].time, first_early.time);
+        assert_eq!(parsed[1].time, first_late.time);
+        assert_eq!(parsed[2].grade, 80);
+
+        // Keys are in a stable order
+        let data_pos = written.find("\"data\"").unwrap();
+        let grade_pos = written.find("\"grade\"").unwrap();
+        assert!(data_pos < grade_pos);
+    }
+
+    #[test]
+    fn test_write_submission_json_array_rejects_csv() {
+        let dir = tempdir::TempDir::new("results_file_json_bad").unwrap();
+        let file = dir.path().join("submissions.json");
+        std::fs::write(&file, "x,y\n1,2\n").unwrap();
+
+        let mut rf = ResultsFile::json_array(&file).unwrap();
+        assert!(rf.write_submission(&Submission::new()).is_err());
+    }
 }
@@ -0,0 +1,338 @@
+//! A server that grades submissions as they come in
+//!
+//! The dropbox only records submissions that were graded somewhere else.
+//! This grades each one against a rubric first, and sends the grade back.
+//! See [`Rubric::serve`](crate::rubric::Rubric::serve).
+
+// std uses
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+// external uses
+use anyhow::anyhow;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket_contrib::json::Json;
+use serde_json::{json, Value};
+
+// internal uses
+use crate::{Result, Rubric, Submission};
+use crate::rubric::SEED_KEY;
+use super::{DataLimits, ResultsFile, ResultsFormat, open_results_file, server_config, within_limits};
+use super::submission::entry_name;
+
+
+/// How to run a grading server, for [`Rubric::serve`](crate::rubric::Rubric::serve).
+///
+/// ```rust
+/// use rubric::dropbox::{ResultsFormat, ServerConfig};
+///
+/// let config = ServerConfig::new(8080)
+///     .format(ResultsFormat::JsonArray)
+///     .rotate(5);
+/// assert_eq!(config.results_path().to_str(), Some("submissions.json"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    port: u16,
+    format: ResultsFormat,
+    results_path: Option<PathBuf>,
+    rotate_keep: Option<usize>,
+    limits: DataLimits,
+}
+
+impl ServerConfig {
+    /// A server on the given port that writes results to `submissions.csv`
+    pub fn new(port: u16) -> Self {
+        ServerConfig {
+            port,
+            format: ResultsFormat::Csv,
+            results_path: None,
+            rotate_keep: None,
+            limits: DataLimits::default(),
+        }
+    }
+
+    /// Sets how results are stored. A JSON array goes in
+    /// `submissions.json` unless the path is set.
+    pub fn format(mut self, format: ResultsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets where results are written
+    pub fn results_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.results_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Archives the existing results file when the server starts, keeping
+    /// the newest `keep` archives. See [`ResultsFile::rotate`](crate::dropbox::ResultsFile::rotate).
+    pub fn rotate(mut self, keep: usize) -> Self {
+        self.rotate_keep = Some(keep);
+        self
+    }
+
+    /// Sets how much data a submission can have. Ones that go over are rejected
+    /// with a `413 Payload Too Large`. Defaults to [`DataLimits::default`](crate::dropbox::DataLimits).
+    pub fn limits(mut self, limits: DataLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the port the server listens on
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Returns where results are written
+    pub fn results_path(&self) -> PathBuf {
+        match (&self.results_path, self.format) {
+            (Some(path), _) => path.clone(),
+            (None, ResultsFormat::Csv) => PathBuf::from("submissions.csv"),
+            (None, ResultsFormat::JsonArray) => PathBuf::from("submissions.json"),
+        }
+    }
+}
+
+
+// A submission to grade, and where to send what happened
+type Job = (Submission, Sender<std::result::Result<Value, String>>);
+
+/// Sends submissions from the routes to the thread with the rubric.
+/// The rubric's tests can't be shared between threads, so it stays on
+/// the one that called `serve`.
+struct GradingQueue(Mutex<Sender<Job>>);
+
+/// Grades a submission and sends back the grade
+#[post("/submit", format = "application/json", data = "<submission>")]
+fn grade_submission(queue: State<GradingQueue>, limits: State<DataLimits>, submission: Json<Submission>) -> std::result::Result<Json<Value>, Custom<String>> {
+    let submission = submission.into_inner();
+    within_limits(&submission, &limits)?;
+
+    let unavailable = || Custom(Status::ServiceUnavailable, String::from("The grader isn't running"));
+    let (reply, graded) = channel();
+    queue.0.lock().expect("Lock grading queue")
+        .send((submission, reply))
+        .map_err(|_| unavailable())?;
+
+    match graded.recv() {
+        Ok(Ok(response)) => Ok(Json(response)),
+        Ok(Err(e)) => {
+            eprintln!("Error: {}", e);
+            Err(Custom(Status::InternalServerError, String::from("The submission couldn't be recorded")))
+        },
+        Err(_) => Err(unavailable())
+    }
+}
+
+// Grades each submission as it comes in, until there aren't any more senders
+fn grade_loop(mut rubric: Rubric, mut results: ResultsFile, jobs: Receiver<Job>) {
+    for (mut submission, reply) in jobs {
+        reseed(&rubric, &mut submission);
+        rubric.reset();
+        submission.grade_against(&mut rubric);
+
+        let response = match results.write_submission(&submission) {
+            Ok(()) => Ok(graded_response(&rubric, &submission)),
+            Err(e) => Err(format!("Could not write submission: {:#}", e))
+        };
+        // They might have hung up, it's still recorded
+        reply.send(response).ok();
+    }
+}
+
+// Students don't get to pick their own seed, it would pick their worths too.
+// Any seed they sent is replaced with the one for their `id`, or the rubric's
+// seed if they don't have one.
+fn reseed(rubric: &Rubric, submission: &mut Submission) {
+    submission.data.remove(SEED_KEY);
+    let seed = submission.data.get("id").and_then(|id| rubric.seed_for(id));
+    if let Some(seed) = seed {
+        submission.data.insert(SEED_KEY.to_string(), seed.to_string());
+    }
+}
+
+// What's sent back after grading. Hidden criteria are left out.
+fn graded_response(rubric: &Rubric, submission: &Submission) -> Value {
+    let shown = |entries: &[String]| -> Vec<String> {
+        entries.iter()
+            .filter(|entry| {
+                let name = entry_name(entry);
+                !rubric.criteria().iter().any(|crit| crit.hide && crit.name == name)
+            })
+            .cloned()
+            .collect()
+    };
+    json!({
+        "grade": submission.grade,
+        "total": rubric.total_points(),
+        "passed": shown(&submission.passed),
+        "failed": shown(&submission.failed),
+    })
+}
+
+
+impl Rubric {
+    /// Runs a server that grades submissions against this rubric.
+    ///
+    /// Submissions are posted as JSON to `/submit`, like the
+    /// [dropbox](crate::dropbox), but they don't need to be graded first. Each one
+    /// is graded here, written to the results file, and the response has the grade:
+    ///
+    /// ```json
+    /// { "grade": 80, "total": 100, "passed": ["Compiles (+80)"], "failed": ["Tests pass (-0)"] }
+    /// ```
+    ///
+    /// [Hidden](crate::rubric::Criterion::hide) criteria aren't in `passed` or `failed`,
+    /// but still count toward the grade. Students can't choose their own
+    /// [seed](crate::rubric::Rubric::seed): a [`SEED_KEY`](crate::rubric::SEED_KEY) in the
+    /// data is replaced with [`seed_for`](crate::rubric::Rubric::seed_for) the `id` in
+    /// the data, or the rubric's seed if there's no `id`.
+    ///
+    /// Every criterion needs a test attached first. If any don't, this returns an `Err`
+    /// right away instead of starting. See [`strict_grade`](crate::rubric::Rubric::strict_grade).
+    ///
+    /// Submissions are graded one at a time, in the order they come in. This runs until the
+    /// program is stopped, and only returns if the server couldn't start or stops on its own.
+    ///
+    /// ```no_run
+    /// # use rubric::{Rubric, TestData, yaml};
+    /// # use rubric::dropbox::ServerConfig;
+    /// fn always(_: &TestData) -> bool { true }
+    ///
+    /// let mut rubric = Rubric::from_yaml(yaml!("../../test_data/test_rubric.yml").unwrap()).unwrap();
+    /// rubric.attach_funcs(&[("first_crit", always), ("second_crit", always)]).unwrap();
+    /// rubric.serve(ServerConfig::new(8080)).unwrap();
+    /// ```
+    pub fn serve(self, config: ServerConfig) -> Result<()> {
+        let unattached = self.unattached();
+        if !unattached.is_empty() {
+            return Err(anyhow!(
+                "Not serving, these criteria don't have a test: {}",
+                unattached.join(", ")
+            ));
+        }
+
+        let results = open_results_file(&config.results_path(), config.format, config.rotate_keep)?;
+        let (jobs, queue) = channel();
+        let rocket = rocket::custom(server_config(config.port)?)
+            .manage(GradingQueue(Mutex::new(jobs)))
+            .manage(config.limits)
+            .mount("/", routes![super::return_ok, grade_submission]);
+
+        println!("Grading server is open! accepting POST requests to /submit");
+        let server = thread::spawn(move || rocket.launch());
+        // The queue closes when the server stops
+        grade_loop(self, results, queue);
+
+        match server.join() {
+            Ok(e) => Err(anyhow!("The server stopped: {}", e)),
+            Err(_) => Err(anyhow!("The server panicked"))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+    use crate::{data, TestData};
+    use crate::rubric::Criterion;
+
+    fn rubric() -> Rubric {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Has name").worth(10)
+            .test(Box::new(|data: &TestData| data.contains_key("name"))).build());
+        rubric.add(Criterion::new("Has id").worth(5)
+            .test(Box::new(|data: &TestData| data.contains_key("id"))).build());
+        rubric
+    }
+
+    #[test]
+    fn test_config() {
+        let config = ServerConfig::new(8080);
+        assert_eq!(config.port(), 8080);
+        assert_eq!(config.results_path(), PathBuf::from("submissions.csv"));
+        assert_eq!(config.results_file("out/results.csv").results_path(), PathBuf::from("out/results.csv"));
+    }
+
+    #[test]
+    fn test_reseed() {
+        let mut rubric = rubric();
+        rubric.seed = Some(42);
+
+        let mut sub = Submission::from_data(data! { "id" => "1234", SEED_KEY => "7" });
+        reseed(&rubric, &mut sub);
+        assert_eq!(sub.data[SEED_KEY], rubric.seed_for("1234").unwrap().to_string());
+
+        // Without an id, grading uses the rubric's seed
+        let mut sub = Submission::from_data(data! { SEED_KEY => "7" });
+        reseed(&rubric, &mut sub);
+        assert!(!sub.data.contains_key(SEED_KEY));
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.data[SEED_KEY], "42");
+    }
+
+    #[test]
+    fn test_response_leaves_out_hidden() {
+        let mut rubric = rubric();
+        rubric.add(Criterion::new("Secret").worth(1).hide(true)
+            .test(Box::new(|_: &TestData| true)).build());
+
+        let mut sub = Submission::from_data(data! { "name" => "luke" });
+        sub.grade_against(&mut rubric);
+        let response = graded_response(&rubric, &sub);
+
+        assert_eq!(response["grade"], 11);
+        assert_eq!(response["passed"], json!(["Has name (+10)"]));
+        assert_eq!(response["failed"], json!(["Has id (-0)"]));
+    }
+
+    #[test]
+    fn test_serve_unattached() {
+        let mut rubric = rubric();
+        rubric.add(Criterion::new("Forgotten").worth(1).build());
+        let err = rubric.serve(ServerConfig::new(0)).unwrap_err();
+        assert_eq!(err.to_string(), "Not serving, these criteria don't have a test: Forgotten (forgotten)");
+    }
+
+    #[test]
+    fn test_serve_grades_submissions() {
+        let dir = tempdir::TempDir::new("grading_server").unwrap();
+        let results = dir.path().join("submissions.csv");
+        // Find a free port
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+        let config = ServerConfig::new(port).results_file(&results);
+        // The rubric's tests can't be sent between threads, so it's made on the server's
+        thread::spawn(move || rubric().serve(config));
+
+        let url = format!("http://127.0.0.1:{}/submit", port);
+        let sub = Submission::from_data(data! { "name" => "luke" });
+        let client = reqwest::blocking::Client::new();
+        let start = Instant::now();
+        let resp = loop {
+            match client.post(&url).json(&sub).send() {
+                Ok(resp) => break resp,
+                Err(_) if start.elapsed() < Duration::from_secs(10) => thread::sleep(Duration::from_millis(50)),
+                Err(e) => panic!("Server never started: {}", e)
+            }
+        };
+
+        assert!(resp.status().is_success());
+        let body: Value = resp.json().unwrap();
+        assert_eq!(body["grade"], 10);
+        assert_eq!(body["total"], 15);
+        assert_eq!(body["passed"][0], "Has name (+10)");
+
+        let written = std::fs::read_to_string(&results).unwrap();
+        assert_eq!(written.lines().count(), 2);
+        assert!(written.contains("luke"));
+    }
+}
@@ -1,19 +1,24 @@
 //! A bundle of data that rubrics are graded against, and is submitted for review
 
 // std uses
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, Read, Write};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 // external uses
 use chrono::{DateTime, Local};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
+use anyhow::{bail, Context};
 use reqwest::blocking::Response;
+use sha2::{Digest, Sha256};
 
 // internal uses
-use crate::dropbox::results_file::AsCsv;
-use crate::rubric::Rubric;
+use crate::dropbox::results_file::{AsCsv, CsvOptions};
+use crate::rubric::{Criterion, Rubric, ResultCache, SEED_KEY};
 use crate::helpers::web;
 use crate::dropbox::fingerprint::Fingerprint;
-use crate::TIMESTAMP_FORMAT;
+use crate::{report, Error, TIMESTAMP_FORMAT};
 
 /// A type alias to `HashMap<String, String>`
 ///
@@ -28,6 +33,12 @@ fn default_timestamp_format() -> String {
     String::from(TIMESTAMP_FORMAT)
 }
 
+// Writes the data with its keys sorted, so a submission
+// always serializes the same way
+fn serialize_sorted<S: Serializer>(data: &TestData, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    data.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
 
 /// A submission is a bundle of data that represents
 /// one student's submission. They will do some sort of work
@@ -36,6 +47,7 @@ fn default_timestamp_format() -> String {
 /// a Submission to a central webserver where the instructor can
 /// collect the graded submissions.
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Submission {
     /// A local timestamp when the submission was created
     pub time: DateTime<Local>,
@@ -44,6 +56,10 @@ pub struct Submission {
     pub grade: isize,
     /// Extra data attached to the submission.
     /// Leave it empty if you don't need it
+    ///
+    /// The keys are sorted when it's serialized, so the same
+    /// submission always gives the same JSON.
+    #[serde(serialize_with = "serialize_sorted")]
     pub data: TestData,
     /// If the submission is late or not
     pub late: bool,
@@ -55,7 +71,153 @@ pub struct Submission {
     /// This uses TIMESTAMP_FORMAT from the crate root.
     #[serde(default = "default_timestamp_format")]
     timestamp_format: String,
-    fingerprint: Option<Fingerprint>
+    fingerprint: Option<Fingerprint>,
+    /// Why the grade was changed by hand, if it was.
+    ///
+    /// See [`override_grade`](crate::dropbox::submission::Submission::override_grade).
+    #[serde(default)]
+    pub override_reason: Option<String>,
+    /// The result of each criterion that was tested when grading, in the
+    /// order they were tested.
+    #[serde(default)]
+    pub results: Vec<CriterionResult>,
+    /// The [`version`](crate::rubric::Rubric::version) of the rubric this
+    /// submission was last graded against.
+    #[serde(default)]
+    pub rubric_version: Option<String>,
+    /// Overall comments from a grader, apart from the criteria's messages.
+    ///
+    /// See [`add_feedback`](crate::dropbox::submission::Submission::add_feedback).
+    #[serde(default)]
+    pub feedback: Vec<String>,
+    /// If the submission has been graded against a rubric.
+    ///
+    /// See [`is_graded`](crate::dropbox::submission::Submission::is_graded).
+    #[serde(default)]
+    pub graded: bool
+}
+
+/// How a single criterion went when a submission was graded.
+///
+/// This keeps enough about the criterion (like its tags) to report on a
+/// submission without the rubric it was graded against.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CriterionResult {
+    /// The criterion's name
+    pub name: String,
+    /// The criterion's func
+    pub func: String,
+    /// If the criterion passed
+    pub passed: bool,
+    /// The points the criterion added to the grade. This is negative
+    /// for a penalty that was applied.
+    ///
+    /// Grades are whole points, so a criterion with a fractional worth
+    /// (see [`worth_f64`](crate::rubric::Criterion::worth_f64)) awards however
+    /// much it moved the rounded grade. Two criteria worth `2.5` award `3` and
+    /// `2`. That way the awarded points always add up to the grade.
+    ///
+    /// Older results called this `points`, those still load.
+    #[serde(alias = "points")]
+    pub awarded: isize,
+    /// The points the criterion could have added, its
+    /// [`weighted_worth`](crate::rubric::Criterion::weighted_worth).
+    ///
+    /// This is rounded on its own, so with fractional worths it can be
+    /// a point off from `awarded` even when the criterion passed.
+    #[serde(default)]
+    pub possible: isize,
+    /// The criterion's tags
+    pub tags: Vec<String>,
+    /// The message shown for the result, its
+    /// [`status_message`](crate::rubric::Criterion::status_message). If it failed,
+    /// this is the failure its `outcome_test` picked, if it picked one.
+    ///
+    /// Older results don't have this.
+    #[serde(default)]
+    pub message: String,
+    /// True if the criterion wasn't run because the time budget ran out, see
+    /// [`Rubric::grade_within`](crate::rubric::Rubric::grade_within). It didn't
+    /// pass or fail, and the `message` says why.
+    #[serde(default)]
+    pub not_run: bool,
+}
+
+/// The difference between two graded submissions, usually a resubmission
+/// and the submission before it.
+///
+/// Criteria are referred to by name. You should get one of these from
+/// [`Submission::diff`](crate::dropbox::submission::Submission::diff).
+#[derive(Debug, PartialEq)]
+pub struct SubmissionDiff {
+    /// Criteria that pass now, but didn't pass before
+    pub newly_passed: Vec<String>,
+    /// Criteria that fail now, but didn't fail before
+    pub newly_failed: Vec<String>,
+    /// Criteria with the same result in both submissions
+    pub unchanged: Vec<String>,
+    /// The current grade minus the previous grade
+    pub grade_delta: isize,
+}
+
+/// Limits on how much data a submission can carry, so a client can't
+/// send enormous submissions to a server. See
+/// [`Submission::check_limits`](crate::dropbox::submission::Submission::check_limits).
+///
+/// Lengths and sizes are in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataLimits {
+    /// The most keys the data can have
+    pub max_keys: usize,
+    /// The longest any one value in the data can be
+    pub max_value_len: usize,
+    /// The biggest the whole submission can be, as JSON
+    pub max_size: usize,
+}
+
+impl Default for DataLimits {
+    /// 100 keys, values up to 10 KB, and 1 MB in total
+    fn default() -> Self {
+        DataLimits {
+            max_keys: 100,
+            max_value_len: 10_000,
+            max_size: 1_000_000,
+        }
+    }
+}
+
+// Called before each criterion is graded, see `Rubric::grade_with_progress`
+type Progress<'a> = &'a mut dyn FnMut(usize, usize, &Criterion);
+
+// Called with each result as soon as it's made, see `Rubric::resume_grade_with`
+type OnResult<'a> = &'a mut dyn FnMut(&CriterionResult);
+
+// The points a criterion gives when it passes, which might be jittered
+fn worth_for(crit: &Criterion, perturbed: &HashMap<String, isize>) -> isize {
+    perturbed.get(&crit.func).copied().unwrap_or_else(|| crit.weighted_worth())
+}
+
+// Why a criterion was skipped when the time budget ran out
+const BUDGET_EXCEEDED: &str = "not run (time budget exceeded)";
+
+// Command output to keep in the data. Newlines would break the csv
+// row, so they're flattened, and it's cut off at `limit` characters.
+fn captured(output: &str, limit: usize) -> String {
+    let flat = output.trim().lines().collect::<Vec<_>>().join(" ");
+    match flat.char_indices().nth(limit) {
+        Some((end, _)) => format!("{}...", &flat[..end]),
+        None => flat
+    }
+}
+
+// Entries in `passed` and `failed` look like "Criterion name (+10)".
+// This strips off the points so we're left with the name.
+pub(crate) fn entry_name(entry: &str) -> &str {
+    match entry.rfind(" (") {
+        Some(i) if entry.ends_with(')') => &entry[..i],
+        _ => entry
+    }
 }
 
 impl Submission {
@@ -81,7 +243,12 @@ impl Submission {
             failed: Vec::new(),
             timestamp_format: default_timestamp_format(),
             late: false,
-            fingerprint: None
+            fingerprint: None,
+            override_reason: None,
+            results: Vec::new(),
+            rubric_version: None,
+            feedback: Vec::new(),
+            graded: false
         }
     }
 
@@ -132,6 +299,52 @@ impl Submission {
         sub
     }
 
+    /// Runs a closure on the submission's data. Use this to clean up or
+    /// add to the data before grading, so the criteria don't each have to.
+    ///
+    /// Returns the submission, so it can be chained.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use rubric::{Submission, data};
+    /// let mut sub = Submission::from_data(data! { "username" => "  Luke " });
+    ///
+    /// sub.apply(|data| {
+    ///     let username = data["username"].trim().to_lowercase();
+    ///     data.insert("username".to_string(), username);
+    /// }).apply(|data| {
+    ///     let email = format!("{}@example.com", data["username"]);
+    ///     data.insert("email".to_string(), email);
+    /// });
+    ///
+    /// assert_eq!(sub.data["username"], "luke");
+    /// assert_eq!(sub.data["email"], "luke@example.com");
+    /// ```
+    pub fn apply(&mut self, f: impl FnOnce(&mut TestData)) -> &mut Self {
+        f(&mut self.data);
+        self
+    }
+
+
+    /// Returns a [JSON Schema](https://json-schema.org/) describing a
+    /// submission, as it's sent to the dropbox.
+    ///
+    /// This is for clients written in other languages, so they can check
+    /// their submissions before sending them. It needs the `schema` feature.
+    ///
+    /// ```rust
+    /// # #[cfg(feature = "schema")] {
+    /// use rubric::Submission;
+    ///
+    /// let schema = Submission::json_schema();
+    /// assert!(schema["properties"]["data"].is_object());
+    /// # }
+    /// ```
+    #[cfg(feature = "schema")]
+    pub fn json_schema() -> serde_json::Value {
+        serde_json::to_value(schemars::schema_for!(Submission))
+            .expect("Couldn't serialize the submission schema")
+    }
 
     /// Creates a fingerprint based on the provided secret key.
     ///
@@ -163,18 +376,350 @@ impl Submission {
         self.failed.push(format!("{} (-{})", message, to_penalize));
     }
 
+    /// Clears the grade, passed and failed criteria, results, rubric version, late flag,
+    /// and any override reason, so the submission can be graded again. The data is kept.
+    ///
+    /// The submission isn't [`graded`](crate::dropbox::submission::Submission::is_graded) after this.
+    pub fn reset_grade(&mut self) {
+        self.graded = false;
+        self.grade = 0;
+        self.passed.clear();
+        self.failed.clear();
+        self.late = false;
+        self.override_reason = None;
+        self.results.clear();
+        self.rubric_version = None;
+    }
+
+    /// Returns true if the submission has been graded against a rubric.
+    ///
+    /// A new submission isn't graded, so its grade of 0 doesn't mean anything
+    /// yet. Grading it in any way (like [`grade_against`](crate::dropbox::submission::Submission::grade_against))
+    /// marks it graded, even if grading stopped early because it was past a deadline.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission};
+    /// let mut sub = Submission::new();
+    /// assert!(!sub.is_graded());
+    ///
+    /// sub.grade_against(&mut Rubric::default());
+    /// assert!(sub.is_graded());
+    /// ```
+    pub fn is_graded(&self) -> bool {
+        self.graded
+    }
+
+    /// Returns an `Err` if the submission hasn't been graded.
+    ///
+    /// Use it before reporting or saving a submission, so an ungraded one
+    /// doesn't show up as a grade of 0. See [`is_graded`](crate::dropbox::submission::Submission::is_graded).
+    ///
+    /// ```rust
+    /// # use rubric::Submission;
+    /// let sub = Submission::new();
+    /// assert!(sub.assert_graded().is_err());
+    /// ```
+    pub fn assert_graded(&self) -> Result<(), Error> {
+        if !self.graded {
+            bail!("The submission hasn't been graded yet");
+        }
+        Ok(())
+    }
+
+    /// Returns an `Err` saying what's too big if the submission goes over any of the limits.
+    ///
+    /// The dropbox and the grading server check these before recording a
+    /// submission, and reject it if it's too big.
+    ///
+    /// ```rust
+    /// # use rubric::{Submission, data};
+    /// # use rubric::dropbox::DataLimits;
+    /// let limits = DataLimits { max_keys: 1, ..DataLimits::default() };
+    ///
+    /// assert!(Submission::from_data(data! { "name" => "luke" }).check_limits(&limits).is_ok());
+    ///
+    /// let sub = Submission::from_data(data! { "name" => "luke", "id" => "1234" });
+    /// assert_eq!(
+    ///     sub.check_limits(&limits).unwrap_err().to_string(),
+    ///     "The submission has 2 data keys, the limit is 1"
+    /// );
+    /// ```
+    pub fn check_limits(&self, limits: &DataLimits) -> Result<(), Error> {
+        if self.data.len() > limits.max_keys {
+            bail!("The submission has {} data keys, the limit is {}", self.data.len(), limits.max_keys);
+        }
+
+        // The first in order, so it's the same one every time
+        let too_long = self.data.iter()
+            .filter(|(_, value)| value.len() > limits.max_value_len)
+            .min_by_key(|(key, _)| key.as_str());
+        if let Some((key, value)) = too_long {
+            bail!("The value of `{}` is {} bytes, the limit is {}", key, value.len(), limits.max_value_len);
+        }
+
+        let size = serde_json::to_vec(self)?.len();
+        if size > limits.max_size {
+            bail!("The submission is {} bytes as JSON, the limit is {}", size, limits.max_size);
+        }
+        Ok(())
+    }
+
+    /// The submission's results in a table, for reading in a terminal.
+    ///
+    /// There's a row for each criterion in the rubric, with its worth, the points
+    /// it gave, and its status, and the grade is under the table. Criteria that
+    /// weren't tested show a dash. Hidden criteria are left out, set their
+    /// [`hide`](crate::rubric::Criterion::hide) to `false` to show them.
+    ///
+    /// It uses the current [`symbols`](crate::report::symbols) and
+    /// [`labels`](crate::report::labels). The statuses are colored unless
+    /// [`no_color`](crate::report::no_color), and the box is ASCII if the symbols are.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, TestData};
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Compiles").worth(30).test(Box::new(|_: &TestData| true)).build());
+    ///
+    /// let mut sub = Submission::new();
+    /// sub.grade_against(&mut rubric);
+    /// println!("{}", sub.as_table(&rubric));
+    /// // ┌───────────┬───────┬─────────┬────────┐
+    /// // │ Criterion │ Worth │ Awarded │ Status │
+    /// // ├───────────┼───────┼─────────┼────────┤
+    /// // │ Compiles  │ 30    │ 30      │ ✓      │
+    /// // └───────────┴───────┴─────────┴────────┘
+    /// // Grade: 30/30 (100%)
+    /// ```
+    pub fn as_table(&self, rubric: &Rubric) -> String {
+        let symbols = report::symbols();
+        let rows: Vec<Vec<(String, Option<&str>)>> = rubric.in_grading_order().into_iter()
+            .filter(|crit| !crit.hide)
+            .map(|crit| {
+                let result = self.results.iter().find(|r| r.func == crit.func);
+                let (awarded, status) = match result {
+                    Some(r) if r.not_run => (String::from("—"), (symbols.skip.clone(), None)),
+                    Some(r) if r.passed => (r.awarded.to_string(), (symbols.pass.clone(), Some("green"))),
+                    Some(r) => (r.awarded.to_string(), (symbols.fail.clone(), Some("red"))),
+                    None if crit.skipped => (String::from("—"), (symbols.skip.clone(), None)),
+                    None => (String::from("—"), (String::from("—"), None)),
+                };
+                vec![(crit.name.clone(), None), (crit.shown_worth().to_string(), None), (awarded, None), status]
+            })
+            .collect();
+
+        let mut table = report::boxed_table(&["Criterion", "Worth", "Awarded", "Status"], &rows);
+        let total = rubric.total_points();
+        table.push_str(&format!(
+            "{}: {}/{} ({}%){}\n",
+            report::labels().grade, self.grade, total,
+            report::percent_of(self.grade, total), report::bonus_note(rubric.bonus_total())
+        ));
+        table
+    }
+
+    /// Returns true if the submission was graded against a different version
+    /// of the rubric than the one given, meaning it might need to be regraded.
+    ///
+    /// If the rubric doesn't have a version, this is always false.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission};
+    /// let mut rubric = Rubric::default();
+    /// rubric.version = Some(String::from("1.0"));
+    ///
+    /// let mut sub = Submission::new();
+    /// sub.grade_against(&mut rubric);
+    /// assert!(!sub.graded_with_older(&rubric));
+    ///
+    /// rubric.version = Some(String::from("1.1"));
+    /// assert!(sub.graded_with_older(&rubric));
+    /// ```
+    pub fn graded_with_older(&self, rubric: &Rubric) -> bool {
+        rubric.version.is_some() && self.rubric_version != rubric.version
+    }
+
+    /// How long after the rubric's deadline this submission was made, by its `time`.
+    ///
+    /// This is negative if it was made before the deadline, and `None` if
+    /// the rubric doesn't have a deadline.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission};
+    /// use chrono::{Duration, Local};
+    ///
+    /// let mut rubric = Rubric::default();
+    /// let mut sub = Submission::new();
+    /// assert!(sub.time_since_deadline(&rubric).is_none());
+    ///
+    /// rubric.deadline = Some(Local::now());
+    /// sub.time = rubric.deadline.unwrap() + Duration::minutes(192);
+    /// let late = sub.time_since_deadline(&rubric).unwrap();
+    /// assert_eq!((late.num_hours(), late.num_minutes() % 60), (3, 12));
+    /// ```
+    pub fn time_since_deadline(&self, rubric: &Rubric) -> Option<chrono::Duration> {
+        rubric.deadline.map(|deadline| self.time.signed_duration_since(deadline))
+    }
+
+    /// Sets the grade by hand, and records why.
+    ///
+    /// Use this when a grade changes after an appeal or a manual review. The
+    /// reason is kept in `override_reason`, and is written with the submission
+    /// in both JSON and CSV, so the change can be audited later.
+    ///
+    /// ```rust
+    /// # use rubric::Submission;
+    /// let mut sub = Submission::new();
+    /// sub.override_grade(85, "Regraded after appeal");
+    ///
+    /// assert_eq!(sub.grade, 85);
+    /// assert_eq!(sub.override_reason.as_deref(), Some("Regraded after appeal"));
+    /// assert!(sub.is_graded());
+    /// ```
+    pub fn override_grade(&mut self, grade: isize, reason: &str) {
+        self.grade = grade;
+        self.graded = true;
+        self.override_reason = Some(String::from(reason));
+    }
+
+    /// The header for [`to_gradebook_row`](crate::dropbox::submission::Submission::to_gradebook_row),
+    /// like `"id,Lab 1"`.
+    ///
+    /// Gradebook imports (like Canvas or Moodle) want one column for the student
+    /// and one column per assignment, named the way the gradebook names them.
+    pub fn gradebook_header(student_col: &str, assignment_col: &str) -> String {
+        CsvOptions::default().row(&[String::from(student_col), String::from(assignment_col)])
+    }
+
+    /// A CSV row for importing the grade into a gradebook, under the header from
+    /// [`gradebook_header`](crate::dropbox::submission::Submission::gradebook_header).
+    ///
+    /// The row is the student's id, then the grade. The id is the value of
+    /// `student_col` in the submission's data, like `"id"`, so the column and the
+    /// data key have the same name. If the data doesn't have it, the id is empty.
+    /// The assignment's column is only named in the header, the grade goes under
+    /// it whatever it's called, so the row doesn't need it.
+    ///
+    /// ```rust
+    /// # use rubric::{Submission, data};
+    /// let mut sub = Submission::from_data(data! { "id" => "1234" });
+    /// sub.grade = 85;
+    ///
+    /// assert_eq!(Submission::gradebook_header("id", "Lab 1"), "id,Lab 1");
+    /// assert_eq!(sub.to_gradebook_row("id"), "1234,85");
+    /// ```
+    pub fn to_gradebook_row(&self, student_col: &str) -> String {
+        let id = self.data.get(student_col).cloned().unwrap_or_default();
+        CsvOptions::default().row(&[id, self.grade.to_string()])
+    }
+
+    /// Adds a comment about the submission as a whole, like
+    /// "Great structure, watch your error handling".
+    ///
+    /// Feedback is written with the submission in both JSON and CSV, and is
+    /// shown at the end of [`render_artifact`](crate::report::render_artifact),
+    /// [`GradeReport`](crate::report::GradeReport), and
+    /// [`report::short_for`](crate::report::short_for) and [`long_for`](crate::report::long_for).
+    /// It's about the student's work, not the grade, so
+    /// [`reset_grade`](crate::dropbox::submission::Submission::reset_grade) keeps it.
+    ///
+    /// ```rust
+    /// # use rubric::Submission;
+    /// let mut sub = Submission::new();
+    /// sub.add_feedback("Great structure");
+    /// sub.add_feedback("Watch your error handling");
+    ///
+    /// assert_eq!(sub.feedback.len(), 2);
+    /// ```
+    pub fn add_feedback<S: AsRef<str>>(&mut self, msg: S) {
+        self.feedback.push(String::from(msg.as_ref()));
+    }
+
     /// Tests a submission against a list of criterion
+    ///
+    /// Any earlier grading is cleared first with
+    /// [`reset_grade`](crate::dropbox::submission::Submission::reset_grade), so grading
+    /// the same submission twice gives the same grade instead of doubling it.
+    ///
+    /// If the rubric has a [`seed`](crate::rubric::Rubric::seed), it's added to the
+    /// data under [`SEED_KEY`](crate::rubric::SEED_KEY) first, unless the data already
+    /// has one.
     pub fn grade_against(&mut self, rubric: &mut Rubric) {
+        self.grade_with(rubric, Local::now(), None, None, None, None);
+    }
+
+    /// Same as [`grade_against`](crate::dropbox::submission::Submission::grade_against),
+    /// but async tests (see
+    /// [`CriterionBuilder::test_async`](crate::rubric::CriterionBuilder::test_async))
+    /// are awaited first. They're awaited one at a time, in no particular order.
+    ///
+    /// This doesn't need any particular runtime. Only available with the `async` feature.
+    #[cfg(feature = "async")]
+    pub async fn grade_against_async(&mut self, rubric: &mut Rubric) {
+        // The async tests see the data the same way the others will
+        let mut data = self.data.clone();
+        if let Some(seed) = rubric.seed {
+            data.entry(SEED_KEY.to_string()).or_insert_with(|| seed.to_string());
+        }
+
+        for crit in rubric.criteria.iter_mut() {
+            if !crit.applies_to(&data) || !crit.missing_data(&data).is_empty() {
+                continue;
+            }
+            if let Some(test) = &crit.async_test {
+                crit.awaited = Some(test(&data).await);
+            }
+        }
+
+        self.grade_against(rubric);
+
+        // Grading can stop early (like past the deadline), don't
+        // leave results around for the next time
+        for crit in rubric.criteria.iter_mut() {
+            crit.awaited = None;
+        }
+    }
+
+    // Grades the submission like `grade_against`. If there's a budget and it runs
+    // out, the rest of the criteria aren't tested. Returns the names of those criteria.
+    // If there's a cache, results are taken from it when they can be, and added to it
+    // when they can't. If there's a progress callback, it's called before each criterion.
+    // Lateness is judged as if it were graded at `at`.
+    pub(crate) fn grade_with(
+        &mut self,
+        rubric: &mut Rubric,
+        at: DateTime<Local>,
+        budget: Option<Duration>,
+        mut cache: Option<&mut ResultCache>,
+        mut progress: Option<Progress>,
+        mut on_result: Option<OnResult>
+    ) -> Vec<String> {
+        let start = Instant::now();
+        let mut not_run = Vec::new();
+        self.reset_grade();
+        self.graded = true;
+        self.rubric_version = rubric.version.clone();
+
+        if let Some(seed) = rubric.seed {
+            self.data.entry(SEED_KEY.to_string()).or_insert_with(|| seed.to_string());
+        }
+
+        // What each criterion is worth for this student, if the worths are jittered
+        let perturbed = match self.data.get(SEED_KEY).and_then(|seed| seed.parse().ok()) {
+            Some(seed) => rubric.perturbed_worths(seed),
+            None => HashMap::new()
+        };
+
         // Penalties
-        if rubric.past_final_deadline() {
+        if rubric.past_final_deadline_at(at) {
             eprintln!("Final deadline ({}) has passed.", rubric.final_deadline.unwrap());
             eprintln!("Your instructor has chosen to not allow late submission");
             eprintln!("This submission will be recorded, but with a grade of 0");
             self.penalty(self.grade, "Past final deadline");
-            return;
+            return not_run;
         }
 
-        if rubric.past_due() {
+        if rubric.past_due_at(at) {
             // Submission is late, mark it as such
             self.late = true;
 
@@ -186,7 +731,7 @@ impl Submission {
             // exactly 24 hours + 1 second after the deadline is 2 days.
             let how_late = rubric.deadline
                 .unwrap()
-                .signed_duration_since(Local::now())
+                .signed_duration_since(at)
                 .num_days()
                 .abs() + 1;
             let daily_penalty = rubric.daily_penalty * how_late as isize;
@@ -200,110 +745,771 @@ impl Submission {
                 eprintln!("This submission will be recorded, but with a grade of 0");
                 // Penalize 100% of the points and return
                 self.penalty(self.grade, "Past deadline");
-                return;
+                return not_run;
             }
 
 
         }
 
+        // The data changes while grading, so cache on what it started as
+        let data_hash = cache.as_ref().map(|_| self.data_hash());
+
         // Additions
-        for crit in &mut rubric.sorted().into_iter() {
-            if crit.test_with_data(&self.data) {
-                self.addition(crit.worth, &crit.name);
+        let total = rubric.len();
+        let output_limit = rubric.output_limit;
+        // Fractional worths are added up and rounded as they go. Each one awards
+        // however much that moves the rounded sum, so the awarded points add up to
+        // the grade, which is the same as rounding once at the end.
+        let mut exact = 0.0_f64;
+        for (i, crit) in rubric.sorted().iter_mut().enumerate() {
+            if let Some(progress) = progress.as_deref_mut() {
+                progress(i, total, crit);
+            }
+
+            // Out of time, leave the rest untested
+            if matches!(budget, Some(b) if start.elapsed() >= b) {
+                crit.skip_because(BUDGET_EXCEEDED);
+                let result = CriterionResult {
+                    name: crit.name.clone(),
+                    func: crit.func.clone(),
+                    passed: false,
+                    awarded: 0,
+                    possible: worth_for(crit, &perturbed),
+                    tags: crit.tags.clone(),
+                    message: crit.skip_reason(),
+                    not_run: true,
+                };
+                self.push_result(result, &mut on_result);
+                not_run.push(crit.name.clone());
+                continue;
+            }
+
+            // Criteria that don't apply aren't passed or failed
+            if !crit.applies_to(&self.data) {
+                crit.skip();
+                continue;
+            }
+
+            // Neither are criteria without the data they need
+            let missing = crit.missing_data(&self.data);
+            if !missing.is_empty() {
+                let reason = format!("missing data: {}", missing.join(", "));
+                crit.skip_because(&reason);
+                continue;
+            }
+
+            if !crit.has_valid_weight() {
+                eprintln!(
+                    "Warning: criterion '{}' has an invalid weight ({}), it won't add any points",
+                    crit.name, crit.weight.unwrap()
+                );
+            } else if crit.is_clamped() {
+                eprintln!(
+                    "Warning: criterion '{}' has a weight of {}, it's clamped to its worth ({})",
+                    crit.name, crit.weight.unwrap(), crit.points_possible()
+                );
+            }
+
+            let passed = match (cache.as_deref_mut(), &data_hash) {
+                (Some(cache), Some(hash)) => match cache.get(&crit.func, hash) {
+                    Some(passed) => {
+                        crit.reset();
+                        crit.status = Some(passed);
+                        passed
+                    },
+                    None => {
+                        let passed = crit.test_with_data(&self.data);
+                        cache.insert(&crit.func, hash, passed);
+                        passed
+                    }
+                },
+                _ => crit.test_with_data(&self.data)
+            };
+            let awarded = match (passed, crit.worth_f64) {
+                (false, _) => 0,
+                (true, Some(_)) => {
+                    let before = exact.round();
+                    exact += crit.exact_worth();
+                    (exact.round() - before) as isize
+                },
+                (true, None) => worth_for(crit, &perturbed)
+            };
+            if passed {
+                self.addition(awarded, &crit.name);
             } else {
                 // Failing a criteria just means +0 points
                 self.penalty(0, &crit.name);
             }
+            let result = CriterionResult {
+                name: crit.name.clone(),
+                func: crit.func.clone(),
+                passed,
+                awarded,
+                possible: worth_for(crit, &perturbed),
+                tags: crit.tags.clone(),
+                message: crit.status_message(),
+                not_run: false,
+            };
+            self.push_result(result, &mut on_result);
+
+            // Keep what a command criterion did for reporting
+            if let Some(result) = &crit.command_result {
+                let exit_code = result.exit_code.map_or(String::new(), |c| c.to_string());
+                self.data.insert(format!("{}_stdout", crit.func), captured(&result.stdout, output_limit));
+                self.data.insert(format!("{}_stderr", crit.func), captured(&result.stderr, output_limit));
+                self.data.insert(format!("{}_exit_code", crit.func), exit_code);
+            }
         }
+
+        not_run
     }
 
-    /// Posts the submission to the URL in JSON format. Meant to be sent
-    /// to a dropbox. Really just calls [`helpers::web::post_json`](rubric::helpers::web::post_json).
-    pub fn submit(&self, url: &str) -> Result<Response, reqwest::Error> {
-        web::post_json(url, self)
+    // Adds a result, after handing it to the callback if there is one
+    fn push_result(&mut self, result: CriterionResult, on_result: &mut Option<OnResult>) {
+        if let Some(on_result) = on_result {
+            on_result(&result);
+        }
+        self.results.push(result);
     }
 
-    /// Overrides the default timestamp format.
-    /// The default is `%F %a %T %:z` which gives
-    /// ```text
-    /// 2001-08-04 Thu 00:34:60 +09:30
+    /// The points earned for each tag, from the submission's `results`.
+    ///
+    /// Every tag on a criterion that was tested is in the map, even if
+    /// none of its criteria passed. A criterion with more than one tag
+    /// counts toward each of them. Since this only uses the submission, it
+    /// works on submissions read back from JSON without the rubric.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, TestData};
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Formatted").worth(5).tag("style").test(Box::new(|_: &TestData| true)).build());
+    /// rubric.add(Criterion::new("Tests pass").worth(20).tag("tests").test(Box::new(|_: &TestData| false)).build());
+    ///
+    /// let mut sub = Submission::new();
+    /// sub.grade_against(&mut rubric);
+    ///
+    /// let points = sub.points_by_tag();
+    /// assert_eq!(points["style"], 5);
+    /// assert_eq!(points["tests"], 0);
     /// ```
-    /// This timestamp format is human readable and also sortable in a spreadsheet.
-    pub fn set_timestamp_format(&mut self, new_format: &str) {
-        self.timestamp_format = String::from(new_format);
+    pub fn points_by_tag(&self) -> HashMap<String, isize> {
+        let mut points = HashMap::new();
+        for result in self.results.iter().filter(|r| !r.not_run) {
+            for tag in &result.tags {
+                *points.entry(tag.clone()).or_insert(0) += result.awarded;
+            }
+        }
+        points
     }
-}
 
-impl AsCsv for TestData {
-    /// Returns the test data, serialized to a csv string. It will be
-    /// sorted alphabetically by key.
-    fn as_csv(&self) -> String {
-        let mut v: Vec<_> = self.into_iter().collect();
-        v.sort_by(|x,y| x.0.cmp(&y.0));
-        v.iter().map(|v| v.1.replace(",", ";") ).collect::<Vec<_>>().join(",")
+    /// Each tested criterion's name, the points it added, and the points it could
+    /// have added, in the order they were graded.
+    ///
+    /// This comes from the submission's `results`, so skipped criteria aren't in
+    /// it. Without late penalties or an override, the awarded points add up to
+    /// the grade.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, TestData};
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Compiles").worth(10).test(Box::new(|_: &TestData| true)).build());
+    /// rubric.add(Criterion::new("Tests pass").worth(20).test(Box::new(|_: &TestData| false)).build());
+    ///
+    /// let mut sub = Submission::new();
+    /// sub.grade_against(&mut rubric);
+    ///
+    /// assert_eq!(sub.grade_breakdown(), vec![
+    ///     (String::from("Compiles"), 10, 10),
+    ///     (String::from("Tests pass"), 0, 20),
+    /// ]);
+    /// ```
+    pub fn grade_breakdown(&self) -> Vec<(String, isize, isize)> {
+        self.results.iter()
+            .filter(|r| !r.not_run)
+            .map(|r| (r.name.clone(), r.awarded, r.possible))
+            .collect()
     }
 
-    /// Returns the filename that the [`ResultsFile`](crate::results_file::ResultsFile)
-    /// uses as its output
+    /// Returns true if the criterion with the given name is in the
+    /// submission's `passed` list.
     ///
-    /// This probably shouldn't get used for test data, as it will be written as part
-    /// of a submission, not on it's own.
-    fn filename(&self) -> String {
-        String::from("submission_data.csv")
+    /// ```rust
+    /// # use rubric::Submission;
+    /// let mut sub = Submission::new();
+    /// sub.passed.push(String::from("Git installed (+10)"));
+    ///
+    /// assert!(sub.has_passed("Git installed"));
+    /// assert!(!sub.has_passed("Git init"));
+    /// ```
+    pub fn has_passed(&self, name: &str) -> bool {
+        self.passed.iter().any(|e| entry_name(e) == name)
     }
 
-    /// Returns a header to write to a csv file. This should match the fields in `as_csv` above.
-    fn header(&self) -> String {
-        // let keys: Vec<&String> = self.keys().collect();
-        // let mut owned_keys: Vec<String> = keys.iter().map(|&k| k.to_owned() ).collect();
-        // owned_keys.sort_by(|a,b| a.cmp(&b) );
-        // return format!("{}", owned_keys.join(","));
-        let mut v: Vec<_> = self.into_iter().collect();
-        v.sort_by(|x,y| x.0.cmp(&y.0));
-        v.iter().map(|v| v.0.to_owned() ).collect::<Vec<_>>().join(",")
+    /// The submission's grade as a percentage (0 - 100) of the rubric's total points.
+    ///
+    /// If the rubric has a total of zero, this is `0.0`.
+    pub fn grade_percentage(&self, rubric: &Rubric) -> f64 {
+        let total = rubric.total_points_f64();
+        if total == 0.0 {
+            return 0.0;
+        }
+        self.grade as f64 / total * 100.0
     }
-}
-
-impl AsCsv for Submission {
-    /// Returns the submission's values in csv format. The `TestData` atttached will be
-    /// sorted alphabetically by key.
-    fn as_csv(&self) -> String {
-        let mut csv = format!(
-            "{},{},{},{},{},{}",
-            self.time.format(&self.timestamp_format),
-            self.late,
-            self.grade,
-            self.passed.join(";"),
-            self.failed.join(";"),
-            self.data.as_csv()
-        );
 
-        if let Some(fp) = &self.fingerprint {
-            csv = format!("{},{}", csv, fp.as_csv());
+    /// Returns true if the submission passed the rubric as a whole.
+    ///
+    /// A submission passes if its grade percentage is at least the rubric's
+    /// [`pass_threshold`](crate::rubric::Rubric::pass_threshold), and it didn't
+    /// fail any `required` criteria. If the rubric doesn't have a threshold, only
+    /// the required criteria are checked.
+    ///
+    /// Grade the submission before calling this.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, TestData};
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.pass_threshold = Some(50.0);
+    /// rubric.add(Criterion::new("Works").worth(10).test(Box::new(|_: &TestData| true)).build());
+    ///
+    /// let mut sub = Submission::new();
+    /// sub.grade_against(&mut rubric);
+    /// assert!(sub.passed_rubric(&rubric));
+    /// ```
+    pub fn passed_rubric(&self, rubric: &Rubric) -> bool {
+        let failed_required = rubric.criteria().iter()
+            .filter(|c| c.required)
+            .any(|c| self.failed.iter().any(|e| entry_name(e) == c.name));
+        if failed_required {
+            return false;
         }
 
-        csv
+        match rubric.pass_threshold {
+            Some(threshold) => self.grade_percentage(rubric) >= threshold,
+            None => true
+        }
     }
 
-    /// Returns the filename to use when writing submissions to disk
-    fn filename(&self) -> String {
-        String::from("submissions.csv")
+    /// Returns true if the criterion with the given name is in the
+    /// submission's `failed` list.
+    pub fn has_failed(&self, name: &str) -> bool {
+        self.failed.iter().any(|e| entry_name(e) == name)
     }
 
-    /// Returns a header of all the fields, matching the data in `as_csv`
-    fn header(&self) -> String {
-        let mut header = format!("time,late,grade,passed,failed,{}", self.data.header());
-        if let Some(fp) = &self.fingerprint {
-            header = format!("{},{}", header, fp.header());
+    /// A SHA-256 hash of the submission's data, as a hex string.
+    ///
+    /// Only the `data` is hashed. The keys are sorted first, so two submissions
+    /// with the same data always have the same hash, no matter what order it was
+    /// inserted in. This is a cheap way to find identical submissions.
+    ///
+    /// ```rust
+    /// # use rubric::{Submission, data};
+    /// let first = Submission::from_data(data! { "a" => "1", "b" => "2" });
+    /// let second = Submission::from_data(data! { "b" => "2", "a" => "1" });
+    ///
+    /// assert_eq!(first.data_hash(), second.data_hash());
+    /// ```
+    pub fn data_hash(&self) -> String {
+        let mut pairs: Vec<_> = self.data.iter().collect();
+        pairs.sort();
+
+        let mut hasher = Sha256::new();
+        for (key, value) in pairs {
+            // Length prefixes so ("ab", "c") and ("a", "bc") hash differently
+            hasher.update(format!("{}:{}{}:{}", key.len(), key, value.len(), value));
+        }
+        let mut hex = String::new();
+        for byte in hasher.finalize() {
+            hex.push_str(&format!("{:02x}", byte));
         }
-        header
+        hex
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Compares this submission against a previous one.
+    ///
+    /// This is meant for resubmissions, so you can show a student
+    /// which criteria they fixed and which ones they broke.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use rubric::Submission;
+    /// let mut previous = Submission::new();
+    /// previous.failed.push(String::from("Git installed (-0)"));
+    ///
+    /// let mut current = Submission::new();
+    /// current.grade = 10;
+    /// current.passed.push(String::from("Git installed (+10)"));
+    ///
+    /// let diff = current.diff(&previous);
+    /// assert_eq!(diff.newly_passed, vec!["Git installed"]);
+    /// assert_eq!(diff.grade_delta, 10);
+    /// ```
+    pub fn diff(&self, previous: &Submission) -> SubmissionDiff {
+        let prev_passed: Vec<&str> = previous.passed.iter().map(|e| entry_name(e)).collect();
+        let prev_failed: Vec<&str> = previous.failed.iter().map(|e| entry_name(e)).collect();
+
+        let mut diff = SubmissionDiff {
+            newly_passed: Vec::new(),
+            newly_failed: Vec::new(),
+            unchanged: Vec::new(),
+            grade_delta: self.grade - previous.grade,
+        };
+
+        for name in self.passed.iter().map(|e| entry_name(e)) {
+            if prev_passed.contains(&name) {
+                diff.unchanged.push(String::from(name));
+            } else {
+                diff.newly_passed.push(String::from(name));
+            }
+        }
+
+        for name in self.failed.iter().map(|e| entry_name(e)) {
+            if prev_failed.contains(&name) {
+                diff.unchanged.push(String::from(name));
+            } else {
+                diff.newly_failed.push(String::from(name));
+            }
+        }
+
+        diff
+    }
+
+    /// Posts the submission to the URL in JSON format. Meant to be sent
+    /// to a dropbox. The dropbox doesn't send back a body, so this returns
+    /// the whole response instead of deserializing it like
+    /// [`helpers::web::post_json`](crate::helpers::web::post_json) does.
+    pub fn submit(&self, url: &str) -> Result<Response, reqwest::Error> {
+        web::send_json(url, self)
+    }
+
+    /// Overrides the default timestamp format.
+    /// The default is `%F %a %T %:z` which gives
+    /// ```text
+    /// 2001-08-04 Thu 00:34:60 +09:30
+    /// ```
+    /// This timestamp format is human readable and also sortable in a spreadsheet.
+    pub fn set_timestamp_format(&mut self, new_format: &str) {
+        self.timestamp_format = String::from(new_format);
+    }
+
+    /// Reads a submission back from a row of a CSV results file, the opposite of
+    /// [`as_csv_with`](crate::dropbox::AsCsv::as_csv_with). Both the header and the
+    /// row should already be split with the same options they were written with.
+    ///
+    /// Columns are matched by name, and any column that isn't one of the submission's
+    /// own fields goes in the data. If the last two columns are `secret` and `platform`,
+    /// they're the fingerprint. The time has to be in the default timestamp format.
+    ///
+    /// The `results` aren't written to CSV, so they'll be empty. Neither is whether it was
+    /// [`graded`](crate::dropbox::submission::Submission::is_graded), but the dropbox marks
+    /// everything it records as graded, so it's `true`. The names in `passed`
+    /// and `failed` are only changed if there was a delimiter in them and the options
+    /// don't quote.
+    ///
+    /// ```rust
+    /// # use rubric::{Submission, data};
+    /// use rubric::dropbox::{AsCsv, CsvOptions};
+    ///
+    /// let mut sub = Submission::from_data(data! { "name" => "luke" });
+    /// sub.grade = 10;
+    ///
+    /// let options = CsvOptions::default();
+    /// let header = options.split(&sub.header());
+    /// let row = options.split(&sub.as_csv());
+    ///
+    /// let read = Submission::from_csv_row(&header, &row, &options).unwrap();
+    /// assert_eq!(read.grade, 10);
+    /// assert!(read.is_graded());
+    /// assert_eq!(read.data["name"], "luke");
+    /// ```
+    pub fn from_csv_row(header: &[String], row: &[String], options: &CsvOptions) -> Result<Submission, Error> {
+        if header.len() != row.len() {
+            bail!("The row has {} values, but the header has {}", row.len(), header.len());
+        }
+
+        let list = |value: &str| options.split_list(value);
+        let optional = |value: &str| -> Option<String> {
+            if value.is_empty() { None } else { Some(value.to_owned()) }
+        };
+
+        let mut columns = header.len();
+        let mut sub = Submission::new();
+        sub.graded = true;
+        if header.ends_with(&[String::from("secret"), String::from("platform")]) {
+            columns -= 2;
+            sub.fingerprint = Some(Fingerprint {
+                secret: row[columns].clone(),
+                platform: row[columns + 1].clone()
+            });
+        }
+
+        for (key, value) in header.iter().zip(row).take(columns) {
+            match key.as_str() {
+                "time" => {
+                    sub.time = DateTime::parse_from_str(value, TIMESTAMP_FORMAT)
+                        .context(format!("Couldn't read the time `{}`", value))?
+                        .with_timezone(&Local);
+                },
+                "late" => sub.late = value.parse().context(format!("Couldn't read late `{}`", value))?,
+                "grade" => sub.grade = value.parse().context(format!("Couldn't read the grade `{}`", value))?,
+                "passed" => sub.passed = list(value),
+                "failed" => sub.failed = list(value),
+                "override_reason" => sub.override_reason = optional(value),
+                "rubric_version" => sub.rubric_version = optional(value),
+                "feedback" => sub.feedback = list(value),
+                // Submissions without data have an empty column
+                "" => {},
+                _ => { sub.data.insert(key.clone(), value.clone()); }
+            }
+        }
+
+        Ok(sub)
+    }
+
+    /// Reads one submission as JSON from a reader, like a file or stdin.
+    ///
+    /// To read many submissions, one per line, use [`read_submissions`](crate::dropbox::read_submissions).
+    ///
+    /// ```rust
+    /// use rubric::Submission;
+    ///
+    /// let json = serde_json::to_string(&Submission::new()).unwrap();
+    /// let sub = Submission::from_reader(json.as_bytes()).unwrap();
+    /// assert!(!sub.is_graded());
+    /// ```
+    pub fn from_reader<R: Read>(reader: R) -> Result<Submission, Error> {
+        serde_json::from_reader(reader).context("Couldn't parse a submission from JSON")
+    }
+
+    /// Writes the submission as one line of JSON, ending with a newline.
+    ///
+    /// Writing several submissions to the same writer gives
+    /// [JSON Lines](https://jsonlines.org/), which [`read_submissions`](crate::dropbox::read_submissions)
+    /// reads back one at a time. Nothing is buffered here, wrap the writer in
+    /// a `BufWriter` when writing a lot of them.
+    ///
+    /// ```rust
+    /// # use rubric::{Submission, data};
+    /// let mut out = Vec::new();
+    /// Submission::from_data(data! { "name" => "luke" }).to_writer(&mut out).unwrap();
+    /// Submission::from_data(data! { "name" => "leia" }).to_writer(&mut out).unwrap();
+    ///
+    /// assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+    /// ```
+    pub fn to_writer<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        serde_json::to_writer(&mut *writer, self).context("Couldn't write the submission")?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// Reads submissions from [JSON Lines](https://jsonlines.org/), one submission per line.
+///
+/// Lines are read one at a time as the iterator is used, so the whole input
+/// is never in memory. Blank lines are skipped. A line that isn't a submission
+/// gives an `Err` saying which line it was, and reading can carry on after it.
+/// Pair this with [`Submission::to_writer`](crate::dropbox::Submission::to_writer)
+/// to pipe submissions through a program.
+///
+/// ```no_run
+/// use std::io::{stdin, stdout};
+/// # use rubric::{Rubric, yaml};
+/// use rubric::dropbox::read_submissions;
+///
+/// # let mut rubric = Rubric::from_yaml(yaml!("../../test_data/test_rubric.yml").unwrap()).unwrap();
+/// let mut out = stdout();
+/// for sub in read_submissions(stdin().lock()) {
+///     let mut sub = sub.unwrap();
+///     sub.grade_against(&mut rubric);
+///     sub.to_writer(&mut out).unwrap();
+/// }
+/// ```
+pub fn read_submissions<R: BufRead>(reader: R) -> impl Iterator<Item = Result<Submission, Error>> {
+    reader.lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line.context(format!("Couldn't read line {}", i + 1))?;
+            serde_json::from_str(&line).context(format!("Couldn't parse a submission on line {}", i + 1))
+        })
+}
+
+// Returns the keys and values of the data as csv columns, sorted
+// alphabetically by key. Both `header()` and `as_csv()` use this so
+// the values always line up with their header.
+fn csv_columns(data: &TestData) -> (Vec<String>, Vec<String>) {
+    let mut pairs: Vec<_> = data.iter().collect();
+    pairs.sort_by(|x, y| x.0.cmp(y.0));
+    pairs.into_iter()
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .unzip()
+}
+
+/// Converts [`TestData`] to and from a URL query string.
+///
+/// This is useful for labs that submit data from an HTML form with
+/// a GET request, or any other time the data ends up in a URL.
+///
+/// ```rust
+/// use rubric::TestData;
+/// use rubric::dropbox::QueryString;
+///
+/// let data = TestData::from_query_string("name=Luke%20Skywalker&id=1234");
+/// assert_eq!(data["name"], "Luke Skywalker");
+///
+/// assert_eq!(data.to_query_string(), "id=1234&name=Luke+Skywalker");
+/// ```
+pub trait QueryString {
+    /// Parses a query string into test data, decoding percent-encoded
+    /// keys and values. A leading `?` is ignored.
+    ///
+    /// If a key appears more than once, the last value wins.
+    fn from_query_string(query: &str) -> Self;
+
+    /// Encodes the test data as a query string. Keys are sorted
+    /// alphabetically, so the output is always the same for the same data.
+    fn to_query_string(&self) -> String;
+}
+
+impl QueryString for TestData {
+    fn from_query_string(query: &str) -> Self {
+        let query = query.trim_start_matches('?');
+        form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect()
+    }
+
+    fn to_query_string(&self) -> String {
+        let mut pairs: Vec<_> = self.iter().collect();
+        pairs.sort_by(|x, y| x.0.cmp(y.0));
+        form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(pairs)
+            .finish()
+    }
+}
+
+/// Builds [`TestData`] from key/value pairs at runtime.
+///
+/// This is the runtime version of the [`data!`](../macro.data.html) macro,
+/// for when you've parsed the pairs from somewhere. The keys and values can
+/// be any type that converts into a `String`. If a key is repeated, the last
+/// value wins.
+///
+/// ```rust
+/// use rubric::TestData;
+/// use rubric::dropbox::FromPairs;
+///
+/// let data = TestData::from_pairs(vec![("name", "luke"), ("id", "1234")]);
+/// assert_eq!(data["name"], "luke");
+/// ```
+pub trait FromPairs {
+    /// Builds test data from an iterator of `(key, value)` pairs.
+    fn from_pairs<I, K, V>(pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>;
+}
+
+impl FromPairs for TestData {
+    fn from_pairs<I, K, V>(pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        pairs.into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect()
+    }
+}
+
+/// Case-insensitive key lookups on [`TestData`].
+///
+/// Students don't always type keys the way the rubric expects, so
+/// `GitHubUsername` won't be found by `data.get("githubusername")`.
+/// These only ignore case, `github_username` still won't match.
+///
+/// If some keys differ only by case, like `Name` and `name`, an exact
+/// match is used first. Otherwise the key that sorts first wins, so the
+/// result doesn't depend on the order of the map.
+///
+/// ```rust
+/// use rubric::data;
+/// use rubric::dropbox::TestDataExt;
+///
+/// let mut data = data! { "GitHubUsername" => "llamicron" };
+/// assert_eq!(data.get_ci("githubusername").unwrap(), "llamicron");
+///
+/// data.normalize_keys();
+/// assert_eq!(data["githubusername"], "llamicron");
+/// ```
+pub trait TestDataExt {
+    /// Gets the value for `key`, ignoring case.
+    fn get_ci(&self, key: &str) -> Option<&String>;
+
+    /// Lowercases every key, so plain lookups with lowercase keys work.
+    ///
+    /// When keys collide, the value kept is the one [`get_ci`](crate::dropbox::TestDataExt::get_ci)
+    /// would have returned.
+    fn normalize_keys(&mut self);
+}
+
+impl TestDataExt for TestData {
+    fn get_ci(&self, key: &str) -> Option<&String> {
+        if let Some(value) = self.get(key) {
+            return Some(value);
+        }
+        let key = key.to_lowercase();
+        self.iter()
+            .filter(|(k, _)| k.to_lowercase() == key)
+            .min_by(|x, y| x.0.cmp(y.0))
+            .map(|(_, v)| v)
+    }
+
+    fn normalize_keys(&mut self) {
+        let mut pairs: Vec<(String, String)> = self.drain().collect();
+        // Keys that are already lowercase win, then the one that sorts first
+        pairs.sort_by_key(|(k, _)| (*k != k.to_lowercase(), k.clone()));
+        for (key, value) in pairs {
+            self.entry(key.to_lowercase()).or_insert(value);
+        }
+    }
+}
+
+impl AsCsv for TestData {
+    /// Returns the test data, serialized to a csv string. It will be
+    /// sorted alphabetically by key.
+    fn as_csv(&self) -> String {
+        self.as_csv_with(&CsvOptions::default())
+    }
+
+    /// Returns the filename that the [`ResultsFile`](crate::results_file::ResultsFile)
+    /// uses as its output
+    ///
+    /// This probably shouldn't get used for test data, as it will be written as part
+    /// of a submission, not on it's own.
+    fn filename(&self) -> String {
+        String::from("submission_data.csv")
+    }
+
+    /// Returns a header to write to a csv file. This should match the fields in `as_csv` above.
+    fn header(&self) -> String {
+        self.header_with(&CsvOptions::default())
+    }
+
+    fn as_csv_with(&self, options: &CsvOptions) -> String {
+        options.row(&csv_columns(self).1)
+    }
+
+    fn header_with(&self, options: &CsvOptions) -> String {
+        options.row(&csv_columns(self).0)
+    }
+}
+
+impl FromStr for Submission {
+    type Err = Error;
+
+    /// Parses a submission from a JSON string, like the ones the
+    /// dropbox receives or that [`batch`](crate::dropbox::batch) grades.
+    ///
+    /// Only JSON is supported. A CSV row can't be turned back into a
+    /// submission without its header, so that has to be done explicitly.
+    ///
+    /// ```rust
+    /// use rubric::Submission;
+    ///
+    /// let sub = Submission::new();
+    /// let json = serde_json::to_string(&sub).unwrap();
+    ///
+    /// let parsed: Submission = json.parse().unwrap();
+    /// assert_eq!(parsed.grade, sub.grade);
+    ///
+    /// assert!("grade,late,name".parse::<Submission>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if !s.starts_with('{') {
+            bail!("Couldn't parse a submission, expected a JSON object");
+        }
+        serde_json::from_str(s).context("Couldn't parse a submission from JSON")
+    }
+}
+
+impl AsCsv for Submission {
+    /// Returns the submission's values in csv format. The `TestData` atttached will be
+    /// sorted alphabetically by key.
+    fn as_csv(&self) -> String {
+        self.as_csv_with(&CsvOptions::default())
+    }
+
+    /// Returns the filename to use when writing submissions to disk
+    fn filename(&self) -> String {
+        String::from("submissions.csv")
+    }
+
+    /// Returns a header of all the fields, matching the data in `as_csv`
+    fn header(&self) -> String {
+        self.header_with(&CsvOptions::default())
+    }
+
+    /// The values with the given options. Names in the `passed` and `failed`
+    /// lists, and the feedback, are joined with the options' `passed_failed_separator`
+    /// (see [`CsvOptions::list`](crate::dropbox::CsvOptions::list)).
+    ///
+    /// Feedback usually has commas in it, so its column is always quoted,
+    /// even if the options don't quote. See [`CsvOptions::quoted_field`](crate::dropbox::CsvOptions::quoted_field).
+    fn as_csv_with(&self, options: &CsvOptions) -> String {
+        let before = options.row(&[
+            self.time.format(&self.timestamp_format).to_string(),
+            self.late.to_string(),
+            self.grade.to_string(),
+            options.list(&self.passed),
+            options.list(&self.failed),
+            self.override_reason.clone().unwrap_or_default(),
+            self.rubric_version.clone().unwrap_or_default(),
+        ]);
+        let feedback = options.quoted_field(&options.list(&self.feedback));
+
+        let mut after = data_columns(&self.data).1;
+        if let Some(fp) = &self.fingerprint {
+            after.push(fp.secret.clone());
+            after.push(fp.platform.clone());
+        }
+        let delimiter = options.delimiter.to_string();
+        [before, feedback, options.row(&after)].join(&delimiter)
+    }
+
+    fn header_with(&self, options: &CsvOptions) -> String {
+        let mut header: Vec<String> = ["time", "late", "grade", "passed", "failed", "override_reason", "rubric_version", "feedback"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        header.extend(data_columns(&self.data).0);
+        if self.fingerprint.is_some() {
+            header.push(String::from("secret"));
+            header.push(String::from("platform"));
+        }
+        options.row(&header)
+    }
+}
+
+// The data's columns for a submission's csv. Data with no keys still
+// gets one (empty) column, so rows line up with older files.
+fn data_columns(data: &TestData) -> (Vec<String>, Vec<String>) {
+    if data.is_empty() {
+        return (vec![String::new()], vec![String::new()]);
+    }
+    csv_columns(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
     use crate::{data, yaml, attach};
+    use crate::rubric::Criterion;
 
 
     #[test]
@@ -328,6 +1534,76 @@ mod tests {
         assert_eq!(sub2.data["key"], "value");
     }
 
+    // Runs a future to completion. The ones in these tests never wait on anything.
+    #[cfg(feature = "async")]
+    fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct NoopWaker;
+        impl Wake for NoopWaker {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = Box::pin(fut);
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn test_grade_against_async() {
+        async fn respond(name: String) -> bool {
+            !name.is_empty()
+        }
+
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Async")
+            .worth(10)
+            .test_async(|data: &TestData| respond(data["name"].clone()))
+            .build());
+        rubric.add(Criterion::new("Sync").worth(5).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Async fails")
+            .worth(20)
+            .test_async(|_: &TestData| async { false })
+            .build());
+
+        let mut sub = Submission::from_data(data! { "name" => "luke" });
+        block_on(sub.grade_against_async(&mut rubric));
+        assert_eq!(sub.grade, 15);
+        assert!(sub.has_passed("Async"));
+        assert!(sub.has_failed("Async fails"));
+
+        // Without awaiting, async criteria fail
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.grade, 5);
+        assert!(sub.has_failed("Async"));
+    }
+
+    #[test]
+    fn test_apply_before_grading() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Username")
+            .test(Box::new(|data: &TestData| data["username"] == "luke"))
+            .worth(10)
+            .build());
+
+        let mut sub = Submission::from_data(data! { "username" => " LUKE\n" });
+        sub.apply(|data| {
+            let username = data["username"].trim().to_lowercase();
+            data.insert("username".to_string(), username);
+        }).grade_against(&mut rubric);
+
+        assert_eq!(sub.data["username"], "luke");
+        assert_eq!(sub.grade, 10);
+        assert!(sub.has_passed("Username"));
+    }
+
     #[test]
     fn test_submission_as_csv() {
         let sub = Submission::from_data(data! { "a" => "v", "b" => "v2" });
@@ -341,6 +1617,255 @@ mod tests {
         assert!((&sub2).as_csv().contains(expected));
     }
 
+    #[test]
+    fn test_grade_against_twice() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Passes").worth(10).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Fails").worth(5).test(Box::new(|_: &TestData| false)).build());
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        sub.grade_against(&mut rubric);
+
+        assert_eq!(sub.grade, 10);
+        assert_eq!(sub.passed, vec!["Passes (+10)"]);
+        assert_eq!(sub.failed, vec!["Fails (-0)"]);
+        assert_eq!(sub.results.len(), 2);
+    }
+
+    #[test]
+    fn test_rubric_version_stamped() {
+        let raw = r#"
+            name: Versioned
+            version: "2.1"
+            criteria:
+                Anything:
+                    worth: 10
+        "#;
+        let mut rubric = Rubric::from_yaml(raw).unwrap();
+        assert_eq!(rubric.version.as_deref(), Some("2.1"));
+
+        let mut sub = Submission::new();
+        assert!(sub.rubric_version.is_none());
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.rubric_version.as_deref(), Some("2.1"));
+        assert!(sub.as_csv().contains(",2.1,"));
+
+        let parsed: Submission = serde_json::to_string(&sub).unwrap().parse().unwrap();
+        assert_eq!(parsed.rubric_version.as_deref(), Some("2.1"));
+        assert!(!parsed.graded_with_older(&rubric));
+
+        rubric.version = Some(String::from("3.0"));
+        assert!(parsed.graded_with_older(&rubric));
+
+        // Without a version on the rubric, nothing is stale
+        let mut unversioned = Rubric::default();
+        sub.grade_against(&mut unversioned);
+        assert!(sub.rubric_version.is_none());
+        assert!(!sub.graded_with_older(&unversioned));
+    }
+
+    #[test]
+    fn test_grade_against_missing_required_data() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Port is 8080")
+            .worth(10)
+            .require_data("port")
+            .require_data("host")
+            // This would panic if `port` is missing
+            .test(Box::new(|data: &TestData| data["port"] == "8080"))
+            .build());
+
+        let mut missing = Submission::from_data(data! { "name" => "luke" });
+        missing.grade_against(&mut rubric);
+        assert_eq!(missing.grade, 0);
+        assert!(!missing.has_failed("Port is 8080"));
+        assert!(rubric[0].skipped);
+        assert_eq!(rubric[0].skip_reason(), "missing data: port, host");
+        assert_eq!(rubric.total_points(), 0);
+
+        let mut present = Submission::from_data(data! { "port" => "8080", "host" => "localhost" });
+        present.grade_against(&mut rubric);
+        assert_eq!(present.grade, 10);
+        assert!(!rubric[0].skipped);
+        assert!(rubric[0].skip_reason.is_none());
+    }
+
+    #[test]
+    fn test_gradebook_row() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Has name").worth(10)
+            .test(Box::new(|data: &TestData| data.contains_key("name"))).build());
+
+        let mut sub = Submission::from_data(data! { "SIS User ID" => "s1234", "name" => "luke" });
+        sub.grade_against(&mut rubric);
+
+        let header = Submission::gradebook_header("SIS User ID", "Lab 1");
+        let row = sub.to_gradebook_row("SIS User ID");
+        assert_eq!(header, "SIS User ID,Lab 1");
+        let columns: Vec<&str> = row.split(',').collect();
+        assert_eq!(columns, vec!["s1234", "10"]);
+
+        // No id in the data
+        assert_eq!(sub.to_gradebook_row("id"), ",10");
+    }
+
+    #[test]
+    fn test_feedback() {
+        let mut sub = Submission::new();
+        sub.add_feedback("Great structure, nice work");
+        sub.add_feedback(String::from("Watch your error handling"));
+
+        // JSON
+        let json = serde_json::to_string(&sub).unwrap();
+        let mut parsed: Submission = json.parse().unwrap();
+        assert_eq!(parsed.feedback, sub.feedback);
+
+        // Regrading keeps it
+        parsed.reset_grade();
+        assert_eq!(parsed.feedback.len(), 2);
+
+        // CSV, quoted so the comma stays, even if the options don't quote
+        sub.add_feedback("Tests pass; docs don't");
+        for options in &[CsvOptions::new(), CsvOptions::new().quote('"')] {
+            let values = sub.as_csv_with(options);
+            assert!(values.contains(r#""Great structure, nice work;Watch your error handling;Tests pass\; docs don't""#));
+
+            let header = options.split(&sub.header_with(options));
+            let read = Submission::from_csv_row(&header, &options.split(&values), options).unwrap();
+            assert_eq!(read.feedback, sub.feedback);
+        }
+        assert!(sub.header().contains(",feedback,"));
+
+        // Older submissions don't have any
+        let old = r#"{"time":"2020-05-01T22:23:21.180875-05:00","late":false,"grade":0,"passed":[],"failed":[],"data":{}}"#;
+        let old: Submission = old.parse().unwrap();
+        assert!(old.feedback.is_empty());
+    }
+
+    #[test]
+    fn test_time_since_deadline() {
+        let mut rubric = Rubric::default();
+        let mut sub = Submission::new();
+        assert_eq!(sub.time_since_deadline(&rubric), None);
+
+        let deadline = Local::now();
+        rubric.deadline = Some(deadline);
+
+        // On time
+        sub.time = deadline - chrono::Duration::hours(2);
+        assert_eq!(sub.time_since_deadline(&rubric), Some(chrono::Duration::hours(-2)));
+        sub.time = deadline;
+        assert_eq!(sub.time_since_deadline(&rubric), Some(chrono::Duration::zero()));
+
+        // Late
+        sub.time = deadline + chrono::Duration::minutes(90);
+        assert_eq!(sub.time_since_deadline(&rubric), Some(chrono::Duration::minutes(90)));
+    }
+
+    #[test]
+    fn test_as_table() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Compiles").worth(30).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Tests pass").worth(10).test(Box::new(|_: &TestData| false)).build());
+        rubric.add(Criterion::new("Secret").worth(5).hide(true).test(Box::new(|_: &TestData| true)).build());
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+
+        let table = sub.as_table(&rubric);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[1].contains("Criterion") && lines[1].contains("Status"));
+        assert!(lines.iter().any(|l| l.contains("Compiles") && l.contains("30")));
+        assert!(lines.iter().any(|l| l.contains("Tests pass") && l.contains(" 0 ")));
+        assert!(!table.contains("Secret"));
+        assert_eq!(lines.last().unwrap(), &"Grade: 35/45 (77.8%)");
+
+        // Every line of the box is the same width
+        let width = lines[0].chars().count();
+        assert!(lines[..lines.len() - 1].iter().all(|l| l.chars().count() == width || l.contains('\u{1b}')));
+
+        rubric.get_mut("secret").unwrap().hide = false;
+        assert!(sub.as_table(&rubric).contains("Secret"));
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let limits = DataLimits { max_keys: 2, max_value_len: 5, max_size: 10_000 };
+        assert!(Submission::from_data(data! { "a" => "12345", "b" => "" }).check_limits(&limits).is_ok());
+
+        let too_many = Submission::from_data(data! { "a" => "1", "b" => "2", "c" => "3" });
+        assert_eq!(
+            too_many.check_limits(&limits).unwrap_err().to_string(),
+            "The submission has 3 data keys, the limit is 2"
+        );
+
+        let too_long = Submission::from_data(data! { "b" => "123456", "a" => "1234567" });
+        assert_eq!(
+            too_long.check_limits(&limits).unwrap_err().to_string(),
+            "The value of `a` is 7 bytes, the limit is 5"
+        );
+
+        let small = DataLimits { max_size: 50, ..limits };
+        let err = Submission::from_data(data! { "a" => "1" }).check_limits(&small).unwrap_err();
+        assert!(err.to_string().starts_with("The submission is "));
+    }
+
+    #[test]
+    fn test_is_graded() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Always").worth(10).test(Box::new(|_: &TestData| true)).build());
+
+        let mut sub = Submission::new();
+        assert!(!sub.is_graded());
+        assert!(sub.assert_graded().is_err());
+
+        sub.grade_against(&mut rubric);
+        assert!(sub.is_graded());
+        assert!(sub.assert_graded().is_ok());
+
+        // It's kept in JSON, older submissions without it aren't graded
+        let parsed: Submission = serde_json::to_string(&sub).unwrap().parse().unwrap();
+        assert!(parsed.is_graded());
+        let old = r#"{"time":"2020-05-01T22:23:21.180875-05:00","late":false,"grade":0,"passed":[],"failed":[],"data":{}}"#;
+        assert!(!old.parse::<Submission>().unwrap().is_graded());
+
+        sub.reset_grade();
+        assert!(!sub.is_graded());
+    }
+
+    #[test]
+    fn test_override_grade() {
+        let mut sub = Submission::new();
+        sub.grade = 60;
+        sub.override_grade(75, "Appeal, see email");
+        // A grade set by hand is still a grade
+        assert!(sub.is_graded());
+
+        // JSON
+        let json = serde_json::to_string(&sub).unwrap();
+        let parsed: Submission = json.parse().unwrap();
+        assert_eq!(parsed.grade, 75);
+        assert_eq!(parsed.override_reason.as_deref(), Some("Appeal, see email"));
+
+        // CSV, the comma is replaced so it doesn't add a column
+        let header: Vec<&str> = "time,late,grade,passed,failed,override_reason,rubric_version,feedback,".split(',').collect();
+        let values = sub.as_csv();
+        let values: Vec<&str> = values.split(',').collect();
+        assert_eq!(sub.header(), header.join(","));
+        assert_eq!(values.len(), header.len());
+        assert_eq!(values[2], "75");
+        assert_eq!(values[5], "Appeal; see email");
+
+        // Older submissions without the field still parse
+        let old = r#"{"time":"2020-05-01T22:23:21.180875-05:00","late":false,"grade":0,"passed":[],"failed":[],"data":{}}"#;
+        let old: Submission = old.parse().unwrap();
+        assert!(old.override_reason.is_none());
+
+        sub.reset_grade();
+        assert!(sub.override_reason.is_none());
+    }
+
     #[test]
     fn test_serialize_deserialize_json() {
         let mut sub = Submission::from_data(data! { "k2" => "v2", "k" => "v" });
@@ -354,6 +1879,22 @@ mod tests {
         assert_eq!(built_sub.grade, sub.grade);
     }
 
+    #[test]
+    fn test_serialization_is_deterministic() {
+        let keys: Vec<String> = (0..50).map(|i| format!("key{}", i)).collect();
+        let mut sub = Submission::from_data(keys.iter().map(|k| (k.clone(), k.clone())).collect());
+        sub.grade = 10;
+        let first = serde_json::to_string(&sub).unwrap();
+
+        // Parsed into a new map, and then written again
+        let parsed: Submission = first.parse().unwrap();
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), first);
+        assert_eq!(serde_json::to_string(&sub).unwrap(), first);
+
+        // Keys come out sorted
+        assert!(first.contains(r#""data":{"key0":"key0","key1":"key1","key10":"key10","#));
+    }
+
     #[test]
     fn test_grade_against_rubric() {
         let yaml = yaml!("../../test_data/test_rubric.yml").unwrap();
@@ -370,6 +1911,188 @@ mod tests {
         assert_eq!(sub.grade, 50);
     }
 
+    #[test]
+    fn test_grade_stores_command_output() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Git installed")
+            .func("git_installed")
+            .worth(10)
+            .command("git", &["--version"])
+            .build());
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+
+        assert_eq!(sub.grade, 10);
+        assert!(sub.data["git_installed_stdout"].contains("git version"));
+        assert_eq!(sub.data["git_installed_stderr"], "");
+        assert_eq!(sub.data["git_installed_exit_code"], "0");
+    }
+
+    #[test]
+    fn test_grade_stores_truncated_stderr() {
+        let mut rubric = Rubric::default();
+        rubric.output_limit = 10;
+        rubric.add(Criterion::new("Bad git command")
+            .func("bad_git")
+            .worth(10)
+            .command("git", &["not-a-git-command"])
+            .build());
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+
+        assert!(sub.has_failed("Bad git command"));
+        assert_eq!(sub.data["bad_git_stdout"], "");
+        let stderr = &sub.data["bad_git_stderr"];
+        assert_eq!(stderr.chars().count(), 13);
+        assert!(stderr.ends_with("..."));
+        assert!(stderr.starts_with("git: "));
+        assert_ne!(sub.data["bad_git_exit_code"], "0");
+    }
+
+    #[test]
+    fn test_captured_output() {
+        assert_eq!(captured("line one\nline two\n", 100), "line one line two");
+        assert_eq!(captured("héllo world", 5), "héllo...");
+        assert_eq!(captured("exactly", 7), "exactly");
+        assert_eq!(captured("", 0), "");
+    }
+
+    #[test]
+    fn test_grade_with_condition() {
+        fn rubric() -> Rubric {
+            let mut rubric = Rubric::default();
+            rubric.add(Criterion::new("Always").worth(5).test(Box::new(|_: &TestData| true)).build());
+            rubric.add(Criterion::new("Rust only")
+                .worth(10)
+                .test(Box::new(|_: &TestData| true))
+                .when(|data: &TestData| data.get("language").map(|l| l.as_str()) == Some("rust"))
+                .build());
+            rubric
+        }
+
+        // Condition is true, the criterion is graded
+        let mut applies = rubric();
+        let mut sub = Submission::from_data(data! { "language" => "rust" });
+        sub.grade_against(&mut applies);
+        assert_eq!(sub.grade, 15);
+        assert!(sub.has_passed("Rust only"));
+        assert!(!applies[1].skipped);
+
+        // Condition is false, the criterion is skipped
+        let mut skipped = rubric();
+        let mut sub = Submission::from_data(data! { "language" => "python" });
+        sub.grade_against(&mut skipped);
+        assert_eq!(sub.grade, 5);
+        assert!(!sub.has_passed("Rust only"));
+        assert!(sub.failed.is_empty());
+        assert!(skipped[1].skipped);
+        assert!(skipped[1].status.is_none());
+        assert_eq!(skipped.total_points(), 5);
+    }
+
+    #[test]
+    fn test_weighted_criterion_grade() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Unweighted").worth(10).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Weighted").worth(10).weight(0.5).test(Box::new(|_: &TestData| true)).build());
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+
+        assert_eq!(sub.grade, 15);
+        assert!(sub.passed.contains(&String::from("Unweighted (+10)")));
+        assert!(sub.passed.contains(&String::from("Weighted (+5)")));
+        assert_eq!(rubric.points(), 15);
+        assert_eq!(rubric.total_points(), 15);
+        // Still shows the nominal worth
+        assert_eq!(rubric[1].points_display(), "10 / 10 pts");
+    }
+
+    #[test]
+    fn test_grade_against_invalid_weight() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Bonus").worth(10).weight(-1.5).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Penalty").worth(-5).weight(f64::INFINITY).test(Box::new(|_: &TestData| true)).build());
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+
+        assert_eq!(sub.grade, 0);
+        assert_eq!(rubric.total_points(), 0);
+    }
+
+    #[test]
+    fn test_grade_against_clamps_weight_over_one() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Overweight").worth(10).weight(1.5).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Penalty").worth(4).penalty(true).weight(2.5).test(Box::new(|_: &TestData| true)).build());
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+
+        assert_eq!(sub.results[0].awarded, 10);
+        assert_eq!(sub.results[1].awarded, -4);
+        assert_eq!(sub.grade, 6);
+        assert_eq!(rubric.required_total(), 10);
+    }
+
+    #[test]
+    fn test_passed_rubric_threshold() {
+        let mut rubric = Rubric::default();
+        rubric.pass_threshold = Some(70.0);
+        rubric.add(Criterion::new("Only").worth(100).build());
+
+        let mut sub = Submission::new();
+        sub.grade = 70;
+        assert!(sub.passed_rubric(&rubric));
+
+        sub.grade = 69;
+        assert!(!sub.passed_rubric(&rubric));
+
+        sub.grade = 71;
+        assert!(sub.passed_rubric(&rubric));
+
+        rubric.pass_threshold = None;
+        sub.grade = 0;
+        assert!(sub.passed_rubric(&rubric));
+    }
+
+    #[test]
+    fn test_passed_rubric_required_failure() {
+        let mut rubric = Rubric::default();
+        rubric.pass_threshold = Some(50.0);
+        rubric.add(Criterion::new("Compiles").worth(10).required(true).build());
+        rubric.add(Criterion::new("Style").worth(90).test(Box::new(|_: &TestData| true)).build());
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.grade, 90);
+        assert!(!sub.passed_rubric(&rubric));
+    }
+
+    #[test]
+    fn test_data_hash() {
+        let mut first = TestData::new();
+        first.insert(String::from("name"), String::from("luke"));
+        first.insert(String::from("id"), String::from("1234"));
+        first.insert(String::from("repo"), String::from("lab_1"));
+
+        let mut second = TestData::new();
+        second.insert(String::from("repo"), String::from("lab_1"));
+        second.insert(String::from("id"), String::from("1234"));
+        second.insert(String::from("name"), String::from("luke"));
+
+        let hash = Submission::from_data(first).data_hash();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, Submission::from_data(second).data_hash());
+
+        // Different data, different hash
+        let other = Submission::from_data(data! { "name" => "luk", "id" => "e1234", "repo" => "lab_1" });
+        assert_ne!(hash, other.data_hash());
+    }
+
     #[test]
     fn test_test_data_as_csv() {
         let d = data! {
@@ -386,6 +2109,23 @@ mod tests {
         assert_eq!(d.filename(), expected_filename);
     }
 
+    #[test]
+    fn test_test_data_header_and_values_align() {
+        let mut d = TestData::new();
+        d.insert(String::from("zebra"), String::from("z"));
+        d.insert(String::from("apple"), String::from("a"));
+        d.insert(String::from("mango"), String::from("m"));
+        d.insert(String::from("banana"), String::from("b"));
+
+        let header: Vec<String> = d.header().split(',').map(String::from).collect();
+        let values: Vec<String> = d.as_csv().split(',').map(String::from).collect();
+        assert_eq!(header, vec!["apple", "banana", "mango", "zebra"]);
+        assert_eq!(header.len(), values.len());
+        for (key, value) in header.iter().zip(values.iter()) {
+            assert_eq!(&d[key], value);
+        }
+    }
+
     #[test]
     fn test_as_csv_replaces_commas() {
         let sub = Submission::from_data(data! {
@@ -437,10 +2177,308 @@ mod tests {
         assert!(sub.fingerprint.is_some());
     }
 
+    #[test]
+    fn test_diff_submissions() {
+        let mut previous = Submission::new();
+        previous.grade = 10;
+        previous.passed.push(String::from("Second (+10)"));
+        previous.passed.push(String::from("Third (+5)"));
+        previous.failed.push(String::from("First (-0)"));
+
+        let mut current = Submission::new();
+        current.grade = 15;
+        current.passed.push(String::from("First (+10)"));
+        current.passed.push(String::from("Third (+5)"));
+        current.failed.push(String::from("Second (-0)"));
+
+        let diff = current.diff(&previous);
+        assert_eq!(diff.newly_passed, vec!["First"]);
+        assert_eq!(diff.newly_failed, vec!["Second"]);
+        assert_eq!(diff.unchanged, vec!["Third"]);
+        assert_eq!(diff.grade_delta, 5);
+    }
+
     #[test]
     fn test_submission_as_csv_with_fingerprint() {
         let mut sub = Submission::new();
         sub.set_fingerprint("secret key");
         assert!(sub.header().contains("secret,platform"));
     }
+
+    #[test]
+    fn test_from_query_string() {
+        let data = TestData::from_query_string("?name=Luke%20Skywalker&email=luke%40example.com&msg=a+b%26c");
+        assert_eq!(data["name"], "Luke Skywalker");
+        assert_eq!(data["email"], "luke@example.com");
+        assert_eq!(data["msg"], "a b&c");
+
+        let empty = TestData::from_query_string("");
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_from_query_string_repeated_keys() {
+        let data = TestData::from_query_string("os=windows&os=linux");
+        assert_eq!(data.len(), 1);
+        assert_eq!(data["os"], "linux");
+    }
+
+    #[test]
+    fn test_from_pairs() {
+        let from_strs = TestData::from_pairs(vec![("name", "luke"), ("id", "1234")]);
+        assert_eq!(from_strs, data! { "name" => "luke", "id" => "1234" });
+
+        let owned = vec![
+            (String::from("name"), String::from("luke")),
+            (String::from("id"), String::from("1234")),
+        ];
+        assert_eq!(TestData::from_pairs(owned), from_strs);
+
+        // Mixed types, and the last value wins
+        let mixed = TestData::from_pairs(vec![("id", String::from("1")), ("id", String::from("2"))]);
+        assert_eq!(mixed.len(), 1);
+        assert_eq!(mixed["id"], "2");
+
+        let empty: Vec<(&str, &str)> = Vec::new();
+        assert!(TestData::from_pairs(empty).is_empty());
+    }
+
+    #[test]
+    fn test_get_ci() {
+        let data = data! { "GitHubUsername" => "llamicron", "ID" => "1234" };
+        assert_eq!(data.get_ci("githubusername").unwrap(), "llamicron");
+        assert_eq!(data.get_ci("GITHUBUSERNAME").unwrap(), "llamicron");
+        assert_eq!(data.get_ci("id").unwrap(), "1234");
+        assert!(data.get_ci("github_username").is_none());
+
+        // Exact matches first, then the key that sorts first
+        let data = data! { "Name" => "upper", "name" => "lower", "NAME" => "caps" };
+        assert_eq!(data.get_ci("name").unwrap(), "lower");
+        assert_eq!(data.get_ci("Name").unwrap(), "upper");
+        assert_eq!(data.get_ci("nAmE").unwrap(), "caps");
+    }
+
+    #[test]
+    fn test_normalize_keys() {
+        let mut data = data! { "GitHubUsername" => "llamicron", "id" => "1234" };
+        data.normalize_keys();
+        assert_eq!(data, data! { "githubusername" => "llamicron", "id" => "1234" });
+
+        let mut data = data! { "Name" => "upper", "NAME" => "caps" };
+        data.normalize_keys();
+        assert_eq!(data, data! { "name" => "caps" });
+
+        let mut data = data! { "Name" => "upper", "name" => "lower" };
+        data.normalize_keys();
+        assert_eq!(data, data! { "name" => "lower" });
+    }
+
+    #[test]
+    fn test_to_query_string() {
+        let data = data! {
+            "name" => "Luke Skywalker",
+            "key with spaces" => "a&b=c"
+        };
+        let query = data.to_query_string();
+        assert_eq!(query, "key+with+spaces=a%26b%3Dc&name=Luke+Skywalker");
+        assert_eq!(TestData::from_query_string(&query), data);
+    }
+
+    #[test]
+    fn test_from_str_json() {
+        let mut sub = Submission::from_data(data! { "name" => "luke", "id" => "1234" });
+        sub.grade = 42;
+        sub.passed.push(String::from("Has name (+42)"));
+        sub.set_fingerprint("secret");
+        let json = serde_json::to_string(&sub).unwrap();
+
+        let parsed: Submission = format!("  {}\n", json).parse().unwrap();
+        assert_eq!(parsed.grade, 42);
+        assert_eq!(parsed.data, sub.data);
+        assert_eq!(parsed.passed, sub.passed);
+        assert_eq!(parsed.time, sub.time);
+        assert_eq!(parsed.fingerprint(), sub.fingerprint());
+    }
+
+    #[test]
+    fn test_from_str_invalid() {
+        assert!("".parse::<Submission>().is_err());
+        assert!("luke,1234,42".parse::<Submission>().is_err());
+        assert!("{ not json }".parse::<Submission>().is_err());
+    }
+
+    #[test]
+    fn test_grade_against_seed() {
+        // Passes or fails "randomly", based on the seed
+        fn random_rubric(seed: u64) -> Rubric {
+            let mut rubric = Rubric::default();
+            rubric.seed = Some(seed);
+            for i in 0..8 {
+                rubric.add(Criterion::new(&format!("Case {}", i)).worth(1).test(Box::new(move |data: &TestData| {
+                    let seed: u64 = data[SEED_KEY].parse().unwrap();
+                    (seed.wrapping_mul(6364136223846793005).wrapping_add(i) >> 33) % 2 == 0
+                })).build());
+            }
+            rubric
+        }
+
+        let mut first = Submission::new();
+        first.grade_against(&mut random_rubric(7));
+        let mut second = Submission::new();
+        second.grade_against(&mut random_rubric(7));
+
+        assert_eq!(first.data[SEED_KEY], "7");
+        assert_eq!(first.grade, second.grade);
+        assert_eq!(first.passed, second.passed);
+        assert_eq!(first.failed, second.failed);
+
+        // A seed already in the data wins
+        let mut own_seed = Submission::from_data(data! { SEED_KEY => "99" });
+        own_seed.grade_against(&mut random_rubric(7));
+        assert_eq!(own_seed.data[SEED_KEY], "99");
+    }
+
+    #[test]
+    fn test_grade_against_no_seed() {
+        let mut sub = Submission::new();
+        sub.grade_against(&mut Rubric::default());
+        assert!(!sub.data.contains_key(SEED_KEY));
+    }
+
+    #[test]
+    #[cfg(feature = "schema")]
+    fn test_json_schema() {
+        let schema = Submission::json_schema();
+        let properties = schema["properties"].as_object().unwrap();
+        for key in &["time", "grade", "data", "late", "passed", "failed", "fingerprint"] {
+            assert!(properties.contains_key(*key), "schema is missing {}", key);
+        }
+        assert_eq!(schema["properties"]["data"]["type"], "object");
+        assert_eq!(schema["properties"]["data"]["additionalProperties"]["type"], "string");
+
+        // The fingerprint and timestamp format are optional
+        let required: Vec<&str> = schema["required"].as_array().unwrap()
+            .iter()
+            .map(|v| v.as_str().unwrap())
+            .collect();
+        assert!(required.contains(&"data"));
+        assert!(!required.contains(&"fingerprint"));
+        assert!(!required.contains(&"timestamp_format"));
+    }
+
+    #[test]
+    fn test_points_by_tag() {
+        let raw = r#"
+            name: Tagged
+            criteria:
+                Formatted:
+                    worth: 5
+                    tags: [style]
+                Documented:
+                    worth: 10
+                    tags: [style, docs]
+                Tests pass:
+                    worth: 20
+                    tags: [tests]
+                Untagged:
+                    worth: 1
+        "#;
+        let mut rubric = Rubric::from_yaml(raw).unwrap();
+        rubric.get_mut("formatted").unwrap().attach(Box::new(|_: &TestData| true));
+        rubric.get_mut("documented").unwrap().attach(Box::new(|_: &TestData| true));
+        rubric.get_mut("untagged").unwrap().attach(Box::new(|_: &TestData| true));
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.results.len(), 4);
+
+        let points = sub.points_by_tag();
+        assert_eq!(points.len(), 3);
+        assert_eq!(points["style"], 15);
+        assert_eq!(points["docs"], 10);
+        assert_eq!(points["tests"], 0);
+
+        // Works without the rubric
+        let json = serde_json::to_string(&sub).unwrap();
+        let parsed: Submission = json.parse().unwrap();
+        assert_eq!(parsed.points_by_tag(), points);
+    }
+
+    #[test]
+    fn test_grade_breakdown() {
+        let mut rubric = Rubric::from_yaml(yaml!("../../test_data/test_rubric.yml").unwrap()).unwrap();
+        let funcs: Vec<String> = rubric.funcs().iter().map(|f| f.to_string()).collect();
+        for (i, func) in funcs.iter().enumerate() {
+            let passes = i % 2 == 0;
+            rubric.get_mut(func).unwrap().attach(Box::new(move |_: &TestData| passes));
+        }
+        rubric.add(Criterion::new("Penalty").worth(3).penalty(true).index(1000).test(Box::new(|_: &TestData| true)).build());
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        let breakdown = sub.grade_breakdown();
+
+        assert_eq!(breakdown.len(), rubric.len());
+        assert_eq!(breakdown.iter().map(|b| b.1).sum::<isize>(), sub.grade);
+        assert_eq!(breakdown.iter().map(|b| b.2).sum::<isize>(), rubric.total_points());
+        // Graded order
+        let names: Vec<&str> = breakdown.iter().map(|b| b.0.as_str()).collect();
+        assert_eq!(names, rubric.names());
+        assert_eq!(breakdown.last().unwrap(), &(String::from("Penalty"), -3, -3));
+    }
+
+    #[test]
+    fn test_stream_jsonl() {
+        let names = ["luke", "leia", "han"];
+        let mut out = Vec::new();
+        for (i, name) in names.iter().enumerate() {
+            let mut sub = Submission::from_data(data! { "name" => *name });
+            sub.grade = i as isize;
+            sub.to_writer(&mut out).unwrap();
+        }
+        // A blank line and one that isn't a submission in the middle
+        out.extend_from_slice(b"\nnot json\n");
+        Submission::new().to_writer(&mut out).unwrap();
+
+        let read: Vec<_> = read_submissions(std::io::Cursor::new(out)).collect();
+        assert_eq!(read.len(), 5);
+        for (i, name) in names.iter().enumerate() {
+            let sub = read[i].as_ref().unwrap();
+            assert_eq!(sub.data["name"], *name);
+            assert_eq!(sub.grade, i as isize);
+        }
+        assert_eq!(
+            read[3].as_ref().unwrap_err().to_string(),
+            "Couldn't parse a submission on line 5"
+        );
+        assert!(read[4].is_ok());
+    }
+
+    #[test]
+    fn test_results_awarded_and_possible() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Passes").worth(10).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Fails").worth(20).test(Box::new(|_: &TestData| false)).build());
+        rubric.add(Criterion::new("Weighted").worth(10).weight(0.5).test(Box::new(|_: &TestData| true)).build());
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+
+        let result = |name: &str| sub.results.iter().find(|r| r.name == name).unwrap().clone();
+        assert_eq!((result("Passes").awarded, result("Passes").possible), (10, 10));
+        assert_eq!((result("Fails").awarded, result("Fails").possible), (0, 20));
+        assert_eq!((result("Weighted").awarded, result("Weighted").possible), (5, 5));
+
+        // Graded in order of func, with no indices
+        let json = serde_json::to_value(&sub).unwrap();
+        assert_eq!(json["results"][0]["possible"], 20);
+        assert_eq!(json["results"][1]["awarded"], 10);
+
+        // Results from before these were added still load
+        let old: CriterionResult = serde_json::from_str(
+            r#"{"name": "Passes", "func": "passes", "passed": true, "points": 10, "tags": []}"#
+        ).unwrap();
+        assert_eq!(old.awarded, 10);
+        assert_eq!(old.possible, 0);
+    }
 }
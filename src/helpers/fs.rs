@@ -2,7 +2,19 @@
 
 // std uses
 use std::fs;
+use std::io::{ErrorKind, Read};
 use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// external uses
+use regex::Regex;
+use serde_json::Value;
+use anyhow::{anyhow, Context};
+
+// internal uses
+use crate::Result;
 
 
 /// Returns true if a file or dir at the given path exists
@@ -34,6 +46,321 @@ pub fn file_contains<P: AsRef<Path>>(path: P, needle: &str) -> bool {
     false
 }
 
+/// Returns the first capture group of the first line in a file
+/// that matches the pattern.
+///
+/// Returns `None` if the file couldn't be read, the pattern is invalid or
+/// doesn't have a capture group, or no line matches.
+///
+/// ```rust
+/// use rubric::helpers::fs;
+///
+/// let version = fs::extract("Cargo.toml", r#"^version = "(.+)""#);
+/// assert!(version.is_some());
+/// ```
+pub fn extract<P: AsRef<Path>>(path: P, pattern: &str) -> Option<String> {
+    extract_all(path, pattern).into_iter().next()
+}
+
+/// Like [`extract`](crate::helpers::fs::extract), but returns the first
+/// capture group of *every* matching line, in order.
+///
+/// Returns an empty `Vec` if there are no matches, or for any of the
+/// reasons `extract` would return `None`.
+///
+/// ```rust
+/// use rubric::helpers::fs;
+///
+/// let deps = fs::extract_all("Cargo.toml", r#"^(\w+) = \{ version"#);
+/// assert!(deps.contains(&String::from("serde")));
+/// ```
+pub fn extract_all<P: AsRef<Path>>(path: P, pattern: &str) -> Vec<String> {
+    let re = match Regex::new(pattern) {
+        Ok(re) if re.captures_len() > 1 => re,
+        _ => return Vec::new()
+    };
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(_) => return Vec::new()
+    };
+
+    content.lines()
+        .filter_map(|line| re.captures(line))
+        .filter_map(|cap| cap.get(1).map(|m| m.as_str().to_owned()))
+        .collect()
+}
+
+/// Reads a JSON file and returns the value at a [JSON Pointer](https://tools.ietf.org/html/rfc6901),
+/// like `/server/port`, as a string.
+///
+/// Strings are returned without their quotes, so `"8080"` and `8080` both give `8080`.
+/// Everything else (numbers, booleans, `null`, objects, and arrays) is returned as
+/// it's written in JSON. Returns `None` if the file couldn't be read,
+/// isn't valid JSON, or nothing is at the pointer.
+///
+/// ```rust
+/// use rubric::helpers::fs;
+///
+/// assert_eq!(fs::json_get("test_data/config.json", "/server/port"), Some(String::from("8080")));
+/// assert_eq!(fs::json_get("test_data/config.json", "/users/0"), Some(String::from("luke")));
+/// assert!(fs::json_get("test_data/config.json", "/server/missing").is_none());
+/// ```
+pub fn json_get<P: AsRef<Path>>(path: P, pointer: &str) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let json: Value = serde_json::from_str(&content).ok()?;
+    match json.pointer(pointer)? {
+        Value::String(s) => Some(s.to_owned()),
+        other => Some(other.to_string())
+    }
+}
+
+/// Returns true if `a` was modified more recently than `b`, like a
+/// binary that was built after its source was last changed.
+///
+/// If both were modified at the same time, this returns `false`. Some file
+/// systems only keep modification times to the second, so a quick edit and
+/// rebuild can look like that. Returns `None` if either modification time
+/// can't be read, like if the file doesn't exist.
+///
+/// ```rust
+/// use rubric::helpers::fs;
+///
+/// assert_eq!(fs::file_newer_than("Cargo.toml", "Cargo.toml"), Some(false));
+/// assert!(fs::file_newer_than("Cargo.toml", "doesntexist").is_none());
+/// ```
+pub fn file_newer_than<P: AsRef<Path>>(a: P, b: P) -> Option<bool> {
+    let a = fs::metadata(a).and_then(|m| m.modified()).ok()?;
+    let b = fs::metadata(b).and_then(|m| m.modified()).ok()?;
+    Some(a > b)
+}
+
+/// Returns true if a file starts with the given bytes, like a
+/// [magic number](https://en.wikipedia.org/wiki/List_of_file_signatures).
+///
+/// Only the first few bytes are read, so this is cheap even for big files.
+/// It's handy for checking that a file really is what its extension says, and
+/// not a text file that was renamed. Returns `false` if the file is shorter
+/// than `bytes` or couldn't be read.
+///
+/// ```rust
+/// use rubric::helpers::fs;
+///
+/// assert!(fs::file_starts_with("Cargo.toml", b"[package]"));
+/// assert!(!fs::file_starts_with("doesntexist", b"[package]"));
+/// ```
+pub fn file_starts_with<P: AsRef<Path>>(path: P, bytes: &[u8]) -> bool {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return false
+    };
+    let mut prefix = Vec::with_capacity(bytes.len());
+    match file.take(bytes.len() as u64).read_to_end(&mut prefix) {
+        Ok(_) => prefix == bytes,
+        Err(_) => false
+    }
+}
+
+/// Returns true if the file is a PNG image, by its first bytes.
+/// See [`file_starts_with`](crate::helpers::fs::file_starts_with).
+pub fn is_png<P: AsRef<Path>>(path: P) -> bool {
+    file_starts_with(path, b"\x89PNG\r\n\x1a\n")
+}
+
+/// Returns true if the file is an ELF binary (what's built on Linux), by its first bytes.
+/// See [`file_starts_with`](crate::helpers::fs::file_starts_with).
+pub fn is_elf<P: AsRef<Path>>(path: P) -> bool {
+    file_starts_with(path, b"\x7fELF")
+}
+
+/// Returns true if the file is a zip archive, by its first bytes. Empty archives
+/// count too. Lots of formats are zip archives underneath, like `.jar` and `.docx`.
+/// See [`file_starts_with`](crate::helpers::fs::file_starts_with).
+pub fn is_zip<P: AsRef<Path>>(path: P) -> bool {
+    file_starts_with(&path, b"PK\x03\x04") || file_starts_with(&path, b"PK\x05\x06")
+}
+
+/// Creates a directory, and any parents it needs.
+///
+/// It's fine if the directory already exists. This returns an `Err` if it
+/// couldn't be created, or if something other than a directory is already there.
+///
+/// ```rust
+/// use rubric::helpers::fs;
+///
+/// fs::ensure_dir("target/ensure_dir_doc/nested").unwrap();
+/// // Doing it again is fine
+/// fs::ensure_dir("target/ensure_dir_doc/nested").unwrap();
+/// assert!(fs::file_exists("target/ensure_dir_doc/nested"));
+/// # std::fs::remove_dir_all("target/ensure_dir_doc").unwrap();
+/// ```
+pub fn ensure_dir<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    fs::create_dir_all(path)
+        .context(format!("Couldn't create directory {}", path.display()))?;
+    Ok(())
+}
+
+/// Copies a directory and everything in it.
+///
+/// `dst` is created if it doesn't exist. If it does, the two are merged: files
+/// in `src` overwrite files with the same name in `dst`, and anything else already
+/// in `dst` is left alone.
+///
+/// On Unix, symlinks are copied as symlinks, pointing to the same place as
+/// the original. Elsewhere, the file or directory they point to is copied.
+///
+/// Returns an `Err` if `src` isn't a directory, `dst` is inside `src`, or anything
+/// couldn't be copied. If there's an error partway through, whatever was copied
+/// before it is left in `dst`.
+///
+/// ```rust
+/// use rubric::helpers::fs;
+///
+/// fs::copy_dir("test_data", "target/copy_dir_doc").unwrap();
+/// assert!(fs::file_exists("target/copy_dir_doc/test_rubric.yml"));
+/// # std::fs::remove_dir_all("target/copy_dir_doc").unwrap();
+/// ```
+pub fn copy_dir<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> Result<()> {
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    if !src.is_dir() {
+        return Err(anyhow!("Can't copy {}, it isn't a directory", src.display()));
+    }
+
+    ensure_dir(dst)?;
+    // Copying a directory into itself would never end
+    if dst.canonicalize()?.starts_with(src.canonicalize()?) {
+        return Err(anyhow!("Can't copy {} into itself ({})", src.display(), dst.display()));
+    }
+
+    copy_dir_contents(src, dst)
+}
+
+// Copies everything in `src` into `dst`, which already exists
+fn copy_dir_contents(src: &Path, dst: &Path) -> Result<()> {
+    let entries = fs::read_dir(src)
+        .context(format!("Couldn't read directory {}", src.display()))?;
+
+    for entry in entries {
+        let entry = entry?;
+        let from = entry.path();
+        let to = dst.join(entry.file_name());
+        let file_type = entry.file_type()?;
+
+        if file_type.is_symlink() {
+            copy_symlink(&from, &to)?;
+        } else if file_type.is_dir() {
+            ensure_dir(&to)?;
+            copy_dir_contents(&from, &to)?;
+        } else {
+            fs::copy(&from, &to)
+                .context(format!("Couldn't copy {} to {}", from.display(), to.display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> Result<()> {
+    let target = fs::read_link(from)?;
+    // Replace whatever is there, like copying a file would
+    if to.symlink_metadata().is_ok() {
+        fs::remove_file(to)
+            .context(format!("Couldn't replace {}", to.display()))?;
+    }
+    std::os::unix::fs::symlink(&target, to)
+        .context(format!("Couldn't copy symlink {} to {}", from.display(), to.display()))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        ensure_dir(to)?;
+        copy_dir_contents(from, to)
+    } else {
+        fs::copy(from, to)
+            .context(format!("Couldn't copy {} to {}", from.display(), to.display()))?;
+        Ok(())
+    }
+}
+
+// Keeps workspaces made at the same time apart
+static WORKSPACE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A scratch directory that's deleted, with everything in it, when this is dropped.
+///
+/// The directory is made in the system's temp directory with a unique name.
+/// It's still deleted if a test panics, since that drops it too. If you want to look
+/// at what was left after a run, call [`keep`](crate::helpers::fs::TempWorkspace::keep)
+/// and it'll stay.
+///
+/// ```rust
+/// use rubric::helpers::fs::TempWorkspace;
+///
+/// let path = {
+///     let workspace = TempWorkspace::new("lab1").unwrap();
+///     std::fs::write(workspace.path().join("main.rs"), "fn main() {}").unwrap();
+///     assert!(workspace.path().is_dir());
+///     workspace.path().to_path_buf()
+/// };
+/// // It's gone now
+/// assert!(!path.exists());
+/// ```
+#[derive(Debug)]
+pub struct TempWorkspace {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempWorkspace {
+    /// Makes a new, empty workspace. The directory's name starts with `prefix`.
+    ///
+    /// Returns an `Err` if the directory couldn't be made.
+    pub fn new(prefix: &str) -> Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+
+        loop {
+            let count = WORKSPACE_COUNT.fetch_add(1, Ordering::Relaxed);
+            let name = format!("{}-{}-{}-{}", prefix, process::id(), nanos, count);
+            let path = std::env::temp_dir().join(name);
+
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(TempWorkspace { path, keep: false }),
+                // Something else got this name first, try the next one
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e)
+                    .context(format!("Couldn't create workspace {}", path.display())),
+            }
+        }
+    }
+
+    /// The workspace's directory
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Leaves the directory where it is when this is dropped.
+    ///
+    /// This is for debugging, so you can look at what a failed run left behind.
+    pub fn keep(&mut self) {
+        self.keep = true;
+    }
+}
+
+impl Drop for TempWorkspace {
+    fn drop(&mut self) {
+        if !self.keep {
+            // There's nothing to do about an error here, and
+            // panicking while already panicking would abort
+            fs::remove_dir_all(&self.path).ok();
+        }
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -53,4 +380,203 @@ mod tests {
         assert!(!file_contains("test_data/test_rubric.yml", "something it doesn't contain"));
         assert!(!file_contains("src/", "doesn't matter"));
     }
+
+    #[test]
+    fn test_magic_numbers() {
+        assert!(is_png("test_data/magic/image.png"));
+        // Named like a png, but it's text
+        assert!(!is_png("test_data/magic/spoofed.png"));
+        assert!(file_starts_with("test_data/magic/spoofed.png", b"this"));
+        assert!(!is_elf("test_data/magic/image.png"));
+        assert!(!is_zip("test_data/magic/image.png"));
+
+        // Shorter than the magic number
+        let workspace = TempWorkspace::new("magic").unwrap();
+        let short = workspace.path().join("short.png");
+        fs::write(&short, b"\x89P").unwrap();
+        assert!(!is_png(&short));
+        assert!(!is_png("doesntexist"));
+        assert!(!is_png("test_data"));
+        assert!(file_starts_with(&short, b""));
+
+        let zip = workspace.path().join("empty.zip");
+        fs::write(&zip, b"PK\x05\x06").unwrap();
+        assert!(is_zip(&zip));
+
+        // This test is running in one
+        #[cfg(target_os = "linux")]
+        assert!(is_elf(std::env::current_exe().unwrap()));
+    }
+
+    #[test]
+    fn test_file_newer_than() {
+        use std::time::{Duration, SystemTime};
+        use filetime::{set_file_mtime, FileTime};
+
+        let dir = tempdir::TempDir::new("newer_than").unwrap();
+        let source = dir.path().join("main.c");
+        let binary = dir.path().join("main");
+        fs::write(&source, "int main() {}").unwrap();
+        fs::write(&binary, "").unwrap();
+
+        // Some file systems are too coarse to tell writes apart, so set the times
+        let now = SystemTime::now();
+        set_file_mtime(&source, FileTime::from_system_time(now - Duration::from_secs(60))).unwrap();
+        set_file_mtime(&binary, FileTime::from_system_time(now)).unwrap();
+
+        assert_eq!(file_newer_than(&binary, &source), Some(true));
+        assert_eq!(file_newer_than(&source, &binary), Some(false));
+        // The same time isn't newer
+        assert_eq!(file_newer_than(&binary, &binary), Some(false));
+
+        assert!(file_newer_than(binary, dir.path().join("missing")).is_none());
+    }
+
+    #[test]
+    fn test_extract() {
+        let version = extract("Cargo.toml", r#"^version = "(\d+\.\d+\.\d+)""#);
+        assert_eq!(version, Some(String::from(env!("CARGO_PKG_VERSION"))));
+
+        let name = extract("test_data/test_rubric.yml", r"^name: (.+)$");
+        assert_eq!(name, Some(String::from("Test Rubric")));
+    }
+
+    #[test]
+    fn test_extract_no_match() {
+        assert!(extract("Cargo.toml", r"^not in the file (.+)").is_none());
+        assert!(extract("doesntexist", r"(.+)").is_none());
+        // No capture group
+        assert!(extract("Cargo.toml", r"version").is_none());
+        // Bad pattern
+        assert!(extract("Cargo.toml", r"(unclosed").is_none());
+    }
+
+    #[test]
+    fn test_extract_all() {
+        let funcs = extract_all("test_data/test_rubric.yml", r"func: (\w+)");
+        assert_eq!(funcs, vec!["first_crit", "second_crit"]);
+        assert!(extract_all("test_data/test_rubric.yml", r"nothing (\w+)").is_empty());
+    }
+
+    #[test]
+    fn test_json_get() {
+        let path = "test_data/config.json";
+        assert_eq!(json_get(path, "/name"), Some(String::from("my server")));
+        assert_eq!(json_get(path, "/server/host"), Some(String::from("localhost")));
+        assert_eq!(json_get(path, "/server/port"), Some(String::from("8080")));
+        assert_eq!(json_get(path, "/server/tls"), Some(String::from("false")));
+        assert_eq!(json_get(path, "/users/1"), Some(String::from("leia")));
+        assert_eq!(json_get(path, "/users"), Some(String::from(r#"["luke","leia"]"#)));
+        assert_eq!(json_get(path, "/motd"), Some(String::from("null")));
+    }
+
+    #[test]
+    fn test_ensure_dir() {
+        let dir = tempdir::TempDir::new("ensure_dir").unwrap();
+        let nested = dir.path().join("a/b/c");
+
+        ensure_dir(&nested).unwrap();
+        assert!(nested.is_dir());
+        ensure_dir(&nested).unwrap();
+
+        // A file is in the way
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        assert!(ensure_dir(&file).is_err());
+    }
+
+    #[test]
+    fn test_temp_workspace() {
+        let first = TempWorkspace::new("workspace").unwrap();
+        let second = TempWorkspace::new("workspace").unwrap();
+        assert_ne!(first.path(), second.path());
+
+        let path = first.path().to_path_buf();
+        ensure_dir(path.join("nested")).unwrap();
+        fs::write(path.join("nested/file"), "scratch").unwrap();
+        assert!(path.is_dir());
+
+        drop(first);
+        assert!(!path.exists());
+        assert!(second.path().is_dir());
+    }
+
+    #[test]
+    fn test_temp_workspace_panic_and_keep() {
+        let path = std::panic::catch_unwind(|| {
+            let workspace = TempWorkspace::new("workspace_panic").unwrap();
+            let path = workspace.path().to_path_buf();
+            std::panic::panic_any(path);
+        }).unwrap_err().downcast::<PathBuf>().unwrap();
+        assert!(!path.exists());
+
+        let mut kept = TempWorkspace::new("workspace_keep").unwrap();
+        kept.keep();
+        let path = kept.path().to_path_buf();
+        drop(kept);
+        assert!(path.is_dir());
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_copy_dir() {
+        let dir = tempdir::TempDir::new("copy_dir").unwrap();
+        let src = dir.path().join("starter");
+        ensure_dir(src.join("src/bin")).unwrap();
+        fs::write(src.join("Cargo.toml"), "[package]").unwrap();
+        fs::write(src.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(src.join("src/bin/tool.rs"), "fn main() {}").unwrap();
+
+        // Merges into an existing destination
+        let dst = dir.path().join("workspace");
+        ensure_dir(&dst).unwrap();
+        fs::write(dst.join("notes.txt"), "keep me").unwrap();
+        fs::write(dst.join("Cargo.toml"), "old").unwrap();
+
+        copy_dir(&src, &dst).unwrap();
+        assert_eq!(fs::read_to_string(dst.join("Cargo.toml")).unwrap(), "[package]");
+        assert_eq!(fs::read_to_string(dst.join("src/main.rs")).unwrap(), "fn main() {}");
+        assert!(dst.join("src/bin/tool.rs").is_file());
+        assert_eq!(fs::read_to_string(dst.join("notes.txt")).unwrap(), "keep me");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_copy_dir_symlinks() {
+        let dir = tempdir::TempDir::new("copy_dir_symlinks").unwrap();
+        let src = dir.path().join("src");
+        ensure_dir(&src).unwrap();
+        fs::write(src.join("real.txt"), "real").unwrap();
+        std::os::unix::fs::symlink("real.txt", src.join("link.txt")).unwrap();
+
+        let dst = dir.path().join("dst");
+        copy_dir(&src, &dst).unwrap();
+        // Copying again replaces the link
+        copy_dir(&src, &dst).unwrap();
+
+        let link = dst.join("link.txt");
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("real.txt"));
+        assert_eq!(fs::read_to_string(&link).unwrap(), "real");
+    }
+
+    #[test]
+    fn test_copy_dir_errors() {
+        let dir = tempdir::TempDir::new("copy_dir_errors").unwrap();
+        assert!(copy_dir(dir.path().join("doesntexist"), dir.path().join("dst")).is_err());
+        assert!(copy_dir("Cargo.toml", dir.path().join("dst")).is_err());
+        // Into itself
+        assert!(copy_dir(dir.path(), dir.path().join("inside")).is_err());
+    }
+
+    #[test]
+    fn test_json_get_missing() {
+        assert!(json_get("test_data/config.json", "/server/missing").is_none());
+        assert!(json_get("test_data/config.json", "/users/5").is_none());
+        // Pointers have to start with a slash
+        assert!(json_get("test_data/config.json", "name").is_none());
+        // Not JSON
+        assert!(json_get("test_data/test_rubric.yml", "/name").is_none());
+        assert!(json_get("doesntexist", "/name").is_none());
+    }
 }
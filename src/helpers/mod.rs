@@ -3,3 +3,4 @@ pub mod cli;
 pub mod web;
 pub mod fs;
 pub mod system;
+pub mod process;
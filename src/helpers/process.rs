@@ -0,0 +1,474 @@
+//! Running processes and inspecting what they did
+
+// std uses
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+// external uses
+use anyhow::{anyhow, Context};
+
+// internal uses
+use crate::Result;
+
+
+/// The captured output of a command that ran.
+///
+/// `stdout` and `stderr` are converted to strings lossily, so invalid UTF-8
+/// won't cause an error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandResult {
+    /// Everything the command wrote to stdout
+    pub stdout: String,
+    /// Everything the command wrote to stderr
+    pub stderr: String,
+    /// The exit code of the command. This is `None` if the
+    /// process was killed by a signal.
+    pub exit_code: Option<i32>,
+}
+
+impl CommandResult {
+    /// Returns true if the command exited with code 0
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// Returns true if the command exited with the given code
+    pub fn exited_with(&self, code: i32) -> bool {
+        self.exit_code == Some(code)
+    }
+
+    /// Returns true if stdout contains the given string
+    pub fn stdout_contains(&self, needle: &str) -> bool {
+        self.stdout.contains(needle)
+    }
+
+    /// Returns true if stderr contains the given string
+    pub fn stderr_contains(&self, needle: &str) -> bool {
+        self.stderr.contains(needle)
+    }
+
+    /// Returns true if the command wrote nothing to stderr,
+    /// not counting whitespace
+    pub fn stderr_empty(&self) -> bool {
+        self.stderr.trim().is_empty()
+    }
+
+    /// Returns true if the command did everything the expectations ask for.
+    ///
+    /// ```rust
+    /// use rubric::helpers::process::{self, Expectations};
+    ///
+    /// let expected = Expectations::new()
+    ///     .exit_code(0)
+    ///     .stdout_contains("git version")
+    ///     .stderr_empty();
+    ///
+    /// let result = process::run("git", &["--version"]).unwrap();
+    /// assert!(result.matches(&expected));
+    /// ```
+    pub fn matches(&self, expected: &Expectations) -> bool {
+        let exit_code_matches = match expected.exit_code {
+            Some(code) => self.exited_with(code),
+            None => true
+        };
+        exit_code_matches
+            && expected.stdout_contains.iter().all(|s| self.stdout_contains(s))
+            && expected.stderr_contains.iter().all(|s| self.stderr_contains(s))
+            && (!expected.stderr_empty || self.stderr_empty())
+    }
+}
+
+/// What a command should do, checked with [`CommandResult::matches`](crate::helpers::process::CommandResult::matches).
+///
+/// Nothing is expected to start with, each method adds an expectation.
+/// This works well with a command criterion:
+///
+/// ```rust
+/// # use rubric::rubric::Criterion;
+/// use rubric::helpers::process::Expectations;
+///
+/// let expected = Expectations::new().exit_code(0).stdout_contains("git version");
+/// let crit = Criterion::new("Git installed")
+///     .command("git", &["--version"])
+///     .expect(move |result| result.matches(&expected))
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Expectations {
+    exit_code: Option<i32>,
+    stdout_contains: Vec<String>,
+    stderr_contains: Vec<String>,
+    stderr_empty: bool,
+}
+
+impl Expectations {
+    /// Expects nothing
+    pub fn new() -> Self {
+        Expectations::default()
+    }
+
+    /// Expects the command to exit with this code
+    pub fn exit_code(mut self, code: i32) -> Self {
+        self.exit_code = Some(code);
+        self
+    }
+
+    /// Expects stdout to contain this. Can be used more than once.
+    pub fn stdout_contains(mut self, needle: &str) -> Self {
+        self.stdout_contains.push(String::from(needle));
+        self
+    }
+
+    /// Expects stderr to contain this. Can be used more than once.
+    pub fn stderr_contains(mut self, needle: &str) -> Self {
+        self.stderr_contains.push(String::from(needle));
+        self
+    }
+
+    /// Expects nothing but whitespace on stderr
+    pub fn stderr_empty(mut self) -> Self {
+        self.stderr_empty = true;
+        self
+    }
+}
+
+/// Runs a program with the given arguments and captures its output.
+///
+/// Unlike [`cli::cmd`](crate::helpers::cli::cmd), this doesn't go
+/// through a shell. The program is run directly, so the arguments are
+/// passed exactly as you give them.
+///
+/// This returns an `Err` if the program couldn't be started at all, ie. it
+/// isn't installed. A program that runs and fails is still an `Ok`, check the
+/// `exit_code` for that.
+///
+/// ```rust
+/// use rubric::helpers::process;
+///
+/// let result = process::run("git", &["--version"]).unwrap();
+/// assert!(result.success());
+/// assert!(result.stdout.contains("git version"));
+/// ```
+pub fn run(cmd: &str, args: &[&str]) -> Result<CommandResult> {
+    let mut command = Command::new(cmd);
+    command.args(args);
+    output_of(command, cmd)
+}
+
+/// Same as [`run`](crate::helpers::process::run), but the program runs in the
+/// given working directory instead of this one.
+///
+/// Use this for programs that read or write files relative to where they're run,
+/// like a student's program that expects to be run from their repo.
+///
+/// The directory must already exist, or this returns an `Err`. Note that a relative
+/// `cmd` (like `./build.sh`) is found relative to *this* program's directory on some
+/// platforms, so use a program on the `PATH` or an absolute path.
+///
+/// ```rust
+/// use rubric::helpers::process;
+///
+/// let result = process::run_in_dir("src", "git", &["rev-parse", "--show-prefix"]).unwrap();
+/// assert_eq!(result.stdout.trim(), "src/");
+/// ```
+pub fn run_in_dir<P: AsRef<Path>>(dir: P, cmd: &str, args: &[&str]) -> Result<CommandResult> {
+    let dir = dir.as_ref();
+    if !dir.is_dir() {
+        return Err(anyhow!("Can't run `{}` in {}, it isn't a directory", cmd, dir.display()));
+    }
+
+    let mut command = Command::new(cmd);
+    command.args(args).current_dir(dir);
+    output_of(command, cmd)
+}
+
+// Runs the command and captures what it did
+fn output_of(mut command: Command, cmd: &str) -> Result<CommandResult> {
+    let output = command
+        .output()
+        .context(format!("Couldn't run command `{}`", cmd))?;
+
+    Ok(CommandResult {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        exit_code: output.status.code(),
+    })
+}
+
+/// Runs a build command in a directory, and returns what it did.
+///
+/// This is for the common "does it build" criterion, check
+/// [`success`](crate::helpers::process::CommandResult::success) for that. Nothing
+/// is printed, so show the student the `stderr` yourself if you want them to see why
+/// it failed. Unlike [`run_in_dir`](crate::helpers::process::run_in_dir), this never
+/// returns an `Err`. A build that can't be started at all has no exit code, and the
+/// reason is in `stderr`.
+///
+/// ```no_run
+/// use rubric::helpers::process;
+///
+/// let build = process::builds_with("student/", "gcc", &["-o", "main", "main.c"]);
+/// if !build.success() {
+///     eprintln!("{}", build.stderr);
+/// }
+/// ```
+pub fn builds_with<P: AsRef<Path>>(dir: P, cmd: &str, args: &[&str]) -> CommandResult {
+    run_in_dir(&dir, cmd, args).unwrap_or_else(|e| CommandResult {
+        stdout: String::new(),
+        stderr: format!("{:#}", e),
+        exit_code: None,
+    })
+}
+
+/// Returns true if `cargo build` succeeds in the directory.
+///
+/// See [`builds_with`](crate::helpers::process::builds_with), which also gives you the output.
+///
+/// ```no_run
+/// use rubric::helpers::process;
+///
+/// assert!(process::cargo_builds("student/"));
+/// ```
+pub fn cargo_builds<P: AsRef<Path>>(dir: P) -> bool {
+    builds_with(dir, "cargo", &["build"]).success()
+}
+
+/// Returns true if `make` succeeds in the directory, building the default target.
+///
+/// See [`builds_with`](crate::helpers::process::builds_with), which also gives you the output.
+///
+/// ```no_run
+/// use rubric::helpers::process;
+///
+/// assert!(process::make_succeeds("student/"));
+/// ```
+pub fn make_succeeds<P: AsRef<Path>>(dir: P) -> bool {
+    builds_with(dir, "make", &[]).success()
+}
+
+/// Returns true if a program can be found on the `PATH`.
+///
+/// Use this before running a tool like `docker` or `python3`, so a criterion
+/// can fail with a clear reason instead of a confusing error. The program
+/// is never run, this only looks for the file.
+///
+/// If `name` has a path separator in it, that path is checked directly instead.
+/// On Windows, the extensions in `PATHEXT` (like `.exe`) are tried too. On Unix,
+/// the file has to be executable.
+///
+/// ```rust
+/// use rubric::helpers::process;
+///
+/// assert!(process::command_available("git"));
+/// assert!(!process::command_available("definitely_not_a_real_program"));
+/// ```
+pub fn command_available(name: &str) -> bool {
+    if name.is_empty() {
+        return false;
+    }
+
+    let name = Path::new(name);
+    if name.components().count() > 1 {
+        return is_executable(name);
+    }
+
+    let paths = match env::var_os("PATH") {
+        Some(paths) => paths,
+        None => return false,
+    };
+
+    env::split_paths(&paths).any(|dir| {
+        let candidate = dir.join(name);
+        is_executable(&candidate) || windows_extensions().iter().any(|ext| {
+            let mut with_ext = candidate.clone().into_os_string();
+            with_ext.push(ext);
+            is_executable(Path::new(&with_ext))
+        })
+    })
+}
+
+// The extensions Windows will run a program with, from PATHEXT
+#[cfg(windows)]
+fn windows_extensions() -> Vec<String> {
+    env::var("PATHEXT")
+        .unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD"))
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn windows_extensions() -> Vec<String> {
+    Vec::new()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::helpers::fs::{ensure_dir, TempWorkspace};
+
+    #[test]
+    fn test_run_success() {
+        let result = run("git", &["--version"]).unwrap();
+        assert!(result.success());
+        assert_eq!(result.exit_code, Some(0));
+        assert!(result.stdout.contains("git version"));
+    }
+
+    #[test]
+    fn test_run_failure() {
+        let result = run("git", &["not-a-git-command"]).unwrap();
+        assert!(!result.success());
+        assert!(result.stderr.len() > 0);
+    }
+
+    fn result(stdout: &str, stderr: &str, exit_code: Option<i32>) -> CommandResult {
+        CommandResult {
+            stdout: String::from(stdout),
+            stderr: String::from(stderr),
+            exit_code,
+        }
+    }
+
+    #[test]
+    fn test_result_checks() {
+        let ok = result("all tests ok\n", "  \n", Some(0));
+        assert!(ok.exited_with(0));
+        assert!(!ok.exited_with(1));
+        assert!(ok.stdout_contains("ok"));
+        assert!(!ok.stdout_contains("failed"));
+        assert!(ok.stderr_empty());
+        assert!(!ok.stderr_contains("warning"));
+
+        let bad = result("", "warning: unused variable", Some(2));
+        assert!(bad.exited_with(2));
+        assert!(bad.stderr_contains("warning"));
+        assert!(!bad.stderr_empty());
+
+        // Killed by a signal
+        assert!(!result("", "", None).exited_with(0));
+    }
+
+    #[test]
+    fn test_matches() {
+        let ok = result("3 passed\n", "", Some(0));
+        assert!(ok.matches(&Expectations::new()));
+        assert!(ok.matches(&Expectations::new().exit_code(0).stdout_contains("3").stdout_contains("passed").stderr_empty()));
+        assert!(!ok.matches(&Expectations::new().exit_code(1)));
+        assert!(!ok.matches(&Expectations::new().stdout_contains("failed")));
+        assert!(!ok.matches(&Expectations::new().stderr_contains("warning")));
+
+        let noisy = result("3 passed\n", "warning: deprecated", Some(0));
+        assert!(!noisy.matches(&Expectations::new().exit_code(0).stderr_empty()));
+        assert!(noisy.matches(&Expectations::new().exit_code(0).stderr_contains("deprecated")));
+    }
+
+    #[test]
+    fn test_run_missing_program() {
+        assert!(run("definitely_not_a_real_program", &[]).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_run_in_dir() {
+        let dir = tempdir::TempDir::new("process_run_in_dir").unwrap();
+        let result = run_in_dir(dir.path(), "pwd", &["-P"]).unwrap();
+
+        assert!(result.success());
+        let cwd = std::path::PathBuf::from(result.stdout.trim());
+        assert_eq!(cwd, dir.path().canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_run_in_dir_relative_files() {
+        let dir = tempdir::TempDir::new("process_run_in_dir_git").unwrap();
+        run_in_dir(dir.path(), "git", &["init", "-q"]).unwrap();
+        // Only works if git ran in the new repo
+        let result = run_in_dir(dir.path(), "git", &["rev-parse", "--is-inside-work-tree"]).unwrap();
+        assert_eq!(result.stdout.trim(), "true");
+    }
+
+    #[test]
+    fn test_run_in_missing_dir() {
+        assert!(run_in_dir("definitely/not/a/dir", "git", &["--version"]).is_err());
+        assert!(run_in_dir("Cargo.toml", "git", &["--version"]).is_err());
+    }
+
+    // A tiny cargo project, that only builds if `builds` is true
+    fn cargo_project(builds: bool) -> TempWorkspace {
+        let project = TempWorkspace::new("cargo_fixture").unwrap();
+        let main = if builds { "fn main() {}" } else { "fn main() { let x: u8 = \"nope\"; }" };
+        fs::write(project.path().join("Cargo.toml"), "[package]\nname = \"fixture\"\nversion = \"0.1.0\"\n\n[workspace]\n").unwrap();
+        ensure_dir(project.path().join("src")).unwrap();
+        fs::write(project.path().join("src/main.rs"), main).unwrap();
+        project
+    }
+
+    #[test]
+    fn test_cargo_builds() {
+        assert!(cargo_builds(cargo_project(true).path()));
+        assert!(!cargo_builds(cargo_project(false).path()));
+        assert!(!cargo_builds("definitely/not/a/dir"));
+    }
+
+    #[test]
+    fn test_make_succeeds() {
+        if !command_available("make") {
+            return;
+        }
+        let project = TempWorkspace::new("make_fixture").unwrap();
+        fs::write(project.path().join("Makefile"), "all:\n\ttouch built\n").unwrap();
+        assert!(make_succeeds(project.path()));
+        assert!(project.path().join("built").exists());
+
+        fs::write(project.path().join("Makefile"), "all:\n\texit 2\n").unwrap();
+        assert!(!make_succeeds(project.path()));
+    }
+
+    #[test]
+    fn test_builds_with() {
+        let project = TempWorkspace::new("builds_with_fixture").unwrap();
+        assert!(builds_with(project.path(), "git", &["init", "-q"]).success());
+
+        let failed = builds_with(project.path(), "git", &["not-a-git-command"]);
+        assert!(!failed.success());
+        assert!(failed.stderr_contains("not-a-git-command"));
+
+        let missing = builds_with(project.path(), "definitely_not_a_real_program", &[]);
+        assert_eq!(missing.exit_code, None);
+        assert!(missing.stderr_contains("definitely_not_a_real_program"));
+    }
+
+    #[test]
+    fn test_command_available() {
+        assert!(command_available("cargo"));
+        assert!(!command_available("definitely_not_a_real_program"));
+        assert!(!command_available(""));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_command_available_path() {
+        assert!(command_available("/bin/sh"));
+        // Exists, but isn't executable
+        assert!(!command_available("./Cargo.toml"));
+        assert!(!command_available("./definitely_not_a_real_program"));
+    }
+}
@@ -4,13 +4,23 @@
 //! to make requests.
 
 // std uses
+use std::fs;
 use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::thread;
 use std::time::Duration;
 
 // external uses
 use serde::Serialize;
+use serde::de::DeserializeOwned;
+use reqwest::{Certificate, StatusCode};
 use reqwest::blocking::{Client, Response};
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, USER_AGENT};
+use anyhow::Context;
+
+// internal uses
+use crate::Result;
+use crate::dropbox::{QueryString, TestData};
 
 
 static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
@@ -62,7 +72,7 @@ fn construct_headers() -> HeaderMap {
 ///     }
 /// }
 /// ```
-pub fn get(url: &str) -> Result<Response, reqwest::Error> {
+pub fn get(url: &str) -> std::result::Result<Response, reqwest::Error> {
     let client = Client::builder()
         .user_agent(APP_USER_AGENT)
         .timeout(Duration::from_secs(6))
@@ -92,9 +102,61 @@ pub fn site_responds(url: &str) -> bool {
     false
 }
 
-/// Sends a POST request to the url with the given body
+// Sends the body as JSON and hands back the raw response.
+// `post_json` and friends are built on top of this.
+pub(crate) fn send_json<B: Serialize>(url: &str, body: B) -> std::result::Result<Response, reqwest::Error> {
+    let client = Client::builder()
+        .user_agent(APP_USER_AGENT)
+        .build()
+        .expect("Couldn't build reqwest client. This shouldn't happen.");
+
+    client.post(url)
+        .headers(construct_headers())
+        .json(&body)
+        .send()
+}
+
+/// Repeatedly sends GET requests to the url until it responds successfully (2xx),
+/// waiting `interval` between each try.
+///
+/// Returns true as soon as the url responds, or false if it never did
+/// after `attempts` tries. This is useful when a student's service takes a few
+/// seconds to start up, so a single [`site_responds`](crate::helpers::web::site_responds)
+/// would fail.
 ///
-/// `body` must be JSON serializable with `serde`
+/// ```no_run
+/// use std::time::Duration;
+/// use rubric::helpers::web;
+///
+/// // Try for about 10 seconds
+/// if web::wait_for_url("http://localhost:8000/", 10, Duration::from_secs(1)) {
+///     // The service is up, check its behavior
+/// }
+/// ```
+pub fn wait_for_url(url: &str, attempts: u32, interval: Duration) -> bool {
+    for attempt in 0..attempts {
+        if site_responds(url) {
+            return true;
+        }
+        // Don't wait after the last try
+        if attempt + 1 < attempts {
+            thread::sleep(interval);
+        }
+    }
+    false
+}
+
+/// Sends a POST request to the url with the given body, and
+/// deserializes the JSON response body.
+///
+/// `body` must be JSON serializable with `serde`, and the response
+/// body must deserialize into `T`. This returns the status code of the
+/// response along with the body, so you can show a student whatever
+/// the server sent back (like their grade).
+///
+/// This will return an `Err` if the request couldn't be sent, or if the
+/// response body couldn't be deserialized into `T`. If you don't care about
+/// the response, use [`post_json_ok`](crate::helpers::web::post_json_ok).
 ///
 /// ## Example
 /// ```rust
@@ -107,32 +169,47 @@ pub fn site_responds(url: &str) -> bool {
 /// data.insert("key", "value");
 ///
 /// // This url just returns whatever we send it
-/// let result = post_json("https://postman-echo.com/post", data);
+/// let result = post_json::<_, serde_json::Value>("https://postman-echo.com/post", data);
 ///
 /// // If the result went through
-/// if let Ok(resp) = result {
-///     // If the result contains a body
-///     if let Ok(text) = resp.text() {
-///         assert!(text.contains("value"));
-///     }
+/// if let Ok((status, body)) = result {
+///     assert!(status.is_success());
+///     assert_eq!(body["json"]["key"], "value");
 /// }
 /// ```
-pub fn post_json<B: Serialize>(url: &str, body: B) -> Result<Response, reqwest::Error> {
-    let client = Client::builder()
-        .user_agent(APP_USER_AGENT)
-        .build()
-        .expect("Couldn't build reqwest client. This shouldn't happen.");
+pub fn post_json<B: Serialize, T: DeserializeOwned>(url: &str, body: B) -> Result<(StatusCode, T)> {
+    let resp = send_json(url, body).context(format!("Couldn't send POST request to {}", url))?;
+    let status = resp.status();
+    let body = resp.json::<T>().context("Couldn't deserialize response body")?;
+    Ok((status, body))
+}
 
-    client.post(url)
-        .headers(construct_headers())
-        .json(&body)
-        .send()
+/// Sends a POST request to the url with the given body, and returns
+/// true if the request went through and the response was successful.
+///
+/// This is like [`post_json`](crate::helpers::web::post_json), but it
+/// ignores the response body.
+///
+/// ```no_run
+/// use rubric::helpers::web;
+/// use rubric::Submission;
+///
+/// let sub = Submission::new();
+/// if web::post_json_ok("http://localhost:8080/submit", &sub) {
+///     println!("Submission recorded!");
+/// }
+/// ```
+pub fn post_json_ok<B: Serialize>(url: &str, body: B) -> bool {
+    if let Ok(resp) = send_json(url, body) {
+        return resp.status().is_success();
+    }
+    false
 }
 
 
 /// Posts arbitrary data. This is like [`post_json`](crate::helpers::web::post_json) but
 /// it doesn't set the 'application/json' header.
-pub fn post(url: &str, body: &'static str) -> Result<Response, reqwest::Error> {
+pub fn post(url: &str, body: &'static str) -> std::result::Result<Response, reqwest::Error> {
     let client = Client::builder()
         .user_agent(APP_USER_AGENT)
         .build()
@@ -145,6 +222,129 @@ pub fn post(url: &str, body: &'static str) -> Result<Response, reqwest::Error> {
         .send()
 }
 
+/// Posts the data as a form, urlencoded (`application/x-www-form-urlencoded`).
+///
+/// This is what an HTML form sends. The dropbox accepts these at `/submit`
+/// too, and the form data becomes the submission's `data`. Keys are sorted,
+/// see [`QueryString`](crate::dropbox::QueryString).
+///
+/// ```no_run
+/// use rubric::data;
+/// use rubric::helpers::web;
+///
+/// let data = data! { "name" => "Luke Skywalker", "id" => "1234" };
+/// // Sends "id=1234&name=Luke+Skywalker"
+/// let resp = web::post_form("http://localhost:8080/submit", &data);
+/// ```
+pub fn post_form(url: &str, data: &TestData) -> std::result::Result<Response, reqwest::Error> {
+    let client = Client::builder()
+        .user_agent(APP_USER_AGENT)
+        .build()
+        .expect("Couldn't build reqwest client");
+
+    client.post(url)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(data.to_query_string())
+        .send()
+}
+
+
+/// Makes requests with TLS settings, for reaching servers that the
+/// functions in this module would refuse, like a student's service with a
+/// self-signed certificate.
+///
+/// Prefer [`root_certificate`](crate::helpers::web::WebClient::root_certificate)
+/// when you have the certificate. It trusts that one certificate on top of the
+/// usual ones, so the connection is still checked.
+/// [`accept_invalid_certs`](crate::helpers::web::WebClient::accept_invalid_certs) turns
+/// the checks off completely. Any certificate is accepted, even expired ones or ones for
+/// a different host, so anyone between you and the server could read or change
+/// what's sent. Only use it to reach servers you're grading, never to send
+/// anything secret.
+///
+/// ```no_run
+/// use rubric::helpers::web::WebClient;
+///
+/// let client = WebClient::new().root_certificate("student_ca.pem");
+/// let resp = client.get("https://localhost:8443/").unwrap();
+/// assert!(resp.status().is_success());
+///
+/// let insecure = WebClient::new().accept_invalid_certs(true);
+/// assert!(insecure.site_responds("https://localhost:8443/"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WebClient {
+    accept_invalid_certs: bool,
+    root_certificates: Vec<PathBuf>,
+}
+
+impl WebClient {
+    /// A client with the usual TLS checks, like the functions in this module
+    pub fn new() -> Self {
+        WebClient::default()
+    }
+
+    /// Accepts any certificate, valid or not. See the warning on [`WebClient`](crate::helpers::web::WebClient).
+    pub fn accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Trusts the certificate in a PEM file, along with the usual ones.
+    /// Can be used more than once.
+    pub fn root_certificate<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.root_certificates.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    // Builds a reqwest client with these settings
+    fn client(&self) -> Result<Client> {
+        let mut builder = Client::builder()
+            .user_agent(APP_USER_AGENT)
+            .timeout(Duration::from_secs(6))
+            .danger_accept_invalid_certs(self.accept_invalid_certs);
+        for path in &self.root_certificates {
+            let pem = fs::read(path)
+                .context(format!("Couldn't read certificate {}", path.display()))?;
+            let cert = Certificate::from_pem(&pem)
+                .context(format!("{} isn't a PEM certificate", path.display()))?;
+            builder = builder.add_root_certificate(cert);
+        }
+        builder.build().context("Couldn't build the web client")
+    }
+
+    /// Performs a GET request, like [`get`](crate::helpers::web::get).
+    ///
+    /// Returns an `Err` if a certificate couldn't be loaded, or the
+    /// request couldn't be sent (including if TLS fails).
+    pub fn get(&self, url: &str) -> Result<Response> {
+        self.client()?
+            .get(url)
+            .send()
+            .context(format!("Couldn't send GET request to {}", url))
+    }
+
+    /// Returns true if a GET request to the url was successful,
+    /// like [`site_responds`](crate::helpers::web::site_responds).
+    pub fn site_responds(&self, url: &str) -> bool {
+        matches!(self.get(url), Ok(resp) if resp.status().is_success())
+    }
+
+    /// Sends the body as JSON and deserializes the response,
+    /// like [`post_json`](crate::helpers::web::post_json).
+    pub fn post_json<B: Serialize, T: DeserializeOwned>(&self, url: &str, body: B) -> Result<(StatusCode, T)> {
+        let resp = self.client()?
+            .post(url)
+            .headers(construct_headers())
+            .json(&body)
+            .send()
+            .context(format!("Couldn't send POST request to {}", url))?;
+        let status = resp.status();
+        let body = resp.json::<T>().context("Couldn't deserialize response body")?;
+        Ok((status, body))
+    }
+}
+
 
 /// Gets the public IPv4 address of the machine,
 /// if there is one.
@@ -180,6 +380,9 @@ mod tests {
     use super::*;
     use crate::dropbox::Submission;
     use crate::data;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use serde::Deserialize;
 
 
     #[test]
@@ -195,6 +398,191 @@ mod tests {
         assert!(text.contains("postman-echo.com"));
     }
 
+    // Starts a server on a random port that reads one request and
+    // responds with the given JSON body. Returns the url to POST to.
+    fn mock_server(body: &'static str) -> String {
+        mock_server_with(vec!["200 OK"], body)
+    }
+
+    // Reads one whole request from the stream, then writes back whatever
+    // `respond` makes out of the request's content type and body.
+    fn serve_one<S: Read + Write>(mut stream: S, respond: impl Fn(&str, &[u8]) -> String) {
+        let mut reader = BufReader::new(&mut stream);
+        let mut content_length = 0;
+        let mut content_type = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.to_lowercase().starts_with("content-length:") {
+                content_length = line[15..].trim().parse().unwrap();
+            }
+            if line.to_lowercase().starts_with("content-type:") {
+                content_type = line[13..].trim().to_string();
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut req_body = vec![0; content_length];
+        reader.read_exact(&mut req_body).unwrap();
+
+        let resp = respond(&content_type, &req_body);
+        stream.write_all(resp.as_bytes()).unwrap();
+    }
+
+    // Like mock_server, but handles one request per status given,
+    // responding with each status in order.
+    fn mock_server_with(statuses: Vec<&'static str>, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        thread::spawn(move || for status in statuses {
+            let (stream, _) = listener.accept().unwrap();
+            serve_one(stream, |_, _| format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            ));
+        });
+
+        format!("http://{}/submit", addr)
+    }
+
+    // Starts an HTTPS server on a random port with the self-signed certificate
+    // in test_data/tls. Every request gets `{"ok": true}`. Returns the port.
+    fn tls_server() -> u16 {
+        let cert = fs::read("test_data/tls/localhost.crt").unwrap();
+        let key = fs::read("test_data/tls/localhost.key").unwrap();
+        let identity = native_tls::Identity::from_pkcs8(&cert, &key).unwrap();
+        let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        thread::spawn(move || for stream in listener.incoming() {
+            // Clients that reject the certificate hang up during the handshake
+            let stream = match acceptor.accept(stream.unwrap()) {
+                Ok(stream) => stream,
+                Err(_) => continue
+            };
+            serve_one(stream, |_, _| {
+                let body = r#"{"ok": true}"#;
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            });
+        });
+
+        port
+    }
+
+    #[test]
+    fn test_web_client_self_signed() {
+        let port = tls_server();
+        let url = format!("https://localhost:{}/", port);
+
+        // The usual checks reject it
+        assert!(!site_responds(&url));
+        assert!(!WebClient::new().site_responds(&url));
+
+        let insecure = WebClient::new().accept_invalid_certs(true);
+        assert!(insecure.site_responds(&url));
+
+        let trusting = WebClient::new().root_certificate("test_data/tls/localhost.crt");
+        assert!(trusting.get(&url).unwrap().status().is_success());
+        let (status, body) = trusting.post_json::<_, serde_json::Value>(&url, data! { "k" => "v" }).unwrap();
+        assert!(status.is_success());
+        assert_eq!(body["ok"], true);
+    }
+
+    #[test]
+    fn test_web_client_bad_certificate() {
+        let missing = WebClient::new().root_certificate("test_data/tls/missing.crt");
+        assert!(missing.get("https://localhost/").is_err());
+        let not_pem = WebClient::new().root_certificate("test_data/test_rubric.yml");
+        assert!(!not_pem.site_responds("https://localhost/"));
+    }
+
+    // Starts a server on a random port that reads one request and responds
+    // with its content type and body, on separate lines.
+    fn echo_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_one(stream, |content_type, req_body| {
+                let body = format!("{}\n{}", content_type, String::from_utf8_lossy(req_body));
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            });
+        });
+
+        format!("http://{}/submit", addr)
+    }
+
+    #[test]
+    fn test_post_form() {
+        let url = echo_server();
+        let data = data! { "name" => "Luke Skywalker", "id" => "1234", "note" => "a&b=c" };
+
+        let resp = post_form(&url, &data).unwrap();
+        assert!(resp.status().is_success());
+
+        let text = resp.text().unwrap();
+        let mut lines = text.lines();
+        assert_eq!(lines.next().unwrap(), "application/x-www-form-urlencoded");
+        let body = lines.next().unwrap();
+        assert_eq!(body, "id=1234&name=Luke+Skywalker&note=a%26b%3Dc");
+        assert_eq!(TestData::from_query_string(body), data);
+    }
+
+    #[test]
+    fn test_wait_for_url() {
+        // Isn't ready for the first two requests
+        let url = mock_server_with(vec!["503 Service Unavailable", "503 Service Unavailable", "200 OK"], "{}");
+        assert!(wait_for_url(&url, 5, Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_wait_for_url_never_up() {
+        let url = mock_server_with(vec!["503 Service Unavailable"; 3], "{}");
+        assert!(!wait_for_url(&url, 3, Duration::from_millis(10)));
+    }
+
+    #[derive(Deserialize)]
+    struct Grade {
+        grade: isize,
+    }
+
+    #[test]
+    fn test_post_json_returns_body() {
+        let url = mock_server(r#"{"grade":42}"#);
+        let sub = Submission::new();
+
+        let (status, body) = post_json::<_, Grade>(&url, &sub).unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.grade, 42);
+    }
+
+    #[test]
+    fn test_post_json_bad_body() {
+        let url = mock_server("not json");
+        assert!(post_json::<_, Grade>(&url, Submission::new()).is_err());
+    }
+
+    #[test]
+    fn test_post_json_ok() {
+        let url = mock_server("{}");
+        assert!(post_json_ok(&url, Submission::new()));
+    }
+
     #[test]
     #[ignore]
     fn test_post_json() {
@@ -202,12 +590,12 @@ mod tests {
             "name" => "luke"
         });
         let url = "https://postman-echo.com/post";
-        let res = post_json(url, &sub);
+        let res = post_json::<_, serde_json::Value>(url, &sub);
 
         assert!(res.is_ok());
 
-        let text = res.unwrap().text().unwrap();
-        assert!(text.contains(r#""name":"luke""#));
+        let (_, body) = res.unwrap();
+        assert_eq!(body["json"]["data"]["name"], "luke");
     }
 
     #[test]
@@ -16,6 +16,11 @@ extern crate anyhow;
 extern crate serde;
 extern crate regex;
 extern crate paris;
+extern crate sha2;
+extern crate form_urlencoded;
+#[cfg(feature = "schema")] extern crate schemars;
+#[cfg(feature = "tui")] extern crate crossterm;
+#[cfg(feature = "watch")] extern crate notify;
 
 // External testing crates
 #[cfg(test)]
@@ -30,6 +35,10 @@ pub mod helpers;
 pub mod dropbox;
 pub mod rubric;
 pub mod report;
+#[cfg(feature = "tui")]
+pub mod review;
+#[cfg(feature = "watch")]
+pub mod watch;
 mod macros;
 
 
@@ -88,7 +88,7 @@ macro_rules! attach {
         $(
             let chunks: Vec<&str> = std::stringify!($func).split("::").collect();
             let func_name = chunks.into_iter().next_back().unwrap();
-            if let Some(c) = $rubric.get(func_name) {
+            if let Some(c) = $rubric.get_mut(func_name) {
                 c.attach(Box::new($func));
             } else {
                 panic!("Criteria with func `{}` not found. `func` field and function name must match exactly", func_name);
@@ -98,7 +98,7 @@ macro_rules! attach {
     // Long way
     ( $rubric:ident, $($func_name:literal => $func:path),* ) => {
         $(
-            if let Some(c) = $rubric.get($func_name) {
+            if let Some(c) = $rubric.get_mut($func_name) {
                 c.attach(Box::new($func));
             } else {
                 panic!("Criterion with func {} not found, can't attach function", $func_name);
@@ -106,3 +106,35 @@ macro_rules! attach {
         )+
     };
 }
+
+
+/// Attaches plain functions to criteria in a rubric, by func, without panicking.
+///
+/// This is like the long form of [`attach!`](../macro.attach.html), but it returns
+/// a [`Result`](crate::Result) instead of panicking. If any of the funcs aren't in the
+/// rubric, the `Err` names every one of them and nothing is attached. That catches a
+/// func that was renamed in the yaml but not in the code (or the other way around).
+/// See [`Rubric::attach_funcs`](crate::rubric::Rubric::attach_funcs).
+///
+/// The functions have to be plain `fn(&TestData) -> bool` items, not closures.
+///
+/// ## Example
+/// ```rust
+/// # use rubric::{Rubric, TestData, yaml, attach_funcs};
+/// fn repo_exists(_: &TestData) -> bool { true }
+/// fn tests_pass(_: &TestData) -> bool { true }
+///
+/// let mut rubric = Rubric::from_yaml(yaml!("../test_data/test_rubric.yml").unwrap()).unwrap();
+/// attach_funcs!(rubric, {
+///     "first_crit" => repo_exists,
+///     "second_crit" => tests_pass
+/// }).expect("The rubric and the code don't match");
+/// ```
+#[macro_export]
+macro_rules! attach_funcs {
+    ( $rubric:expr, { $($func_name:literal => $func:path),+ $(,)? } ) => {
+        $rubric.attach_funcs(&[
+            $( ($func_name, $func as $crate::rubric::TestFn) ),+
+        ])
+    };
+}
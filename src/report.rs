@@ -1,8 +1,275 @@
 /// This module is responsible for printing the rubric
 /// and submission after grading
-use paris::Logger;
+use std::env;
+use std::fmt;
+use std::sync::RwLock;
 
-use crate::Rubric;
+use paris::{Logger, formatter::Formatter};
+use serde::Serialize;
+use serde_json::{json, Value};
+use anyhow::{anyhow, Context};
+
+use crate::{Rubric, Submission, Result};
+use crate::rubric::Criterion;
+
+
+/// The symbols shown next to criteria when printing plain text.
+///
+/// Used by the alternate `Display` of a [`Criterion`](crate::rubric::Criterion)
+/// (`{:#}`), the `Display` of a [`Rubric`](crate::rubric::Rubric), and any
+/// report printed without color.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbols {
+    pub pass: String,
+    pub fail: String,
+    pub skip: String,
+}
+
+impl Symbols {
+    /// `✓`, `✗`, and `-`. This is the default.
+    pub fn unicode() -> Self {
+        Symbols {
+            pass: String::from("✓"),
+            fail: String::from("✗"),
+            skip: String::from("-"),
+        }
+    }
+
+    /// `[P]`, `[F]`, and `[S]`. Good for screen readers and log files.
+    pub fn ascii() -> Self {
+        Symbols {
+            pass: String::from("[P]"),
+            fail: String::from("[F]"),
+            skip: String::from("[S]"),
+        }
+    }
+}
+
+impl Default for Symbols {
+    fn default() -> Self {
+        Symbols::unicode()
+    }
+}
+
+// `None` until they're set, then they're the same on every thread
+static SYMBOLS: RwLock<Option<Symbols>> = RwLock::new(None);
+
+/// Sets the symbols used when printing, on every thread.
+///
+/// Like [`set_labels`](crate::report::set_labels), this is meant to be done
+/// once, before grading.
+///
+/// ```rust
+/// use rubric::report::{self, Symbols};
+///
+/// report::set_symbols(Symbols::ascii());
+/// assert_eq!(report::symbols().pass, "[P]");
+/// ```
+pub fn set_symbols(symbols: Symbols) {
+    *SYMBOLS.write().unwrap_or_else(|e| e.into_inner()) = Some(symbols);
+}
+
+/// Returns the symbols used when printing.
+pub fn symbols() -> Symbols {
+    SYMBOLS.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default()
+}
+
+/// The fixed words used when printing, so they can be translated.
+///
+/// These are only the labels around the rubric. Criterion names and
+/// messages are printed the way they were written.
+///
+/// ```rust
+/// use rubric::report::{self, Labels};
+///
+/// report::set_labels(Labels {
+///     grade: String::from("Nota"),
+///     points: String::from("ptos"),
+///     skipped: String::from("omitido"),
+///     not_tested: String::from("Sin probar"),
+///     feedback: String::from("Comentarios"),
+///     bonus_available: String::from("extra disponible"),
+///     not_applicable: String::from("no aplica"),
+/// });
+/// assert_eq!(report::labels().grade, "Nota");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Labels {
+    /// Before the grade, like `"Grade: 8/10"`
+    pub grade: String,
+    /// After points, like `"8 / 10 pts"`
+    pub points: String,
+    /// For criteria that were skipped
+    pub skipped: String,
+    /// For criteria that haven't been tested yet
+    pub not_tested: String,
+    /// Before a grader's feedback
+    pub feedback: String,
+    /// After the bonus points, like `"(+5 bonus available)"`
+    pub bonus_available: String,
+    /// Why a criterion was skipped, when it wasn't given a reason
+    pub not_applicable: String,
+}
+
+impl Labels {
+    /// The English labels. This is the default.
+    pub fn english() -> Self {
+        Labels {
+            grade: String::from("Grade"),
+            points: String::from("pts"),
+            skipped: String::from("skipped"),
+            not_tested: String::from("Not Tested"),
+            feedback: String::from("Feedback"),
+            bonus_available: String::from("bonus available"),
+            not_applicable: String::from("not applicable"),
+        }
+    }
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Labels::english()
+    }
+}
+
+// `None` until they're set, then they're the same on every thread
+static LABELS: RwLock<Option<Labels>> = RwLock::new(None);
+
+/// Sets the labels used when printing, on every thread.
+///
+/// This is meant to be done once, before grading. Reports printed by the
+/// dropbox server or graded in parallel use these too.
+pub fn set_labels(labels: Labels) {
+    *LABELS.write().unwrap_or_else(|e| e.into_inner()) = Some(labels);
+}
+
+/// Returns the labels used when printing.
+pub fn labels() -> Labels {
+    LABELS.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default()
+}
+
+/// How [`Rubric::display_with`](crate::rubric::Rubric::display_with) shows what criteria are worth.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WorthFormat {
+    /// Points, like `"10 / 25 pts"`. This is the default.
+    #[default]
+    Points,
+    /// A percentage of the rubric's total, like `"10 / 25%"`
+    Percent,
+}
+
+// Breaks a line between words so each piece fits in `width` characters.
+// The line's leading whitespace is kept on every piece. A word that's
+// longer than the width gets a piece to itself.
+pub(crate) fn wrap(line: &str, width: usize) -> Vec<String> {
+    let words = line.trim_start();
+    let lead = &line[..line.len() - words.len()];
+    let lead_len = lead.chars().count();
+    let mut lines = Vec::new();
+    let mut current = String::from(lead);
+    let mut len = lead_len;
+    for word in words.split_whitespace() {
+        let word_len = word.chars().count();
+        if len > lead_len && len + 1 + word_len > width {
+            lines.push(current);
+            current = String::from(lead);
+            len = lead_len;
+        }
+        if len > lead_len {
+            current.push(' ');
+            len += 1;
+        }
+        current.push_str(word);
+        len += word_len;
+    }
+    lines.push(current);
+    lines
+}
+
+// Points as a percentage of the total, with at most one decimal
+// place, like "12.5" or "25". A total of 0 is always "0".
+pub(crate) fn percent_of(points: isize, total: isize) -> String {
+    percent_of_f64(points as f64, total)
+}
+
+// Same as `percent_of`, for points that can have a fraction
+pub(crate) fn percent_of_f64(points: f64, total: isize) -> String {
+    if total == 0 {
+        return String::from("0");
+    }
+    let percent = format!("{:.1}", points / total as f64 * 100.0);
+    String::from(percent.trim_end_matches(".0"))
+}
+
+/// Returns true if the `NO_COLOR` environment variable is set
+/// (to anything but an empty string). See <https://no-color.org>.
+pub fn no_color() -> bool {
+    matches!(env::var_os("NO_COLOR"), Some(v) if !v.is_empty())
+}
+
+// Goes after the grade when there are bonus points, like "Grade: 8/10 (+5 bonus available)"
+pub(crate) fn bonus_note(bonus: isize) -> String {
+    if bonus > 0 {
+        format!(" (+{} {})", bonus, labels().bonus_available)
+    } else {
+        String::new()
+    }
+}
+
+// Draws a table with a box around it and between the cells. Columns are as wide as
+// their widest cell. A cell can have a color (a paris tag, like "green"), which is
+// left off if there's no color. The box is ASCII if the symbols are.
+pub(crate) fn boxed_table(header: &[&str], rows: &[Vec<(String, Option<&str>)>]) -> String {
+    let symbols = symbols();
+    let ascii = [&symbols.pass, &symbols.fail, &symbols.skip].iter().all(|s| s.is_ascii());
+    // Horizontal, vertical, then the corners and joins of the top, middle, and bottom lines
+    let (h, v, top, mid, bottom) = if ascii {
+        ("-", "|", ["+", "+", "+"], ["+", "+", "+"], ["+", "+", "+"])
+    } else {
+        ("─", "│", ["┌", "┬", "┐"], ["├", "┼", "┤"], ["└", "┴", "┘"])
+    };
+    let color = !no_color();
+
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, (text, _)) in widths.iter_mut().zip(row) {
+            *width = (*width).max(text.chars().count());
+        }
+    }
+
+    let line = |[left, join, right]: [&str; 3]| -> String {
+        let cells: Vec<String> = widths.iter().map(|w| h.repeat(w + 2)).collect();
+        format!("{}{}{}\n", left, cells.join(join), right)
+    };
+    let row = |cells: Vec<(String, Option<&str>)>| -> String {
+        let cells: Vec<String> = cells.into_iter().zip(&widths).map(|((text, tag), width)| {
+            let padding = " ".repeat(width - text.chars().count());
+            match tag {
+                Some(tag) if color => format!(" {}{} ", Formatter::new().colorize(&format!("<{}>{}</>", tag, text)), padding),
+                _ => format!(" {}{} ", text, padding),
+            }
+        }).collect();
+        format!("{}{}{}\n", v, cells.join(v), v)
+    };
+
+    let mut out = line(top);
+    out.push_str(&row(header.iter().map(|h| (h.to_string(), None)).collect()));
+    out.push_str(&line(mid));
+    for cells in rows {
+        out.push_str(&row(cells.clone()));
+    }
+    out.push_str(&line(bottom));
+    out
+}
+
+/// Prints the rubric as plain text, with no color. Criteria are marked
+/// with the current [`symbols`](crate::report::symbols).
+///
+/// [`short`](crate::report::short) and [`long`](crate::report::long) print
+/// this instead if `NO_COLOR` is set.
+pub fn plain(rubric: &Rubric) {
+    print!("{}", rubric);
+}
 
 
 /// Prints a very short report of the rubric, with
@@ -22,6 +289,9 @@ use crate::Rubric;
 /// ℹ Grade: 79/80
 /// ```
 pub fn short(mut rubric: &mut Rubric) {
+    if no_color() {
+        return plain(rubric);
+    }
     let mut log = Logger::new();
 
     components::rubric_name(&rubric);
@@ -37,6 +307,9 @@ pub fn short(mut rubric: &mut Rubric) {
 
 /// Prints the full rubric and all criteria
 pub fn long(mut rubric: &mut Rubric) {
+    if no_color() {
+        return plain(rubric);
+    }
     let mut log = Logger::new();
 
     components::rubric_name(&rubric);
@@ -55,6 +328,486 @@ pub fn long(mut rubric: &mut Rubric) {
     components::current_time();
 }
 
+/// Same as [`short`](crate::report::short), with the submission's
+/// [`feedback`](crate::dropbox::submission::Submission::feedback) at the end.
+pub fn short_for(rubric: &mut Rubric, submission: &Submission) {
+    short(rubric);
+    feedback(submission);
+}
+
+/// Same as [`long`](crate::report::long), with the submission's
+/// [`feedback`](crate::dropbox::submission::Submission::feedback) at the end.
+pub fn long_for(rubric: &mut Rubric, submission: &Submission) {
+    long(rubric);
+    feedback(submission);
+}
+
+// Prints a submission's feedback, if it has any
+fn feedback(submission: &Submission) {
+    if submission.feedback.is_empty() {
+        return;
+    }
+    if no_color() {
+        print!("\n{}", feedback_text(submission));
+    } else {
+        components::feedback(submission);
+    }
+}
+
+// The feedback as plain text, like the end of a `GradeReport`
+fn feedback_text(submission: &Submission) -> String {
+    let mut out = format!("{}:\n", labels().feedback);
+    for msg in &submission.feedback {
+        out.push_str(&format!("    {}\n", msg));
+    }
+    out
+}
+
+
+/// Renders a standalone HTML report for a whole class.
+///
+/// The report has a table with one row per submission, showing the
+/// student (the `id` in the submission data, if there is one), their grade,
+/// and whether they passed each criterion in the rubric. Below that are
+/// some simple stats: mean, median, and a histogram of grades.
+///
+/// All the CSS is inline, so you can just write the string to a `.html` file
+/// and open it or send it anywhere.
+///
+/// ## Example
+/// ```rust
+/// # use rubric::{Rubric, Submission, data, report};
+/// let rubric = Rubric::default();
+/// let mut sub = Submission::from_data(data! { "id" => "1234" });
+/// sub.grade = 42;
+///
+/// let html = report::render_class_report(&[sub], &rubric);
+/// assert!(html.contains("1234"));
+/// ```
+pub fn render_class_report(submissions: &[Submission], rubric: &Rubric) -> String {
+    let mut rows = String::new();
+    for (i, sub) in submissions.iter().enumerate() {
+        rows.push_str(&html::student_row(i, sub, rubric));
+    }
+
+    let mut grades: Vec<isize> = submissions.iter().map(|s| s.grade).collect();
+    grades.sort();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{name}</title>
+<style>{style}</style>
+</head>
+<body>
+<h1>{name}</h1>
+<table>
+<tr><th>Student</th><th>Grade</th>{criteria}</tr>
+{rows}</table>
+<h2>Statistics</h2>
+<ul>
+<li>Submissions: {count}</li>
+<li>Mean: {mean:.2}</li>
+<li>Median: {median:.2}</li>
+</ul>
+<h2>Distribution</h2>
+<table>
+{histogram}</table>
+</body>
+</html>
+"#,
+        name = html::escape(&rubric.name),
+        style = html::STYLE,
+        criteria = html::criteria_headers(rubric),
+        rows = rows,
+        count = grades.len(),
+        mean = html::mean(&grades),
+        median = html::median(&grades),
+        histogram = html::histogram(&grades, rubric.total_points())
+    )
+}
+
+
+/// Pieces of the class report. These all return HTML strings.
+mod html {
+    use crate::{Rubric, Submission};
+
+    pub const STYLE: &str = "body{font-family:sans-serif;margin:2em;}\
+        table{border-collapse:collapse;margin-bottom:1em;}\
+        th,td{border:1px solid #ccc;padding:4px 8px;text-align:left;}\
+        th{background:#eee;}\
+        .pass{color:#2a7f2a;}\
+        .fail{color:#b22222;}\
+        .bar{background:#4a7fb5;height:1em;}";
+
+    /// Escapes the characters that mean something in HTML
+    pub fn escape(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    pub fn criteria_headers(rubric: &Rubric) -> String {
+        let mut headers = String::new();
+        for crit in rubric.criteria() {
+            headers.push_str(&format!("<th>{}</th>", escape(&crit.name)));
+        }
+        headers
+    }
+
+    pub fn student_row(i: usize, sub: &Submission, rubric: &Rubric) -> String {
+        // Fall back to the position in the class if there's no id
+        let student = match sub.data.get("id") {
+            Some(id) => escape(id),
+            None => format!("#{}", i + 1),
+        };
+
+        let mut row = format!("<tr><td>{}</td><td>{}/{}</td>", student, sub.grade, rubric.total_points());
+        for crit in rubric.criteria() {
+            if sub.has_passed(&crit.name) {
+                row.push_str("<td class=\"pass\">&#10004;</td>");
+            } else {
+                row.push_str("<td class=\"fail\">&#10008;</td>");
+            }
+        }
+        row.push_str("</tr>\n");
+        row
+    }
+
+    pub fn mean(grades: &[isize]) -> f64 {
+        if grades.is_empty() {
+            return 0.0;
+        }
+        grades.iter().sum::<isize>() as f64 / grades.len() as f64
+    }
+
+    /// `grades` must be sorted
+    pub fn median(grades: &[isize]) -> f64 {
+        let len = grades.len();
+        if len == 0 {
+            return 0.0;
+        }
+        if len % 2 == 0 {
+            (grades[len / 2 - 1] + grades[len / 2]) as f64 / 2.0
+        } else {
+            grades[len / 2] as f64
+        }
+    }
+
+    /// Groups grades into 10 buckets by percentage of the total,
+    /// with one row and bar for each bucket.
+    pub fn histogram(grades: &[isize], total: isize) -> String {
+        let mut buckets = [0usize; 10];
+        for grade in grades {
+            let percent = if total == 0 { 0.0 } else { *grade as f64 / total as f64 * 100.0 };
+            // 100% goes in the top bucket, anything out of range is clamped
+            let bucket = ((percent / 10.0) as isize).clamp(0, 9) as usize;
+            buckets[bucket] += 1;
+        }
+
+        let max = *buckets.iter().max().unwrap_or(&0);
+        let mut out = String::new();
+        for (i, count) in buckets.iter().enumerate() {
+            let width = if max == 0 { 0 } else { count * 200 / max };
+            out.push_str(&format!(
+                "<tr><td>{}-{}%</td><td>{}</td><td><div class=\"bar\" style=\"width:{}px\"></div></td></tr>\n",
+                i * 10,
+                i * 10 + 10,
+                count,
+                width
+            ));
+        }
+        out
+    }
+}
+
+
+/// Builds a single JSON value with both the rubric and a graded submission.
+///
+/// This is meant for archiving. The artifact has everything needed to show
+/// the report again later, even if the rubric's YAML has changed or is gone.
+/// It looks like this:
+/// ```json
+/// {
+///   "rubric": { "name": "...", "desc": null, "total": 80, "criteria": [ ... ] },
+///   "submission": { ... },
+///   "results": [ { "name": "...", "func": "...", "result": "passed", "awarded": 50, "possible": 50, "message": "...", "explanation": null, "skip_reason": null } ]
+/// }
+/// ```
+/// A criterion's `result` is one of `passed`, `failed`, or `skipped`. The
+/// `awarded` and `possible` points include the criterion's `weight`, if it has one.
+/// The `message` is the one the student saw, like a named failure picked by the
+/// criterion's `outcome_test`. It's `null` for a skipped criterion.
+/// The `explanation` is the criterion's [`explanation`](crate::rubric::Criterion::explanation),
+/// if it had an `explain` closure. The rubric's criteria have a whole number `worth`
+/// unless it has a fraction, like `2.5`. A skipped criterion's `skip_reason` is set if
+/// it was skipped for a reason, like missing data.
+/// Use [`render_artifact`](crate::report::render_artifact) to turn it back into a report.
+pub fn grade_artifact(rubric: &Rubric, submission: &Submission) -> Value {
+    let criteria: Vec<Value> = rubric.criteria().iter().map(|c| json!({
+        "name": c.name,
+        "func": c.func,
+        "desc": c.desc,
+        "worth": c.worth_json(),
+        "weight": c.weight,
+        "index": c.index,
+        "messages": [c.success_message(), c.failure_message()],
+        "hide": c.hide,
+        "required": c.required,
+        "bonus": c.bonus,
+        "penalty": c.penalty,
+    })).collect();
+
+    let results: Vec<Value> = rubric.criteria().iter().map(|c| {
+        let (result, awarded, message) = if submission.has_passed(&c.name) {
+            ("passed", awarded(submission, c), Some(result_message(submission, c)))
+        } else if submission.has_failed(&c.name) {
+            ("failed", 0, Some(result_message(submission, c)))
+        } else {
+            ("skipped", 0, None)
+        };
+        // Whole numbers stay whole, like the criterion's worth
+        let possible = match c.worth_f64 {
+            Some(_) => json!(c.exact_worth()),
+            None => json!(c.weighted_worth())
+        };
+        json!({
+            "name": c.name,
+            "func": c.func,
+            "result": result,
+            "awarded": awarded,
+            "possible": possible,
+            "message": message,
+            "explanation": c.explanation,
+            "skip_reason": c.skip_reason,
+        })
+    }).collect();
+
+    json!({
+        "rubric": {
+            "name": rubric.name,
+            "desc": rubric.desc,
+            "total": rubric.total_points(),
+            "bonus": rubric.bonus_total(),
+            "criteria": criteria,
+        },
+        "submission": submission,
+        "results": results,
+    })
+}
+
+/// Renders an artifact from [`grade_artifact`](crate::report::grade_artifact)
+/// as a plain text report.
+///
+/// Returns an `Err` if the artifact is missing anything it needs.
+///
+/// ```rust
+/// # use rubric::{Rubric, Submission, report};
+/// let rubric = Rubric::default();
+/// let sub = Submission::new();
+///
+/// let artifact = report::grade_artifact(&rubric, &sub);
+/// let text = report::render_artifact(&artifact).unwrap();
+/// assert!(text.contains("Grade: 0/0"));
+/// ```
+pub fn render_artifact(artifact: &Value) -> Result<String> {
+    let rubric = &artifact["rubric"];
+    let name = rubric["name"].as_str().ok_or_else(|| anyhow!("Artifact is missing the rubric name"))?;
+    let total = rubric["total"].as_i64().ok_or_else(|| anyhow!("Artifact is missing the rubric total"))?;
+    let submission: Submission = serde_json::from_value(artifact["submission"].clone())
+        .context("Artifact has an invalid submission")?;
+    let results = artifact["results"].as_array().ok_or_else(|| anyhow!("Artifact is missing results"))?;
+
+    // Older artifacts don't have a bonus
+    let bonus = rubric["bonus"].as_i64().unwrap_or(0);
+
+    let symbols = symbols();
+    let labels = labels();
+    let mut out = format!("{}\n{}: {}/{}{}\n\n", name, labels.grade, submission.grade, total, bonus_note(bonus as isize));
+    for (result, crit) in results.iter().zip(rubric["criteria"].as_array().unwrap_or(&Vec::new())) {
+        let crit_name = result["name"].as_str().unwrap_or_default();
+        // Older artifacts only have the criterion's messages
+        let message = |i: usize| result["message"].as_str()
+            .or_else(|| crit["messages"][i].as_str())
+            .unwrap_or_default();
+        let line = match result["result"].as_str() {
+            Some("passed") => format!(
+                "{} {} ({} / {} {})  {}",
+                symbols.pass, crit_name, result["awarded"], result["possible"], labels.points, message(0)
+            ),
+            Some("failed") => format!(
+                "{} {} ({} / {} {})  {}",
+                symbols.fail, crit_name, result["awarded"], result["possible"], labels.points, message(1)
+            ),
+            _ => format!(
+                "{} {}  {} ({})",
+                symbols.skip, crit_name, labels.skipped, result["skip_reason"].as_str().unwrap_or(&labels.not_applicable)
+            ),
+        };
+        out.push_str(&line);
+        out.push('\n');
+        if let Some(explanation) = result["explanation"].as_str() {
+            out.push_str(&format!("    {}\n", explanation));
+        }
+    }
+
+    if !submission.feedback.is_empty() {
+        out.push('\n');
+        out.push_str(&feedback_text(&submission));
+    }
+    Ok(out)
+}
+
+
+/// A graded submission's results, ready to print or serialize.
+///
+/// This has the numbers from grading and a breakdown of each criterion, so it
+/// can be shown in a terminal with `Display`, or sent anywhere as JSON. The `Display`
+/// looks like the [`Rubric`](crate::rubric::Rubric)'s, with each criterion's message
+/// and the percentage. The symbols are colored unless [`no_color`](crate::report::no_color).
+/// Hidden criteria are in the breakdown, but aren't printed.
+///
+/// Get one from [`Rubric::grade_and_report`](crate::rubric::Rubric::grade_and_report),
+/// or [`GradeReport::new`](crate::report::GradeReport::new) for a submission that's
+/// already graded.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GradeReport {
+    /// The rubric's name
+    pub rubric: String,
+    /// The submission's grade, after any late penalty
+    pub grade: isize,
+    /// The rubric's total points, without bonus
+    pub total: isize,
+    /// The grade as a percentage of the total, see
+    /// [`Submission::grade_percentage`](crate::dropbox::submission::Submission::grade_percentage)
+    pub percentage: f64,
+    /// The bonus points the rubric has available
+    pub bonus: isize,
+    /// Each criterion, in the rubric's order
+    pub criteria: Vec<CriterionReport>,
+    /// The submission's feedback
+    pub feedback: Vec<String>,
+}
+
+/// How one criterion went, in a [`GradeReport`](crate::report::GradeReport).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CriterionReport {
+    /// The criterion's name
+    pub name: String,
+    /// The criterion's func
+    pub func: String,
+    /// If it passed, or `None` if it wasn't tested
+    pub status: Option<bool>,
+    /// If it was skipped
+    pub skipped: bool,
+    /// The points it gave, negative for a penalty. These add up to the grade,
+    /// see [`CriterionResult::awarded`](crate::dropbox::CriterionResult::awarded).
+    pub awarded: isize,
+    /// The points it could give, with its fraction if it has one
+    pub possible: f64,
+    /// The success or failure message, whichever applies
+    pub message: String,
+    /// If it's hidden from students
+    pub hidden: bool,
+}
+
+// The points a criterion gave a submission, from its results if it's there
+fn awarded(submission: &Submission, crit: &Criterion) -> isize {
+    submission.results.iter()
+        .find(|r| r.func == crit.func)
+        .map_or_else(|| crit.points_awarded().unwrap_or(0), |r| r.awarded)
+}
+
+// The message a criterion showed for a submission, from its results if it's there
+fn result_message(submission: &Submission, crit: &Criterion) -> String {
+    submission.results.iter()
+        .find(|r| r.func == crit.func && !r.message.is_empty())
+        .map_or_else(|| crit.status_message(), |r| r.message.clone())
+}
+
+impl GradeReport {
+    /// Builds a report of a submission that's already been graded against the rubric.
+    ///
+    /// The criteria are taken from the rubric, so it shouldn't have been reset
+    /// or used to grade anything else since.
+    pub fn new(rubric: &Rubric, submission: &Submission) -> Self {
+        let criteria = rubric.criteria().iter()
+            .map(|crit| CriterionReport {
+                name: crit.name.clone(),
+                func: crit.func.clone(),
+                status: crit.status,
+                skipped: crit.skipped,
+                awarded: awarded(submission, crit),
+                possible: crit.shown_worth(),
+                message: result_message(submission, crit),
+                hidden: crit.hide,
+            })
+            .collect();
+
+        GradeReport {
+            rubric: rubric.name.clone(),
+            grade: submission.grade,
+            total: rubric.total_points(),
+            percentage: submission.grade_percentage(rubric),
+            bonus: rubric.bonus_total(),
+            criteria,
+            feedback: submission.feedback.clone(),
+        }
+    }
+}
+
+impl fmt::Display for GradeReport {
+    /// ```text
+    /// Test Rubric
+    /// ✓ First Criterion (50 / 50 pts)  Passed!
+    /// ✗ Second Criterion (0 / 30 pts)  Failed
+    /// Grade: 50/80 (62.5%)
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbols = symbols();
+        let labels = labels();
+        let color = !no_color();
+
+        writeln!(f, "{}", self.rubric)?;
+        for crit in self.criteria.iter().filter(|c| !c.hidden) {
+            let (symbol, tag) = match (crit.skipped, crit.status) {
+                (true, _) => (symbols.skip.as_str(), None),
+                (false, Some(true)) => (symbols.pass.as_str(), Some("green")),
+                (false, Some(false)) => (symbols.fail.as_str(), Some("red")),
+                (false, None) => (" ", None),
+            };
+            let symbol = match tag {
+                Some(tag) if color => Formatter::new().colorize(&format!("<{}>{}</>", tag, symbol)),
+                _ => symbol.to_owned(),
+            };
+            if crit.skipped {
+                writeln!(f, "{} {}  {}", symbol, crit.name, labels.skipped)?;
+            } else {
+                writeln!(
+                    f, "{} {} ({} / {} {})  {}",
+                    symbol, crit.name, crit.awarded, crit.possible, labels.points, crit.message
+                )?;
+            }
+        }
+        writeln!(
+            f, "{}: {}/{} ({}%){}",
+            labels.grade, self.grade, self.total,
+            percent_of(self.grade, self.total), bonus_note(self.bonus)
+        )?;
+
+        if !self.feedback.is_empty() {
+            writeln!(f, "\n{}:", labels.feedback)?;
+            for msg in &self.feedback {
+                writeln!(f, "    {}", msg)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 
 /// All of these functions just print a different piece of the rubric or submission.
 /// I want to add color and styles to the output, so it gets a little more complicated
@@ -62,7 +815,8 @@ pub fn long(mut rubric: &mut Rubric) {
 mod components {
     use paris::Logger;
     use chrono::Local;
-    use crate::{Rubric, HR_TIMESTAMP_FORMAT};
+    use crate::{Rubric, Submission, HR_TIMESTAMP_FORMAT};
+    use super::{bonus_note, labels};
 
     pub fn rubric_name(rubric: &Rubric) {
         Logger::new().info(format!("<bold>{}</>", rubric.name));
@@ -112,18 +866,20 @@ mod components {
     }
 
     pub fn long_criteria(rubric: &mut Rubric) {
+        let width = rubric.wrap_width;
         for crit in rubric.sorted() {
-            crit.print_long();
+            crit.print_long_wrapped(width);
             println!();
         }
     }
 
     pub fn grade(rubric: &Rubric) {
         let mut log = Logger::new();
+        let label = labels().grade;
         if rubric.points() as isize >= rubric.total_points() {
-            log.success(format!("<bold>Grade: <green>{}/{}</>", rubric.points(), rubric.total_points()));
+            log.success(format!("<bold>{}: <green>{}/{}</>{}", label, rubric.points(), rubric.total_points(), bonus_note(rubric.bonus_total())));
         } else {
-            log.info(format!("<bold>Grade: {}/{}</>", rubric.points(), rubric.total_points()));
+            log.info(format!("<bold>{}: {}/{}</>{}", label, rubric.points(), rubric.total_points(), bonus_note(rubric.bonus_total())));
         }
     }
 
@@ -142,6 +898,15 @@ mod components {
         }
     }
 
+    pub fn feedback(submission: &Submission) {
+        let mut log = Logger::new();
+        log.newline(1);
+        log.info(format!("<bold>{}:</>", labels().feedback));
+        for msg in &submission.feedback {
+            log.log(format!("    {}", msg));
+        }
+    }
+
     pub fn current_time() {
         let now = Local::now();
         Logger::new().info(
@@ -149,3 +914,184 @@ mod components {
         );
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data, TestData, rubric::Criterion};
+
+    #[test]
+    fn test_class_report_contains_grades() {
+        let mut rubric = Rubric::default();
+        rubric.name = String::from("Lab 1");
+        rubric.add(Criterion::new("Git installed").worth(10).build());
+
+        let mut first = Submission::from_data(data! { "id" => "1111" });
+        first.grade = 10;
+        first.passed.push(String::from("Git installed (+10)"));
+
+        let mut second = Submission::from_data(data! { "id" => "2222" });
+        second.grade = 0;
+        second.failed.push(String::from("Git installed (-0)"));
+
+        let html = render_class_report(&[first, second], &rubric);
+        assert!(html.contains("<title>Lab 1</title>"));
+        assert!(html.contains("<td>1111</td><td>10/10</td>"));
+        assert!(html.contains("<td>2222</td><td>0/10</td>"));
+        assert!(html.contains("Mean: 5.00"));
+        assert!(html.contains("Median: 5.00"));
+    }
+
+    #[test]
+    fn test_artifact_round_trip() {
+        let mut rubric = Rubric::default();
+        rubric.name = String::from("Lab 2");
+        rubric.add(Criterion::new("Passes").worth(10).messages("nice", "nope")
+            .test(Box::new(|_: &crate::TestData| true)).build());
+        rubric.add(Criterion::new("Fails").worth(5).messages("nice", "nope").build());
+
+        let mut sub = Submission::from_data(data! { "id" => "1234" });
+        sub.grade_against(&mut rubric);
+
+        let artifact = grade_artifact(&rubric, &sub);
+        let stored = serde_json::to_string(&artifact).unwrap();
+        let loaded: Value = serde_json::from_str(&stored).unwrap();
+        assert_eq!(loaded, artifact);
+
+        let loaded_sub: Submission = serde_json::from_value(loaded["submission"].clone()).unwrap();
+        assert_eq!(loaded_sub, sub);
+        assert_eq!(loaded["rubric"]["criteria"][1]["worth"], 10);
+        assert_eq!(loaded["results"][0]["result"], "failed");
+
+        let text = render_artifact(&loaded).unwrap();
+        assert!(text.starts_with("Lab 2\nGrade: 10/15"));
+        assert!(text.contains("✓ Passes (10 / 10 pts)  nice"));
+        assert!(text.contains("✗ Fails (0 / 5 pts)  nope"));
+        assert!(!text.contains("Feedback"));
+
+        sub.add_feedback("Good start");
+        let text = render_artifact(&grade_artifact(&rubric, &sub)).unwrap();
+        assert!(text.ends_with("\nFeedback:\n    Good start\n"));
+    }
+
+    #[test]
+    fn test_artifact_keeps_named_failure() {
+        use crate::rubric::CriterionOutcome;
+
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Has main").worth(10)
+            .messages("Found main", "main.rs is wrong")
+            .named_failure("missing", "Couldn't find main.rs")
+            .test_outcome(|data: &crate::TestData| match data.get("main_rs") {
+                None => CriterionOutcome::Fail(Some(String::from("missing"))),
+                Some(_) => CriterionOutcome::Pass,
+            })
+            .build());
+
+        let mut missing = Submission::new();
+        missing.grade_against(&mut rubric);
+        assert_eq!(missing.results[0].message, "Couldn't find main.rs");
+
+        // Grading another submission doesn't change what the first one showed
+        let mut found = Submission::from_data(data! { "main_rs" => "fn main() {}" });
+        found.grade_against(&mut rubric);
+        let artifact = grade_artifact(&rubric, &missing);
+        assert_eq!(artifact["results"][0]["message"], "Couldn't find main.rs");
+        let text = render_artifact(&artifact).unwrap();
+        assert!(text.contains("✗ Has main (0 / 10 pts)  Couldn't find main.rs"));
+        assert_eq!(GradeReport::new(&rubric, &missing).criteria[0].message, "Couldn't find main.rs");
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("  one two three", 9), vec!["  one two", "  three"]);
+        // A long word gets a line to itself
+        assert_eq!(wrap("a verylongword b", 5), vec!["a", "verylongword", "b"]);
+        // Widths are in characters, even when the indent isn't ASCII
+        let lead = "\u{3000}\u{3000}";
+        assert_eq!(
+            wrap(&format!("{}aaa bbb ccc", lead), 7),
+            vec![format!("{}aaa", lead), format!("{}bbb", lead), format!("{}ccc", lead)]
+        );
+    }
+
+    #[test]
+    fn test_feedback_text() {
+        let mut sub = Submission::new();
+        sub.add_feedback("Great structure, nice work");
+        sub.add_feedback("Watch your error handling");
+        assert_eq!(
+            feedback_text(&sub),
+            "Feedback:\n    Great structure, nice work\n    Watch your error handling\n"
+        );
+    }
+
+    #[test]
+    fn test_render_bad_artifact() {
+        assert!(render_artifact(&json!({})).is_err());
+    }
+
+    #[test]
+    fn test_worth_format_percent() {
+        let mut rubric = Rubric::default();
+        for (name, worth) in &[("First", 10), ("Second", 20), ("Third", 30)] {
+            let mut crit = Criterion::new(name).worth(*worth).build();
+            crit.status = Some(*name != "Third");
+            rubric.add(crit);
+        }
+        let text = rubric.display_with(WorthFormat::Percent);
+        assert!(text.contains("✓ First (16.7 / 16.7%)"));
+        assert!(text.contains("✗ Third (0 / 50%)"));
+        assert!(text.ends_with("Grade: 50%\n"));
+
+        // The criteria add up to the whole rubric, give or take rounding
+        let sum: f64 = rubric.criteria().iter()
+            .map(|c| c.percent_display(rubric.total_points()))
+            .map(|s| s.split(" / ").nth(1).unwrap().trim_end_matches('%').parse::<f64>().unwrap())
+            .sum();
+        assert!((sum - 100.0).abs() < 0.5, "sum was {}", sum);
+
+        // Display is in points
+        assert_eq!(rubric.display_with(WorthFormat::Points), rubric.to_string());
+        assert!(rubric.to_string().contains("✓ First (10 / 10 pts)"));
+    }
+
+    #[test]
+    fn test_grade_report() {
+        let mut rubric = Rubric::default();
+        rubric.name = String::from("Lab 1");
+        rubric.add(Criterion::new("Compiles").worth(30).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Tests pass").worth(10).test(Box::new(|_: &TestData| false)).build());
+
+        let mut sub = Submission::new();
+        let report = rubric.grade_and_report(&mut sub);
+
+        assert_eq!(report.grade, 30);
+        assert_eq!(report.total, 40);
+        assert_eq!(report.percentage, 75.0);
+        assert_eq!(report.criteria.len(), 2);
+        assert_eq!(report.criteria[0].status, Some(true));
+        assert_eq!(report.criteria[0].awarded, 30);
+        assert_eq!(report.criteria[1].awarded, 0);
+        assert_eq!(report.criteria[1].possible, 10.0);
+
+        let text = report.to_string();
+        assert!(text.starts_with("Lab 1\n"));
+        assert!(text.contains("Compiles (30 / 30 pts)"));
+        assert!(text.contains("Tests pass (0 / 10 pts)"));
+        assert!(text.contains("Grade: 30/40 (75%)"));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["criteria"][1]["name"], "Tests pass");
+        assert_eq!(json["percentage"], 75.0);
+    }
+
+    #[test]
+    fn test_class_report_stats() {
+        assert_eq!(html::mean(&[]), 0.0);
+        assert_eq!(html::median(&[1, 2, 9]), 2.0);
+        assert_eq!(html::median(&[1, 2, 4, 9]), 3.0);
+        assert_eq!(html::escape("<b>&"), "&lt;b&gt;&amp;");
+    }
+}
@@ -0,0 +1,374 @@
+//! A terminal UI for reviewing a graded submission by hand.
+//!
+//! This is only available with the `tui` feature.
+//!
+//! ```toml
+//! [dependencies]
+//! rubric = { version = "*", features = [ "tui" ] }
+//! ```
+// std uses
+use std::io::{stdout, Write};
+
+// external uses
+use crossterm::{
+    cursor, execute, queue, terminal,
+    event::{self, Event, KeyCode, KeyEventKind},
+    style::Print,
+};
+
+// internal uses
+use crate::{Rubric, Submission, Result};
+use crate::rubric::Criterion;
+use crate::report;
+
+
+// What a key press does to the review
+#[derive(Debug, PartialEq)]
+enum Action {
+    Continue,
+    Apply,
+    Cancel,
+}
+
+// The state of a review, kept apart from the terminal so it can be tested
+struct Review<'a> {
+    criteria: Vec<&'a Criterion>,
+    // Whether each criterion passed when it was graded
+    graded: Vec<bool>,
+    // What the reviewer changed it to, if anything
+    overrides: Vec<Option<bool>>,
+    cursor: usize,
+}
+
+impl<'a> Review<'a> {
+    fn new(submission: &Submission, rubric: &'a Rubric) -> Self {
+        let criteria = rubric.in_grading_order();
+
+        // The submission's results are what was graded, the rubric
+        // may have been graded again since
+        let graded: Vec<bool> = criteria.iter()
+            .map(|crit| {
+                submission.results.iter()
+                    .find(|r| r.func == crit.func)
+                    .map(|r| r.passed)
+                    .unwrap_or(crit.status == Some(true))
+            })
+            .collect();
+
+        Review {
+            overrides: vec![None; criteria.len()],
+            criteria,
+            graded,
+            cursor: 0,
+        }
+    }
+
+    // Whether the criterion passes, with the reviewer's override
+    fn passed(&self, i: usize) -> bool {
+        self.overrides[i].unwrap_or(self.graded[i])
+    }
+
+    fn set(&mut self, passed: bool) {
+        if self.criteria.is_empty() {
+            return;
+        }
+        let i = self.cursor;
+        self.overrides[i] = if passed == self.graded[i] { None } else { Some(passed) };
+    }
+
+    fn handle(&mut self, key: KeyCode) -> Action {
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.cursor = self.cursor.saturating_sub(1);
+            },
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.cursor + 1 < self.criteria.len() {
+                    self.cursor += 1;
+                }
+            },
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                if !self.criteria.is_empty() {
+                    let passed = self.passed(self.cursor);
+                    self.set(!passed);
+                }
+            },
+            KeyCode::Char('p') => self.set(true),
+            KeyCode::Char('f') => self.set(false),
+            KeyCode::Char('u') => self.set_original(),
+            KeyCode::Char('q') => return Action::Apply,
+            KeyCode::Esc => return Action::Cancel,
+            _ => {}
+        };
+        Action::Continue
+    }
+
+    fn set_original(&mut self) {
+        if !self.criteria.is_empty() {
+            self.overrides[self.cursor] = None;
+        }
+    }
+
+    // How many points the overrides add (or take away)
+    fn adjustment(&self) -> isize {
+        self.overrides.iter()
+            .zip(&self.criteria)
+            .map(|(o, crit)| match o {
+                Some(true) => crit.weighted_worth(),
+                Some(false) => -crit.weighted_worth(),
+                None => 0
+            })
+            .sum()
+    }
+
+    // The override reason, naming every criterion that was changed
+    fn reason(&self) -> Option<String> {
+        let changes: Vec<String> = self.overrides.iter()
+            .zip(&self.criteria)
+            .filter_map(|(o, crit)| {
+                o.map(|passed| format!("{} {}", crit.name, if passed { "passed" } else { "failed" }))
+            })
+            .collect();
+
+        if changes.is_empty() {
+            None
+        } else {
+            Some(format!("Manual review: {}", changes.join(", ")))
+        }
+    }
+
+    // Changes the submission's grade and results to match the overrides
+    fn apply(&self, submission: &mut Submission) {
+        let reason = match self.reason() {
+            Some(reason) => reason,
+            None => return
+        };
+
+        for (i, crit) in self.criteria.iter().enumerate() {
+            if let Some(passed) = self.overrides[i] {
+                let result = submission.results.iter_mut().find(|r| r.func == crit.func);
+                if let Some(result) = result {
+                    result.passed = passed;
+                    result.awarded = if passed { crit.weighted_worth() } else { 0 };
+                }
+            }
+        }
+
+        let grade = submission.grade + self.adjustment();
+        submission.override_grade(grade, &reason);
+    }
+
+    // The lines to draw, the list of criteria and then
+    // the details of the selected one
+    fn lines(&self, grade: isize) -> Vec<String> {
+        let symbols = report::symbols();
+        let mut lines = vec![
+            format!("Grade: {}", grade + self.adjustment()),
+            String::new(),
+        ];
+
+        for (i, crit) in self.criteria.iter().enumerate() {
+            let pointer = if i == self.cursor { ">" } else { " " };
+            let symbol = if self.passed(i) { &symbols.pass } else { &symbols.fail };
+            let overridden = if self.overrides[i].is_some() { " (overridden)" } else { "" };
+            lines.push(format!(
+                "{} {} {} ({} pts){}",
+                pointer, symbol, crit.name, crit.points_possible(), overridden
+            ));
+        }
+
+        if let Some(crit) = self.criteria.get(self.cursor) {
+            lines.push(String::new());
+            let message = if self.passed(self.cursor) {
+                crit.success_message()
+            } else {
+                crit.current_failure_message()
+            };
+            lines.push(message.clone());
+            if let Some(desc) = &crit.desc {
+                lines.push(desc.clone());
+            }
+            if let Some(explanation) = &crit.explanation {
+                lines.push(explanation.clone());
+            }
+        }
+
+        lines.push(String::new());
+        lines.push(String::from(
+            "up/down: move  space: toggle  p: pass  f: fail  u: undo  q: save and quit  esc: cancel"
+        ));
+        lines
+    }
+}
+
+
+/// Steps through a graded submission's criteria in the terminal, so a grader
+/// can check them and override any that were graded wrong.
+///
+/// Each criterion is shown with its status and worth. The selected one also
+/// shows its message, description, and explanation.
+///
+/// | Key              | Does                           |
+/// |------------------|--------------------------------|
+/// | up/down, `k`/`j` | Move between criteria          |
+/// | space, enter     | Toggle pass/fail               |
+/// | `p`, `f`         | Mark passed, failed            |
+/// | `u`              | Undo the override              |
+/// | `q`              | Save the overrides and quit    |
+/// | esc              | Quit without changing anything |
+///
+/// When overrides are saved, the grade is set with
+/// [`override_grade`](crate::dropbox::submission::Submission::override_grade),
+/// and the reason lists the criteria that were changed. The submission's
+/// [`results`](crate::dropbox::submission::Submission::results) are changed to match.
+///
+/// The submission should already be graded against the rubric.
+///
+/// ```no_run
+/// # use rubric::{Rubric, Submission, yaml};
+/// # let mut rubric = Rubric::from_yaml(yaml!("../test_data/test_rubric.yml").unwrap()).unwrap();
+/// let mut sub = Submission::new();
+/// sub.grade_against(&mut rubric);
+///
+/// rubric::review::review(&mut sub, &rubric).unwrap();
+/// ```
+pub fn review(submission: &mut Submission, rubric: &Rubric) -> Result<()> {
+    let mut review = Review::new(submission, rubric);
+    let mut out = stdout();
+
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let action = run(&mut review, submission.grade, &mut out);
+
+    // Always give the terminal back, even if drawing failed
+    execute!(out, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+
+    if action? == Action::Apply {
+        review.apply(submission);
+    }
+    Ok(())
+}
+
+fn run<W: Write>(review: &mut Review, grade: isize, out: &mut W) -> Result<Action> {
+    loop {
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        for (row, line) in review.lines(grade).iter().enumerate() {
+            queue!(out, cursor::MoveTo(0, row as u16), Print(line))?;
+        }
+        out.flush()?;
+
+        if let Event::Key(key) = event::read()? {
+            // Windows sends releases too
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match review.handle(key.code) {
+                Action::Continue => continue,
+                action => return Ok(action),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{yaml, TestData};
+
+    fn pass(_: &TestData) -> bool { true }
+    fn fail(_: &TestData) -> bool { false }
+
+    fn graded() -> (Rubric, Submission) {
+        let mut rubric = Rubric::from_yaml(yaml!("../test_data/test_rubric.yml").unwrap()).unwrap();
+        let funcs: Vec<String> = rubric.funcs().iter().map(|f| f.to_string()).collect();
+        // Only the first criterion passes
+        for (i, func) in funcs.iter().enumerate() {
+            let test = if i == 0 { pass } else { fail };
+            rubric.get_mut(func).unwrap().attach(Box::new(test));
+        }
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        (rubric, sub)
+    }
+
+    #[test]
+    fn test_navigation_stays_in_bounds() {
+        let (rubric, sub) = graded();
+        let mut review = Review::new(&sub, &rubric);
+
+        review.handle(KeyCode::Up);
+        assert_eq!(review.cursor, 0);
+        for _ in 0..rubric.len() + 3 {
+            review.handle(KeyCode::Char('j'));
+        }
+        assert_eq!(review.cursor, rubric.len() - 1);
+    }
+
+    #[test]
+    fn test_toggle_and_undo() {
+        let (rubric, sub) = graded();
+        let mut review = Review::new(&sub, &rubric);
+
+        assert!(review.passed(0));
+        review.handle(KeyCode::Char(' '));
+        assert_eq!(review.overrides[0], Some(false));
+        // Toggling back isn't an override anymore
+        review.handle(KeyCode::Char(' '));
+        assert_eq!(review.overrides[0], None);
+
+        review.handle(KeyCode::Char('f'));
+        review.handle(KeyCode::Char('u'));
+        assert_eq!(review.overrides[0], None);
+        assert!(review.reason().is_none());
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let (rubric, mut sub) = graded();
+        let before = sub.grade;
+        let mut review = Review::new(&sub, &rubric);
+
+        review.handle(KeyCode::Down);
+        review.handle(KeyCode::Char('p'));
+        assert_eq!(review.handle(KeyCode::Char('q')), Action::Apply);
+        review.apply(&mut sub);
+
+        let crit = review.criteria[1];
+        assert_eq!(sub.grade, before + crit.weighted_worth());
+        assert_eq!(
+            sub.override_reason,
+            Some(format!("Manual review: {} passed", crit.name))
+        );
+        let result = sub.results.iter().find(|r| r.func == crit.func).unwrap();
+        assert!(result.passed);
+        assert_eq!(result.awarded, crit.weighted_worth());
+    }
+
+    #[test]
+    fn test_apply_without_overrides() {
+        let (rubric, mut sub) = graded();
+        let before = sub.grade;
+        let review = Review::new(&sub, &rubric);
+        review.apply(&mut sub);
+
+        assert_eq!(sub.grade, before);
+        assert!(sub.override_reason.is_none());
+    }
+
+    #[test]
+    fn test_lines() {
+        let (rubric, sub) = graded();
+        let mut review = Review::new(&sub, &rubric);
+        review.handle(KeyCode::Char('f'));
+
+        let lines = review.lines(sub.grade);
+        let crit = review.criteria[0];
+        assert_eq!(lines[0], format!("Grade: {}", sub.grade - crit.weighted_worth()));
+        assert!(lines[2].starts_with(">"));
+        assert!(lines[2].contains(&crit.name));
+        assert!(lines[2].ends_with("(overridden)"));
+        assert!(lines.contains(crit.failure_message()));
+    }
+}
@@ -0,0 +1,72 @@
+//! Reusing criterion results between grading runs
+
+// std uses
+use std::collections::HashMap;
+
+
+/// Remembers if each criterion passed for some submission data.
+///
+/// Results are keyed on the criterion's `func` and the submission's
+/// [`data_hash`](crate::dropbox::submission::Submission::data_hash), so grading
+/// the same data again can skip running the test. It's meant to be kept for
+/// one session, like while you're writing a rubric. Nothing is ever invalidated,
+/// so if you change a criterion's test, make a new cache.
+///
+/// Use it with [`Rubric::grade_cached`](crate::rubric::Rubric::grade_cached).
+#[derive(Debug, Default, Clone)]
+pub struct ResultCache {
+    results: HashMap<(String, String), bool>,
+}
+
+impl ResultCache {
+    /// Creates an empty cache
+    pub fn new() -> Self {
+        ResultCache::default()
+    }
+
+    /// The number of cached results
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Returns true if nothing is cached
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// Forgets every cached result
+    pub fn clear(&mut self) {
+        self.results.clear();
+    }
+
+    pub(crate) fn get(&self, func: &str, data_hash: &str) -> Option<bool> {
+        self.results.get(&(func.to_owned(), data_hash.to_owned())).copied()
+    }
+
+    pub(crate) fn insert(&mut self, func: &str, data_hash: &str, passed: bool) {
+        self.results.insert((func.to_owned(), data_hash.to_owned()), passed);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let mut cache = ResultCache::new();
+        assert!(cache.is_empty());
+        assert!(cache.get("compiles", "abc").is_none());
+
+        cache.insert("compiles", "abc", true);
+        cache.insert("compiles", "def", false);
+        assert_eq!(cache.get("compiles", "abc"), Some(true));
+        assert_eq!(cache.get("compiles", "def"), Some(false));
+        assert!(cache.get("runs", "abc").is_none());
+        assert_eq!(cache.len(), 2);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
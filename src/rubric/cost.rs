@@ -0,0 +1,127 @@
+//! How expensive criteria are to test, for scheduling grading jobs
+
+// std uses
+use std::time::Duration;
+
+// external uses
+use serde::{Deserialize, Serialize};
+
+// internal uses
+use super::Rubric;
+
+
+/// A rough idea of how expensive a criterion's test is.
+///
+/// Criteria can declare one with [`CriterionBuilder::cost`](crate::rubric::CriterionBuilder::cost)
+/// or the `cost` key in yaml. It doesn't change how the criterion is tested,
+/// it's there for whatever schedules the grading. See
+/// [`Rubric::cost_profile`](crate::rubric::Rubric::cost_profile).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cost {
+    /// Only looks at the submission's data, or something else in memory
+    Cheap,
+    /// Makes network requests
+    Network,
+    /// Runs another process, or does a lot with the filesystem
+    Process,
+}
+
+impl Cost {
+    /// How long a test with this cost should be given before it's
+    /// considered stuck. Criteria don't have a timeout of their own,
+    /// so this is only a suggestion for whatever schedules the grading.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::Cost;
+    /// assert!(Cost::Cheap.default_timeout() < Cost::Network.default_timeout());
+    /// ```
+    pub fn default_timeout(&self) -> Duration {
+        match self {
+            Cost::Cheap => Duration::from_secs(5),
+            Cost::Network => Duration::from_secs(30),
+            Cost::Process => Duration::from_secs(60),
+        }
+    }
+}
+
+
+/// How many of a rubric's criteria are in each [`Cost`](crate::rubric::Cost) tier,
+/// from [`Rubric::cost_profile`](crate::rubric::Rubric::cost_profile).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CostProfile {
+    pub cheap: usize,
+    pub network: usize,
+    pub process: usize,
+}
+
+impl CostProfile {
+    /// How many criteria there are in all
+    pub fn total(&self) -> usize {
+        self.cheap + self.network + self.process
+    }
+
+    /// Each criterion's [`Cost::default_timeout`](crate::rubric::Cost::default_timeout)
+    /// added up, a rough idea of how long a scheduler should wait for one submission
+    /// to be graded.
+    pub fn default_timeout(&self) -> Duration {
+        Cost::Cheap.default_timeout() * self.cheap as u32
+            + Cost::Network.default_timeout() * self.network as u32
+            + Cost::Process.default_timeout() * self.process as u32
+    }
+}
+
+
+impl Rubric {
+    /// Counts the criteria in each [`Cost`](crate::rubric::Cost) tier.
+    ///
+    /// Use it to decide how many submissions to grade at once, or how long to
+    /// wait for them. A criterion that doesn't declare a cost is counted by
+    /// [`Criterion::cost`](crate::rubric::Criterion::cost()), so ones with a command are
+    /// counted as `Process`.
+    ///
+    /// ```rust
+    /// # use rubric::Rubric;
+    /// # use rubric::rubric::{Cost, Criterion};
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Has name").build());
+    /// rubric.add(Criterion::new("Site is up").cost(Cost::Network).build());
+    /// rubric.add(Criterion::new("Git installed").command("git", &["--version"]).build());
+    ///
+    /// let profile = rubric.cost_profile();
+    /// assert_eq!((profile.cheap, profile.network, profile.process), (1, 1, 1));
+    /// ```
+    pub fn cost_profile(&self) -> CostProfile {
+        let mut profile = CostProfile::default();
+        for crit in &self.criteria {
+            match crit.cost() {
+                Cost::Cheap => profile.cheap += 1,
+                Cost::Network => profile.network += 1,
+                Cost::Process => profile.process += 1,
+            }
+        }
+        profile
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_profile_from_yaml() {
+        let raw = "name: Costs\ncriteria:\n  Has name:\n    func: has_name\n    worth: 5\n  Has id:\n    func: has_id\n    worth: 5\n    cost: cheap\n  Site is up:\n    func: site_is_up\n    worth: 10\n    cost: network\n  Repo cloned:\n    func: repo_cloned\n    worth: 10\n    cost: network\n  Tests pass:\n    func: tests_pass\n    worth: 20\n    cost: process\n";
+        let rubric = Rubric::from_yaml(raw).unwrap();
+        assert_eq!(rubric.get("has_name").unwrap().cost, None);
+        assert_eq!(rubric.get("tests_pass").unwrap().cost(), Cost::Process);
+
+        let profile = rubric.cost_profile();
+        assert_eq!(profile, CostProfile { cheap: 2, network: 2, process: 1 });
+        assert_eq!(profile.total(), rubric.len());
+        assert_eq!(profile.default_timeout(), Duration::from_secs(2 * 5 + 2 * 30 + 60));
+
+        let bad = raw.replace("cost: process", "cost: expensive");
+        assert!(Rubric::from_yaml(&bad).is_err());
+    }
+}
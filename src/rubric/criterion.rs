@@ -10,12 +10,74 @@
 //! but you can if you want. Instead, you should define your criteria in `YAML` then
 //! build that into a [`Rubric`](crate::rubric::Rubric).
 
+// std uses
+use std::fmt;
+use std::collections::HashMap;
+#[cfg(feature = "async")]
+use std::{future::Future, pin::Pin};
+
 // external uses
 use paris::{Logger, formatter::Formatter};
 
 // internal uses
 use crate::TestData;
-use crate::rubric::CriterionBuilder;
+use crate::rubric::{Cost, CriterionBuilder};
+use crate::helpers::process::{self, CommandResult};
+use crate::report;
+
+
+/// A plain function that can be attached as a test, see
+/// [`Rubric::attach_funcs`](crate::rubric::Rubric::attach_funcs)
+pub type TestFn = fn(&TestData) -> bool;
+
+/// Decides if a criterion applies to a submission, based on its data
+pub type Condition = Box<dyn Fn(&TestData) -> bool>;
+
+/// Decides if a command criterion passed, based on what the command did
+pub type CommandPredicate = Box<dyn Fn(&CommandResult) -> bool>;
+
+/// Explains a criterion's result, given the data it was tested with and
+/// whether it passed
+pub type Explanation = Box<dyn Fn(&TestData, bool) -> String>;
+
+/// What a test that can say why it failed returns. See
+/// [`CriterionBuilder::test_outcome`](crate::rubric::CriterionBuilder::test_outcome).
+#[derive(Debug, Clone, PartialEq)]
+pub enum CriterionOutcome {
+    Pass,
+    /// Failed, maybe with the name of the failure message to show
+    Fail(Option<String>),
+}
+
+/// A test that returns a [`CriterionOutcome`](crate::rubric::criterion::CriterionOutcome)
+/// instead of a `bool`
+pub type OutcomeTest = Box<dyn Fn(&TestData) -> CriterionOutcome>;
+
+/// A test that has to be awaited. See
+/// [`CriterionBuilder::test_async`](crate::rubric::CriterionBuilder::test_async).
+#[cfg(feature = "async")]
+pub type AsyncTest = Box<dyn Fn(&TestData) -> Pin<Box<dyn Future<Output = bool>>>>;
+
+/// A command for a criterion to run, and a predicate that judges the result.
+///
+/// You should make one of these with [`CriterionBuilder::command`](crate::rubric::CriterionBuilder::command)
+/// and [`CriterionBuilder::expect`](crate::rubric::CriterionBuilder::expect).
+pub struct CommandTest {
+    /// The program to run
+    pub cmd: String,
+    /// Arguments passed to the program
+    pub args: Vec<String>,
+    /// Decides if the criterion passes, based on what the command did
+    pub expect: CommandPredicate,
+}
+
+impl CommandTest {
+    /// Runs the command. If it couldn't be started, this returns `None`.
+    fn run(&self) -> Option<CommandResult> {
+        let args: Vec<&str> = self.args.iter().map(|a| a.as_str()).collect();
+        process::run(&self.cmd, &args).ok()
+    }
+}
 
 
 /// A single Criterion
@@ -32,6 +94,13 @@ pub struct Criterion {
     /// Can be negative if you wish to subtract points. Be sure to get your logic right.
     /// This value is added to the submission grade *if the test returns true*.
     pub worth: isize,
+    /// The worth, if it has a fraction (like `2.5`).
+    ///
+    /// When this is set, it's used for grading instead of `worth`, which is this
+    /// rounded to a whole point. Grades are still whole points. The fractions of every
+    /// criterion that passed are added up and rounded once, at the end of grading, so
+    /// two half points make a whole one. See [`exact_worth`](crate::rubric::Criterion::exact_worth).
+    pub worth_f64: Option<f64>,
     /// An index to sort by when running.
     ///
     /// Lowest first. Defaults to 100.
@@ -42,7 +111,14 @@ pub struct Criterion {
     /// will be printed. Not much use other than that.
     pub messages: (String, String),
     /// An optional description
+    ///
+    /// It can have more than one line, and `{key}` is replaced with that key
+    /// from the submission's data, see [`desc_with`](crate::rubric::Criterion::desc_with).
     pub desc: Option<String>,
+    /// The description filled in with the data it was last tested with.
+    ///
+    /// This is `None` if there's no description or it hasn't been tested.
+    pub filled_desc: Option<String>,
     /// The criterion's test
     ///
     /// Determines if the criterion passes or fails. This signature is
@@ -55,6 +131,112 @@ pub struct Criterion {
     pub status: Option<bool>,
     /// Renders the criterion unable to be printed
     pub hide: bool,
+    /// A command to run as this criterion's test.
+    ///
+    /// If this is `Some`, the command is run and judged instead of calling `test`.
+    pub command: Option<CommandTest>,
+    /// What the command did, once the criterion has been tested.
+    ///
+    /// This is `None` if there is no `command`, it hasn't been run, or
+    /// the command couldn't be started.
+    pub command_result: Option<CommandResult>,
+    /// Decides if this criterion applies to a submission at all.
+    ///
+    /// If this returns false for a submission's data, the criterion
+    /// is skipped when grading. `None` means it always applies.
+    pub condition: Option<Condition>,
+    /// True if the criterion was skipped during grading because
+    /// its `condition` returned false, or data it needs was missing
+    pub skipped: bool,
+    /// Why the criterion was skipped, if it was given a reason.
+    /// See [`skip_reason`](crate::rubric::Criterion::skip_reason).
+    pub skip_reason: Option<String>,
+    /// Keys that have to be in the submission data for this criterion to be tested.
+    ///
+    /// If any are missing when grading, the criterion is skipped instead of tested.
+    pub required_data: Vec<String>,
+    /// If this criterion fails, the submission fails the whole rubric,
+    /// regardless of its grade.
+    pub required: bool,
+    /// Bonus criteria add points when they pass, but aren't part of the
+    /// total, so failing one doesn't cost anything.
+    ///
+    /// See [`Rubric::bonus_total`](crate::rubric::Rubric::bonus_total).
+    pub bonus: bool,
+    /// Penalty criteria take their worth away when the test returns true,
+    /// ie. the test checks for something that *shouldn't* be there.
+    ///
+    /// The worth is written as a positive number. See
+    /// [`CriterionBuilder::penalty`](crate::rubric::CriterionBuilder::penalty).
+    pub penalty: bool,
+    /// How many more times to run the test if it fails, for tests
+    /// that sometimes fail for no good reason, like ones using the network.
+    /// Penalty criteria aren't retried.
+    ///
+    /// See [`CriterionBuilder::retries`](crate::rubric::CriterionBuilder::retries).
+    pub retries: u32,
+    /// How many times the test was run the last time the criterion was tested,
+    /// counting retries. This is 0 if it hasn't been tested.
+    pub attempts: u32,
+    /// How many times the test has been run in total, counting retries.
+    ///
+    /// Unlike `attempts`, this keeps counting when the criterion is reset.
+    pub run_count: u32,
+    /// Scales how much this criterion adds to the grade, without
+    /// changing the `worth` that's displayed.
+    ///
+    /// See [`weighted_worth`](crate::rubric::Criterion::weighted_worth).
+    pub weight: Option<f64>,
+    /// Explains why the criterion passed or failed.
+    ///
+    /// This runs after the test, and the result is stored in `explanation`.
+    pub explain: Option<Explanation>,
+    /// The explanation of the last result, once the criterion has been tested.
+    ///
+    /// This is `None` if there's no `explain` closure or it hasn't been tested.
+    pub explanation: Option<String>,
+    /// Labels for grouping criteria, like `"style"` or `"week 1"`.
+    ///
+    /// Tags are kept with a submission's results when it's graded, see
+    /// [`Submission::points_by_tag`](crate::dropbox::submission::Submission::points_by_tag).
+    pub tags: Vec<String>,
+    /// The section of the rubric this criterion is in, like `"Setup"`.
+    ///
+    /// Unlike tags, a criterion is only in one group. See
+    /// [`Rubric::groups`](crate::rubric::Rubric::groups).
+    pub group: Option<String>,
+    /// How expensive the test is, if the criterion says. See
+    /// [`cost()`](crate::rubric::Criterion::cost()) for what's used when it doesn't.
+    pub cost: Option<Cost>,
+    /// A test to use instead of `test` that can pick which failure message to show.
+    ///
+    /// See [`CriterionBuilder::test_outcome`](crate::rubric::CriterionBuilder::test_outcome).
+    pub outcome_test: Option<OutcomeTest>,
+    /// Failure messages for specific reasons, by name. An `outcome_test`
+    /// picks one by returning its name.
+    pub named_failures: HashMap<String, String>,
+    /// The name of the failure the `outcome_test` gave the last time it failed, if any.
+    pub failed_because: Option<String>,
+    /// A test to await instead of `test`, if the criterion has one.
+    ///
+    /// These only run when grading with
+    /// [`Submission::grade_against_async`](crate::dropbox::submission::Submission::grade_against_async).
+    #[cfg(feature = "async")]
+    pub async_test: Option<AsyncTest>,
+    // The result of the async test, waiting to be used when the criterion is tested
+    #[cfg(feature = "async")]
+    pub(crate) awaited: Option<bool>,
+    // Whether a test was given, the default one always fails
+    pub(crate) attached: bool,
+}
+
+// Keeps points between 0 and the worth, whichever way the worth goes
+fn clamp_to(points: f64, worth: f64) -> f64 {
+    if worth < 0.0 {
+        points.max(worth).min(0.0)
+    } else {
+        points.min(worth).max(0.0)
+    }
 }
 
 impl Criterion {
@@ -84,9 +266,121 @@ impl Criterion {
         &self.messages.1
     }
 
+    /// Sets the success and failure messages, like the builder's
+    /// [`messages`](crate::rubric::CriterionBuilder::messages) does.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::Criterion;
+    /// let mut crit = Criterion::new("my crit").build();
+    /// crit.set_messages("bien", "mal");
+    /// assert_eq!(crit.success_message(), "bien");
+    /// ```
+    pub fn set_messages<S: AsRef<str>>(&mut self, success: S, failure: S) {
+        self.messages = (
+            String::from(success.as_ref()),
+            String::from(failure.as_ref())
+        );
+    }
+
     /// Sets the test method of a criterion
     pub fn attach(&mut self, test: Box<dyn Fn(&TestData) -> bool>) {
-        self.test = test
+        self.test = test;
+        self.attached = true;
+    }
+
+    /// Sets a test that can pick which failure message to show, like
+    /// [`CriterionBuilder::test_outcome`](crate::rubric::CriterionBuilder::test_outcome).
+    /// It's used instead of the criterion's `test`.
+    pub fn attach_outcome(&mut self, test: OutcomeTest) {
+        self.outcome_test = Some(test);
+    }
+
+    /// The failure message to show, ie. the named failure picked by the
+    /// `outcome_test`, or the normal failure message if it didn't pick one
+    /// (or picked one that doesn't exist).
+    pub fn current_failure_message(&self) -> &String {
+        self.failed_because.as_ref()
+            .and_then(|reason| self.named_failures.get(reason))
+            .unwrap_or_else(|| self.failure_message())
+    }
+
+    /// Returns true if the criterion has something to test with, ie. a test
+    /// was attached, or it has a command or an async test.
+    pub fn has_test(&self) -> bool {
+        #[cfg(feature = "async")]
+        {
+            if self.async_test.is_some() {
+                return true;
+            }
+        }
+        self.attached || self.command.is_some() || self.outcome_test.is_some()
+    }
+
+    /// Returns true if this criterion applies to the given data, ie. it has
+    /// no `condition` or the condition returns true.
+    pub fn applies_to(&self, data: &TestData) -> bool {
+        match &self.condition {
+            Some(condition) => condition(data),
+            None => true
+        }
+    }
+
+    /// Marks the criterion as skipped. It won't have a status, and
+    /// won't give or take any points.
+    pub fn skip(&mut self) {
+        self.status = None;
+        self.skipped = true;
+        self.skip_reason = None;
+    }
+
+    /// Same as [`skip`](crate::rubric::Criterion::skip), but with a reason
+    /// that's shown in reports.
+    pub fn skip_because(&mut self, reason: &str) {
+        self.skip();
+        self.skip_reason = Some(String::from(reason));
+    }
+
+    /// Why the criterion was skipped. This is the
+    /// [`not_applicable`](crate::report::Labels::not_applicable) label,
+    /// `"not applicable"` by default, if it wasn't given a reason.
+    pub fn skip_reason(&self) -> String {
+        self.skip_reason.clone().unwrap_or_else(|| report::labels().not_applicable)
+    }
+
+    /// Returns the keys in `required_data` that aren't in the data.
+    ///
+    /// ```rust
+    /// # use rubric::data;
+    /// # use rubric::rubric::Criterion;
+    /// let crit = Criterion::new("Port is open")
+    ///     .require_data("host")
+    ///     .require_data("port")
+    ///     .build();
+    ///
+    /// assert_eq!(crit.missing_data(&data! { "host" => "localhost" }), vec!["port"]);
+    /// ```
+    pub fn missing_data(&self, data: &TestData) -> Vec<&str> {
+        self.required_data.iter()
+            .filter(|key| !data.contains_key(*key))
+            .map(|key| key.as_str())
+            .collect()
+    }
+
+    /// Clears everything set by testing, so the criterion is like it
+    /// was never tested.
+    pub fn reset(&mut self) {
+        self.status = None;
+        self.skipped = false;
+        self.skip_reason = None;
+        self.command_result = None;
+        self.explanation = None;
+        self.filled_desc = None;
+        self.failed_because = None;
+        self.attempts = 0;
+        #[cfg(feature = "async")]
+        {
+            self.awaited = None;
+        }
     }
 
     /// Runs the criterion's test function with the data provided.
@@ -102,8 +396,161 @@ impl Criterion {
     /// You shouldn't call this method directly, instead grade an entire
     /// [`Rubric`](crate::rubric::Rubric).
     pub fn test_with_data(&mut self, data: &TestData) -> bool {
-        self.status = Some((self.test)(data));
-        self.status.unwrap()
+        self.skipped = false;
+        self.skip_reason = None;
+        self.attempts = 0;
+        // A penalty passing is the bad outcome, so it's not worth retrying for.
+        // That's any criterion taking points away, not just ones marked as a penalty.
+        let tries = if self.points_possible() < 0 { 1 } else { self.retries.saturating_add(1) };
+        let mut passed = false;
+        while !passed && self.attempts < tries {
+            self.attempts += 1;
+            self.run_count += 1;
+            passed = self.attempt(data);
+
+            // An async test's result was awaited once, there's nothing to retry
+            #[cfg(feature = "async")]
+            {
+                if self.async_test.is_some() {
+                    break;
+                }
+            }
+        }
+        self.status = Some(passed);
+        self.explanation = self.explain.as_ref().map(|explain| explain(data, passed));
+        self.filled_desc = self.desc_with(data);
+        passed
+    }
+
+    /// The group this criterion is in, if it's in one
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// How expensive the test is. This is the declared `cost` if there is one. Otherwise
+    /// it's `Process` for criteria with a command, and `Cheap` for everything else.
+    pub fn cost(&self) -> Cost {
+        match (self.cost, &self.command) {
+            (Some(cost), _) => cost,
+            (None, Some(_)) => Cost::Process,
+            (None, None) => Cost::Cheap,
+        }
+    }
+
+    /// The description, with each `{key}` replaced by that key's value in `data`.
+    ///
+    /// Keys that aren't in the data are left as they are. It's done in one pass over
+    /// the description, so a value that looks like a `{key}` isn't replaced again.
+    ///
+    /// ```rust
+    /// # use rubric::data;
+    /// # use rubric::rubric::Criterion;
+    /// let crit = Criterion::new("Cloned")
+    ///     .desc("Clone {repo} into {dir}")
+    ///     .build();
+    /// let data = data! { "repo" => "github.com/llamicron/rubric" };
+    ///
+    /// assert_eq!(crit.desc_with(&data).unwrap(), "Clone github.com/llamicron/rubric into {dir}");
+    /// ```
+    pub fn desc_with(&self, data: &TestData) -> Option<String> {
+        let mut rest = self.desc.as_deref()?;
+        let mut desc = String::new();
+        while let Some(open) = rest.find('{') {
+            desc.push_str(&rest[..open]);
+            let after = &rest[open + 1..];
+            // The key ends at the next `}`, unless another `{` starts first
+            match after.find(['{', '}']) {
+                Some(close) if after[close..].starts_with('}') => {
+                    let key = &after[..close];
+                    match data.get(key) {
+                        Some(value) => desc.push_str(value),
+                        None => desc.push_str(&rest[open..open + close + 2]),
+                    }
+                    rest = &after[close + 1..];
+                },
+                _ => {
+                    desc.push('{');
+                    rest = after;
+                }
+            }
+        }
+        desc.push_str(rest);
+        Some(desc)
+    }
+
+    /// The description for printing under the criterion's name, with every line
+    /// indented by `indent` spaces. Long lines are wrapped to `width` characters,
+    /// counting the indent, unless it's `None`.
+    ///
+    /// Once the criterion is tested, this is the [`filled_desc`](crate::rubric::Criterion::filled_desc).
+    /// The long report wraps to the rubric's [`wrap_width`](crate::rubric::Rubric::wrap_width).
+    ///
+    /// ```rust
+    /// # use rubric::rubric::Criterion;
+    /// let crit = Criterion::new("Setup")
+    ///     .desc("Install git\nThen clone the repo and make sure it builds")
+    ///     .build();
+    /// assert_eq!(crit.desc_display(2, None).unwrap(), "  Install git\n  Then clone the repo and make sure it builds");
+    /// assert_eq!(
+    ///     crit.desc_display(2, Some(26)).unwrap(),
+    ///     "  Install git\n  Then clone the repo and\n  make sure it builds"
+    /// );
+    /// ```
+    pub fn desc_display(&self, indent: usize, width: Option<usize>) -> Option<String> {
+        let desc = self.filled_desc.as_ref().or(self.desc.as_ref())?;
+        let pad = " ".repeat(indent);
+        let lines: Vec<String> = desc.lines()
+            .flat_map(|line| match width {
+                Some(width) => report::wrap(line, width.saturating_sub(indent)),
+                None => vec![line.to_string()]
+            })
+            .map(|line| format!("{}{}", pad, line).trim_end().to_string())
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    // Runs the command or the test once
+    fn attempt(&mut self, data: &TestData) -> bool {
+        self.failed_because = None;
+        if let Some(test) = &self.outcome_test {
+            return match test(data) {
+                CriterionOutcome::Pass => true,
+                CriterionOutcome::Fail(reason) => {
+                    self.failed_because = reason;
+                    false
+                }
+            };
+        }
+        match &self.command {
+            Some(command) => {
+                // A command that couldn't be started always fails
+                let result = command.run();
+                let passed = matches!(&result, Some(r) if (command.expect)(r));
+                self.command_result = result;
+                passed
+            },
+            None => self.run_test(data)
+        }
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn run_test(&mut self, data: &TestData) -> bool {
+        (self.test)(data)
+    }
+
+    // An async test has to have been awaited already, it can't be done here
+    #[cfg(feature = "async")]
+    fn run_test(&mut self, data: &TestData) -> bool {
+        if self.async_test.is_none() {
+            return (self.test)(data);
+        }
+        self.awaited.take().unwrap_or_else(|| {
+            eprintln!(
+                "Warning: criterion '{}' has an async test, grade with `grade_against_async` to run it",
+                self.name
+            );
+            false
+        })
     }
 
     /// Runs the criterions test and assigns the result to `criterion.status`.
@@ -125,8 +572,10 @@ impl Criterion {
         }
 
         let mut log = Logger::new();
-        
-        if let Some(s) = self.status {
+
+        if self.skipped {
+            log.same().info(&self.name).log(format!("\t<bold>{} ({})</>", report::labels().skipped, self.skip_reason()));
+        } else if let Some(s) = self.status {
             // Already tested, diff color based on status
             if s {
                 log.same().success(&self.name).log(
@@ -139,11 +588,18 @@ impl Criterion {
             }
         } else {
             // Not tested
-            log.same().warn(&self.name).log("<bold>Not Tested</>");
+            log.same().warn(&self.name).log(format!("<bold>{}</>", report::labels().not_tested));
         }
     }
 
     pub fn print_long(&self) {
+        self.print_long_wrapped(None);
+    }
+
+    /// Same as [`print_long`](crate::rubric::Criterion::print_long), but the
+    /// description is wrapped to `width` characters. See
+    /// [`desc_display`](crate::rubric::Criterion::desc_display).
+    pub fn print_long_wrapped(&self, width: Option<usize>) {
         // Never print if it's hidden
         if self.hide {
             return;
@@ -151,7 +607,9 @@ impl Criterion {
 
         let mut log = Logger::new();
         // Name and status
-        if let Some(s) = self.status {
+        if self.skipped {
+            log.info(format!("{}  <bold>{} ({})</>", self.name, report::labels().skipped, self.skip_reason()));
+        } else if let Some(s) = self.status {
             if s {
                 log.same().success(&self.name);
             } else {
@@ -161,26 +619,221 @@ impl Criterion {
             log.same().log("  ").log(self.colored_status_message());
         } else {
             // Hasn't been tested
-            log.warn(format!("{}  <bold>Not Tested</>", self.name));
+            log.warn(format!("{}  <bold>{}</>", self.name, report::labels().not_tested));
+        }
+
+        // Description, under the name
+        if let Some(desc) = self.desc_display(2, width) {
+            log.log(desc);
         }
 
-        // Description
-        if let Some(desc) = &self.desc {
-            log.info(desc);
+        // Why it passed or failed
+        if let Some(explanation) = &self.explanation {
+            log.info(explanation);
         }
-        
+
         // Worth
-        log.info(format!("Worth: <bold>{}</>", self.worth));
+        log.info(format!("Points: <bold>{}</>", self.points_display()));
+    }
+
+    /// The points this criterion actually adds to a grade when it passes.
+    ///
+    /// This is the `worth` multiplied by the `weight`, rounded to the nearest
+    /// whole point. Without a weight, it's just the `worth`. A negative worth
+    /// stays negative, so a weighted penalty is scaled the same way. A
+    /// [`penalty`](crate::rubric::Criterion::penalty) criterion's worth is made
+    /// negative first.
+    ///
+    /// A weight that's negative or not finite (`NaN`, infinity) is invalid and
+    /// gives 0 points. This keeps a bad weight from flipping a criterion into a
+    /// penalty, or a penalty into a bonus. See
+    /// [`has_valid_weight`](crate::rubric::Criterion::has_valid_weight).
+    ///
+    /// A criterion never gives more than its worth, so a weight above 1 is
+    /// clamped to it (and a penalty never takes more than its worth). Grading
+    /// warns when that happens, see [`is_clamped`](crate::rubric::Criterion::is_clamped).
+    ///
+    /// The rubric's [`points`](crate::rubric::Rubric::points) and
+    /// [`total_points`](crate::rubric::Rubric::total_points) both use this, so
+    /// grade percentages stay consistent. Everything that's shown to the student
+    /// (like [`points_display`](crate::rubric::Criterion::points_display)) uses the
+    /// nominal `worth`.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::Criterion;
+    /// let crit = Criterion::new("weighted").worth(10).weight(0.5).build();
+    /// assert_eq!(crit.worth, 10);
+    /// assert_eq!(crit.weighted_worth(), 5);
+    ///
+    /// // Clamped to the worth
+    /// let crit = Criterion::new("overweight").worth(10).weight(1.5).build();
+    /// assert_eq!(crit.weighted_worth(), 10);
+    /// ```
+    pub fn weighted_worth(&self) -> isize {
+        self.exact_worth().round() as isize
+    }
+
+    /// Same as [`weighted_worth`](crate::rubric::Criterion::weighted_worth), but
+    /// it isn't rounded, and it uses [`worth_f64`](crate::rubric::Criterion::worth_f64) if it's set.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::Criterion;
+    /// let crit = Criterion::new("weighted").worth_f64(2.5).weight(0.5).build();
+    /// assert_eq!(crit.exact_worth(), 1.25);
+    /// assert_eq!(crit.weighted_worth(), 1);
+    /// ```
+    pub fn exact_worth(&self) -> f64 {
+        let worth = self.nominal_worth();
+        match self.weight {
+            Some(_) if !self.has_valid_weight() => 0.0,
+            Some(weight) => clamp_to(worth * weight, worth),
+            None => worth
+        }
+    }
+
+    /// Returns true if the criterion's weight would make it give more than its
+    /// worth (or a penalty take more), so it's clamped to its worth.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::Criterion;
+    /// assert!(Criterion::new("overweight").worth(10).weight(1.5).build().is_clamped());
+    /// assert!(!Criterion::new("weighted").worth(10).weight(0.5).build().is_clamped());
+    /// ```
+    pub fn is_clamped(&self) -> bool {
+        match self.weight {
+            Some(weight) if self.has_valid_weight() => {
+                let worth = self.nominal_worth();
+                clamp_to(worth * weight, worth) != worth * weight
+            },
+            _ => false
+        }
+    }
+
+    // The worth before it's weighted, negative for a penalty
+    fn nominal_worth(&self) -> f64 {
+        match self.worth_f64 {
+            Some(worth) if self.penalty => -worth.abs(),
+            Some(worth) => worth,
+            None => self.points_possible() as f64
+        }
+    }
+
+    // The worth for JSON. It's a whole number unless the worth has a fraction.
+    pub(crate) fn worth_json(&self) -> serde_json::Value {
+        match self.worth_f64 {
+            Some(worth) => serde_json::json!(worth),
+            None => serde_json::json!(self.worth)
+        }
+    }
+
+    /// Returns false if the criterion has a weight that's negative or not finite.
+    /// A criterion without a weight is always valid.
+    pub fn has_valid_weight(&self) -> bool {
+        match self.weight {
+            Some(w) => w.is_finite() && w >= 0.0,
+            None => true
+        }
+    }
+
+    /// The points this criterion gave after grading.
+    ///
+    /// This is the worth if it passed and 0 if it failed. `None` if
+    /// it hasn't been tested.
+    pub fn points_awarded(&self) -> Option<isize> {
+        self.status.map(|passed| if passed { self.points_possible() } else { 0 })
+    }
+
+    /// The points this criterion can give, ie. its worth.
+    ///
+    /// This is negative for a [`penalty`](crate::rubric::Criterion::penalty),
+    /// whether its worth was written as positive or negative.
+    pub fn points_possible(&self) -> isize {
+        if self.penalty {
+            -self.worth.abs()
+        } else {
+            self.worth
+        }
+    }
+
+    /// The awarded points out of the possible points, like `"10 / 10 pts"`.
+    ///
+    /// If the criterion hasn't been tested, the awarded points
+    /// are shown as a dash, like `"— / 10 pts"`.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::Criterion;
+    /// let mut crit = Criterion::new("my crit").worth(10).build();
+    /// assert_eq!(crit.points_display(), "— / 10 pts");
+    ///
+    /// crit.status = Some(false);
+    /// assert_eq!(crit.points_display(), "0 / 10 pts");
+    /// ```
+    pub fn points_display(&self) -> String {
+        let pts = report::labels().points;
+        // A fractional worth is shown as it is, like "2.5"
+        let possible = self.shown_worth().to_string();
+        match self.status {
+            Some(true) => format!("{} / {} {}", possible, possible, pts),
+            Some(false) => format!("0 / {} {}", possible, pts),
+            None => format!("— / {} {}", possible, pts),
+        }
+    }
+
+    /// Like [`points_display`](crate::rubric::Criterion::points_display), but the points
+    /// are shown as a percentage of `total`, like `"10 / 25%"` for a criterion worth 25
+    /// points out of 100 that earned 10.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::Criterion;
+    /// let mut crit = Criterion::new("my crit").worth(10).build();
+    /// assert_eq!(crit.percent_display(40), "— / 25%");
+    ///
+    /// crit.status = Some(true);
+    /// assert_eq!(crit.percent_display(40), "25 / 25%");
+    ///
+    /// // Fractions aren't rounded off first
+    /// let half = Criterion::new("half").worth_f64(2.5).build();
+    /// assert_eq!(half.percent_display(20), "— / 12.5%");
+    /// ```
+    pub fn percent_display(&self, total: isize) -> String {
+        let possible = report::percent_of_f64(self.shown_worth(), total);
+        match self.status {
+            Some(true) => format!("{} / {}%", possible, possible),
+            Some(false) => format!("{} / {}%", report::percent_of(0, total), possible),
+            None => format!("— / {}%", possible),
+        }
+    }
+
+    // The worth to show in reports, with its fraction if it has one
+    pub(crate) fn shown_worth(&self) -> f64 {
+        self.nominal_worth()
+    }
+
+    // The symbol for the criterion's status, if it's been tested
+    pub(crate) fn status_symbol(&self) -> Option<String> {
+        let symbols = report::symbols();
+        if self.skipped {
+            Some(symbols.skip)
+        } else {
+            match self.status {
+                Some(true) => Some(symbols.pass),
+                Some(false) => Some(symbols.fail),
+                None => None
+            }
+        }
     }
 
 
     /// Returns the success message if the criterion passed, otherwise
     /// returns the failure message
+    ///
+    /// If the criterion's `outcome_test` picked a named failure, that message
+    /// is used instead. See [`current_failure_message`](crate::rubric::Criterion::current_failure_message).
     pub fn status_message(&self) -> String {
         if self.status == Some(true) {
             self.success_message().clone()
         } else {
-            self.failure_message().clone()
+            self.current_failure_message().clone()
         }
     }
 
@@ -192,13 +845,30 @@ impl Criterion {
         if self.status == Some(true) {
             fmt.colorize(&format!("<green>{}</>", self.success_message()))
         } else {
-            fmt.colorize(&format!("<red>{}</>", self.failure_message()))
+            fmt.colorize(&format!("<red>{}</>", self.current_failure_message()))
         }
     }
 
 
 }
 
+impl fmt::Display for Criterion {
+    /// Shows the name and the points, like `"Git installed (10 / 10 pts)"`.
+    ///
+    /// The alternate form (`{:#}`) puts a symbol for the status in front,
+    /// like `"✓ Git installed (10 / 10 pts)"`. The symbols can be changed with
+    /// [`report::set_symbols`](crate::report::set_symbols). Untested criteria
+    /// don't get a symbol.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            if let Some(symbol) = self.status_symbol() {
+                write!(f, "{} ", symbol)?;
+            }
+        }
+        write!(f, "{} ({})", self.name, self.points_display())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -231,6 +901,75 @@ mod tests {
         assert_eq!(c.failure_message(), "failure");
     }
 
+    #[test]
+    fn test_named_failures() {
+        let mut crit = Criterion::new("Has main")
+            .messages("Found main", "main.rs is wrong")
+            .named_failure("missing", "Couldn't find main.rs")
+            .named_failure("empty", "main.rs is empty")
+            .test_outcome(|data: &TestData| match data.get("main_rs") {
+                None => CriterionOutcome::Fail(Some(String::from("missing"))),
+                Some(main) if main.is_empty() => CriterionOutcome::Fail(Some(String::from("empty"))),
+                Some(main) if main.contains("fn main") => CriterionOutcome::Pass,
+                Some(_) => CriterionOutcome::Fail(None),
+            })
+            .build();
+        assert!(crit.has_test());
+
+        assert!(!crit.test_with_data(&TestData::new()));
+        assert_eq!(crit.failed_because.as_deref(), Some("missing"));
+        assert_eq!(crit.status_message(), "Couldn't find main.rs");
+
+        assert!(!crit.test_with_data(&data! { "main_rs" => "" }));
+        assert_eq!(crit.status_message(), "main.rs is empty");
+
+        // No name gets the normal message
+        assert!(!crit.test_with_data(&data! { "main_rs" => "fn other() {}" }));
+        assert!(crit.failed_because.is_none());
+        assert_eq!(crit.status_message(), "main.rs is wrong");
+
+        assert!(crit.test_with_data(&data! { "main_rs" => "fn main() {}" }));
+        assert_eq!(crit.status_message(), "Found main");
+    }
+
+    #[test]
+    fn test_named_failures_from_yaml() {
+        let yaml = r#"
+name: Test
+criteria:
+  Has main:
+    worth: 10
+    named_failures:
+      missing: "Couldn't find main.rs"
+"#;
+        let mut rubric = crate::Rubric::from_yaml(yaml).unwrap();
+        let crit = rubric.get_mut("has_main").unwrap();
+        // Unknown names fall back to the normal message
+        crit.attach_outcome(Box::new(|data: &TestData| {
+            CriterionOutcome::Fail(Some(String::from(if data.is_empty() { "missing" } else { "other" })))
+        }));
+
+        crit.test_with_data(&TestData::new());
+        assert_eq!(crit.status_message(), "Couldn't find main.rs");
+        crit.test_with_data(&data! { "main_rs" => "" });
+        assert_eq!(crit.status_message(), "failed");
+
+        crit.reset();
+        assert!(crit.failed_because.is_none());
+    }
+
+    #[test]
+    fn test_set_messages() {
+        let mut c = test_crit();
+        c.set_messages("bien hecho", "intenta de nuevo");
+        assert_eq!(c.success_message(), "bien hecho");
+        assert_eq!(c.failure_message(), "intenta de nuevo");
+
+        // Owned strings work too
+        c.set_messages(String::from("yes"), String::from("no"));
+        assert_eq!(c.messages, (String::from("yes"), String::from("no")));
+    }
+
     #[test]
     fn test_data_macro() {
         // The long way
@@ -241,4 +980,179 @@ mod tests {
         let data = data! { "key" => "value" };
         assert_eq!(map, data);
     }
+
+    #[test]
+    fn test_command_criterion_passes() {
+        let mut crit = Criterion::new("git installed")
+            .command("git", &["--version"])
+            .expect(|r| r.exit_code == Some(0))
+            .build();
+
+        assert!(crit.test());
+        let result = crit.command_result.unwrap();
+        assert!(result.stdout.contains("git version"));
+    }
+
+    #[test]
+    fn test_command_criterion_fails() {
+        let mut crit = Criterion::new("bad git command")
+            .command("git", &["not-a-git-command"])
+            .build();
+        assert!(!crit.test());
+        assert!(crit.command_result.is_some());
+
+        let mut missing = Criterion::new("missing program")
+            .command("definitely_not_a_real_program", &[])
+            .expect(|_| true)
+            .build();
+        assert!(!missing.test());
+        assert!(missing.command_result.is_none());
+    }
+
+    #[test]
+    fn test_condition() {
+        let crit = Criterion::new("rust only")
+            .when(|data: &TestData| data.get("language").map(|l| l.as_str()) == Some("rust"))
+            .build();
+
+        assert!(crit.applies_to(&data! { "language" => "rust" }));
+        assert!(!crit.applies_to(&data! { "language" => "python" }));
+        assert!(!crit.applies_to(&TestData::new()));
+
+        // No condition always applies
+        assert!(test_crit().applies_to(&TestData::new()));
+    }
+
+    #[test]
+    fn test_weighted_worth() {
+        let unweighted = Criterion::new("plain").worth(10).build();
+        assert_eq!(unweighted.weighted_worth(), 10);
+
+        let weighted = Criterion::new("weighted").worth(10).weight(0.25).build();
+        assert_eq!(weighted.weighted_worth(), 3);
+        // Displayed worth doesn't change
+        assert_eq!(weighted.points_display(), "— / 10 pts");
+
+        let penalty = Criterion::new("penalty").worth(-10).weight(0.5).build();
+        assert_eq!(penalty.weighted_worth(), -5);
+    }
+
+    #[test]
+    fn test_weight_over_one_is_clamped() {
+        let crit = Criterion::new("overweight").worth(10).weight(1.5).build();
+        assert!(crit.is_clamped());
+        assert_eq!(crit.exact_worth(), 10.0);
+        assert_eq!(crit.weighted_worth(), 10);
+
+        let penalty = Criterion::new("penalty").worth(5).penalty(true).weight(3.0).build();
+        assert!(penalty.is_clamped());
+        assert_eq!(penalty.weighted_worth(), -5);
+
+        assert!(!test_crit().is_clamped());
+    }
+
+    #[test]
+    fn test_multi_line_desc() {
+        let mut crit = Criterion::new("Server runs")
+            .desc("Start the server:\n    cargo run -- --port {port}\nThen visit http://localhost:{port} in your browser")
+            .test(Box::new(|_: &TestData| true))
+            .build();
+        assert_eq!(crit.desc_display(4, None).unwrap().lines().count(), 3);
+
+        crit.test_with_data(&data! { "port" => "8080" });
+        let shown = crit.desc_display(4, Some(34)).unwrap();
+        assert_eq!(shown, [
+            "    Start the server:",
+            "        cargo run -- --port 8080",
+            "    Then visit",
+            "    http://localhost:8080 in your",
+            "    browser",
+        ].join("\n"));
+
+        // The template is kept for the next submission
+        assert!(crit.desc.as_ref().unwrap().contains("{port}"));
+        crit.reset();
+        assert!(crit.filled_desc.is_none());
+    }
+
+    #[test]
+    fn test_desc_with_one_pass() {
+        let crit = Criterion::new("Template").desc("{a} and {b}, {{a}} {missing} {a").build();
+        // Values aren't substituted again, whatever order the keys are in
+        let data = data! { "a" => "{b}", "b" => "é" };
+        assert_eq!(crit.desc_with(&data).unwrap(), "{b} and é, {{b}} {missing} {a");
+
+        assert!(Criterion::new("No desc").build().desc_with(&data).is_none());
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut crit = Criterion::new("Has main")
+            .test(Box::new(|data: &TestData| data["main_rs"].contains("fn main")))
+            .explain(|data: &TestData, passed| {
+                if passed {
+                    String::from("found `fn main`")
+                } else {
+                    format!("expected main.rs to contain `fn main`, got `{}`", data["main_rs"])
+                }
+            })
+            .build();
+        assert!(crit.explanation.is_none());
+
+        crit.test_with_data(&data! { "main_rs" => "fn helper() {}" });
+        assert_eq!(crit.explanation.as_deref(), Some("expected main.rs to contain `fn main`, got `fn helper() {}`"));
+
+        crit.test_with_data(&data! { "main_rs" => "fn main() {}" });
+        assert_eq!(crit.explanation.as_deref(), Some("found `fn main`"));
+
+        crit.reset();
+        assert!(crit.explanation.is_none());
+
+        // No explain closure, no explanation
+        let mut plain = test_crit();
+        plain.test();
+        assert!(plain.explanation.is_none());
+    }
+
+    #[test]
+    fn test_invalid_weight_is_clamped() {
+        let negative = Criterion::new("negative").worth(10).weight(-1.5).build();
+        assert!(!negative.has_valid_weight());
+        assert_eq!(negative.weighted_worth(), 0);
+
+        let negative_penalty = Criterion::new("negative penalty").worth(-10).weight(-1.0).build();
+        assert_eq!(negative_penalty.weighted_worth(), 0);
+
+        let infinite = Criterion::new("infinite").worth(10).weight(f64::INFINITY).build();
+        assert!(!infinite.has_valid_weight());
+        assert_eq!(infinite.weighted_worth(), 0);
+
+        let nan = Criterion::new("nan").worth(10).weight(f64::NAN).build();
+        assert_eq!(nan.weighted_worth(), 0);
+
+        assert!(test_crit().has_valid_weight());
+    }
+
+    #[test]
+    fn test_points_display() {
+        let mut crit = test_crit();
+        assert_eq!(crit.points_awarded(), None);
+        assert_eq!(crit.points_display(), "— / 10 pts");
+
+        crit.status = Some(true);
+        assert_eq!(crit.points_awarded(), Some(10));
+        assert_eq!(crit.points_display(), "10 / 10 pts");
+
+        crit.status = Some(false);
+        assert_eq!(crit.points_awarded(), Some(0));
+        assert_eq!(crit.points_display(), "0 / 10 pts");
+    }
+
+    #[test]
+    fn test_display() {
+        let mut crit = test_crit();
+        assert_eq!(format!("{}", crit), "test (— / 10 pts)");
+        crit.test();
+        assert_eq!(format!("{}", crit), "test (10 / 10 pts)");
+    }
 }
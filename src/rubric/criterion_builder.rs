@@ -1,6 +1,16 @@
+// std uses
+use std::collections::HashMap;
+#[cfg(feature = "async")]
+use std::future::Future;
+
 // internal uses
-use crate::{TestData, rubric::Criterion};
+use crate::{TestData, rubric::{Cost, Criterion}};
+use crate::rubric::criterion::{CommandTest, CommandPredicate, Condition, CriterionOutcome, Explanation, OutcomeTest};
+use crate::helpers::process::CommandResult;
+
 
+/// The index of a criterion that wasn't given one.
+pub const DEFAULT_INDEX: i64 = 100;
 
 /// A builder struct that builds a Criterion. You should create one
 /// of these through [`Criterion::new`](crate::criterion::Criterion::new)
@@ -9,11 +19,29 @@ pub struct CriterionBuilder {
     name: String,
     func: Option<String>,
     worth: isize,
+    worth_f64: Option<f64>,
     messages: (String, String),
     desc: Option<String>,
     test: Option<Box<dyn Fn(&TestData) -> bool>>,
+    outcome_test: Option<OutcomeTest>,
+    named_failures: HashMap<String, String>,
     index: i64,
-    hide: bool
+    hide: bool,
+    command: Option<(String, Vec<String>)>,
+    expect: Option<CommandPredicate>,
+    condition: Option<Condition>,
+    required: bool,
+    bonus: bool,
+    penalty: bool,
+    retries: u32,
+    weight: Option<f64>,
+    explain: Option<Explanation>,
+    tags: Vec<String>,
+    group: Option<String>,
+    cost: Option<Cost>,
+    required_data: Vec<String>,
+    #[cfg(feature = "async")]
+    async_test: Option<crate::rubric::criterion::AsyncTest>
 }
 
 impl CriterionBuilder {
@@ -28,11 +56,29 @@ impl CriterionBuilder {
             name: String::from(name.trim()),
             func: None,
             worth: 0,
+            worth_f64: None,
             messages: ("passed".to_string(), "failed".to_string()),
             desc: None,
             test: None,
-            index: 100,
-            hide: false
+            outcome_test: None,
+            named_failures: HashMap::new(),
+            index: DEFAULT_INDEX,
+            hide: false,
+            command: None,
+            expect: None,
+            condition: None,
+            required: false,
+            bonus: false,
+            penalty: false,
+            retries: 0,
+            weight: None,
+            explain: None,
+            tags: Vec::new(),
+            group: None,
+            cost: None,
+            required_data: Vec::new(),
+            #[cfg(feature = "async")]
+            async_test: None
         }
     }
 
@@ -81,6 +127,97 @@ impl CriterionBuilder {
         self
     }
 
+    /// Sets an async test for the criterion, used instead of `test`.
+    ///
+    /// The future can't borrow the data, so clone anything it needs. Async tests
+    /// only run when grading with
+    /// [`Submission::grade_against_async`](crate::dropbox::submission::Submission::grade_against_async).
+    /// Graded any other way, the criterion fails.
+    ///
+    /// Only available with the `async` feature.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// # use rubric::TestData;
+    /// let crit = CriterionBuilder::new("Responds")
+    ///     .test_async(|data: &TestData| {
+    ///         let url = data["url"].clone();
+    ///         async move {
+    ///             // await an async http client here
+    ///             url.starts_with("http")
+    ///         }
+    ///     })
+    ///     .build();
+    /// ```
+    #[cfg(feature = "async")]
+    pub fn test_async<F, Fut>(mut self, test: F) -> Self
+    where
+        F: Fn(&TestData) -> Fut + 'static,
+        Fut: Future<Output = bool> + 'static,
+    {
+        self.async_test = Some(Box::new(move |data: &TestData| {
+            let fut: std::pin::Pin<Box<dyn Future<Output = bool>>> = Box::pin(test(data));
+            fut
+        }));
+        self
+    }
+
+    /// Sets a command for the criterion to run as its test.
+    ///
+    /// When the criterion is tested, the command is run and the result is
+    /// judged by the predicate set with [`expect`](crate::rubric::CriterionBuilder::expect).
+    /// If you don't set one, the criterion passes if the command exits with code 0.
+    /// A command that can't be started at all always fails. A
+    /// [`test_outcome`](crate::rubric::CriterionBuilder::test_outcome) is used
+    /// instead of the command if the criterion has both.
+    ///
+    /// When grading a submission, the command's stdout, stderr, and exit code are
+    /// stored in the submission data as `<func>_stdout`, `<func>_stderr`, and
+    /// `<func>_exit_code`. The output is cut off at the rubric's
+    /// [`output_limit`](crate::rubric::Rubric::output_limit).
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// let crit = CriterionBuilder::new("git installed")
+    ///     .command("git", &["--version"])
+    ///     .expect(|r| r.exit_code == Some(0))
+    ///     .build();
+    /// ```
+    pub fn command(mut self, cmd: &str, args: &[&str]) -> Self {
+        self.command = Some((
+            String::from(cmd),
+            args.iter().map(|a| String::from(*a)).collect()
+        ));
+        self
+    }
+
+    /// Sets the predicate that judges the result of the criterion's
+    /// [`command`](crate::rubric::CriterionBuilder::command).
+    ///
+    /// This does nothing if there's no command.
+    pub fn expect<F: Fn(&CommandResult) -> bool + 'static>(mut self, expect: F) -> Self {
+        self.expect = Some(Box::new(expect));
+        self
+    }
+
+    /// Sets a condition that decides if the criterion applies to a submission.
+    ///
+    /// When grading, the condition is checked with the submission's data before
+    /// the test is run. If it returns false, the criterion is skipped: it doesn't
+    /// pass or fail, and no points are given or taken.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// # use rubric::TestData;
+    /// let crit = CriterionBuilder::new("Cargo.toml exists")
+    ///     .when(|data: &TestData| data.get("language").map(|l| l.as_str()) == Some("rust"))
+    ///     .build();
+    /// ```
+    pub fn when<F: Fn(&TestData) -> bool + 'static>(mut self, condition: F) -> Self {
+        self.condition = Some(Box::new(condition));
+        self
+    }
+
     /// Sets the messages of a criterion.
     ///
     /// ```rust
@@ -114,6 +251,24 @@ impl CriterionBuilder {
     /// Sets the worth on a Criterion
     pub fn worth(mut self, worth: isize) -> Self {
         self.worth = worth;
+        self.worth_f64 = None;
+        self
+    }
+
+    /// Sets a worth that can have a fraction, like `2.5`.
+    ///
+    /// The criterion's `worth` is set to this rounded to a whole point. See
+    /// [`Criterion::worth_f64`](crate::rubric::Criterion::worth_f64).
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// let crit = CriterionBuilder::new("half points").worth_f64(2.5).build();
+    /// assert_eq!(crit.worth, 3);
+    /// assert_eq!(crit.exact_worth(), 2.5);
+    /// ```
+    pub fn worth_f64(mut self, worth: f64) -> Self {
+        self.worth = worth.round() as isize;
+        self.worth_f64 = Some(worth);
         self
     }
 
@@ -131,6 +286,223 @@ impl CriterionBuilder {
         self
     }
 
+    /// Sets the weight of a criterion. The points it adds to a grade
+    /// are its worth multiplied by the weight, but the worth shown to
+    /// students doesn't change. It never adds more than its worth, so
+    /// weights over 1 are clamped.
+    ///
+    /// See [`Criterion::weighted_worth`](crate::rubric::Criterion::weighted_worth).
+    pub fn weight(mut self, weight: f64) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+
+    /// Sets a test that says why it failed, used instead of
+    /// [`test`](crate::rubric::CriterionBuilder::test).
+    ///
+    /// The test returns [`CriterionOutcome::Fail`](crate::rubric::criterion::CriterionOutcome::Fail)
+    /// with the name of a failure message added with
+    /// [`named_failure`](crate::rubric::CriterionBuilder::named_failure), and that
+    /// message is shown instead of the normal failure message. If it gives no name,
+    /// or a name without a message, the normal failure message is shown.
+    ///
+    /// This is also used instead of a [`command`](crate::rubric::CriterionBuilder::command).
+    /// If a criterion has both, the command is never run.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::{CriterionBuilder, CriterionOutcome};
+    /// # use rubric::{TestData, data};
+    /// let mut crit = CriterionBuilder::new("Has main")
+    ///     .messages("Found main", "main.rs is wrong")
+    ///     .named_failure("missing", "Couldn't find main.rs, is it in src/?")
+    ///     .test_outcome(|data: &TestData| match data.get("main_rs") {
+    ///         None => CriterionOutcome::Fail(Some(String::from("missing"))),
+    ///         Some(main) if main.contains("fn main") => CriterionOutcome::Pass,
+    ///         Some(_) => CriterionOutcome::Fail(None),
+    ///     })
+    ///     .build();
+    ///
+    /// crit.test_with_data(&TestData::new());
+    /// assert_eq!(crit.status_message(), "Couldn't find main.rs, is it in src/?");
+    /// ```
+    pub fn test_outcome<F: Fn(&TestData) -> CriterionOutcome + 'static>(mut self, test: F) -> Self {
+        self.outcome_test = Some(Box::new(test));
+        self
+    }
+
+    /// Adds a failure message for a specific reason. See
+    /// [`test_outcome`](crate::rubric::CriterionBuilder::test_outcome).
+    pub fn named_failure(mut self, name: &str, message: &str) -> Self {
+        self.named_failures.insert(String::from(name), String::from(message));
+        self
+    }
+
+    /// Sets a closure that explains the criterion's result.
+    ///
+    /// After the criterion is tested, this is called with the data and whether
+    /// it passed. What it returns is stored in the criterion's `explanation` and
+    /// shown in the long report. Unlike the pass and fail messages, this can look
+    /// at the data to say exactly what went wrong.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// # use rubric::TestData;
+    /// let crit = CriterionBuilder::new("Has main")
+    ///     .test(Box::new(|data: &TestData| data["main_rs"].contains("fn main")))
+    ///     .explain(|data: &TestData, passed| {
+    ///         if passed {
+    ///             String::from("main.rs contains `fn main`")
+    ///         } else {
+    ///             format!("expected main.rs to contain `fn main`, but it was {} bytes without it", data["main_rs"].len())
+    ///         }
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn explain<F: Fn(&TestData, bool) -> String + 'static>(mut self, explain: F) -> Self {
+        self.explain = Some(Box::new(explain));
+        self
+    }
+
+    /// Sets the required flag on a criterion. If a required criterion
+    /// fails, the submission doesn't pass the rubric no matter its grade.
+    /// See [`Submission::passed_rubric`](crate::dropbox::submission::Submission::passed_rubric).
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// let crit = CriterionBuilder::new("Compiles")
+    ///     .required(true)
+    ///     .build();
+    /// ```
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// Sets the bonus flag on a criterion. A bonus criterion adds its
+    /// worth when it passes, but isn't counted in the rubric's total.
+    /// See [`Rubric::bonus_total`](crate::rubric::Rubric::bonus_total).
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// let crit = CriterionBuilder::new("Extra credit")
+    ///     .worth(5)
+    ///     .bonus(true)
+    ///     .build();
+    /// assert!(crit.bonus);
+    /// ```
+    pub fn bonus(mut self, bonus: bool) -> Self {
+        self.bonus = bonus;
+        self
+    }
+
+    /// Sets the penalty flag on a criterion. A penalty's test checks for
+    /// something bad, and the worth is subtracted when the test returns true.
+    /// If it returns false, the grade doesn't change.
+    ///
+    /// This works the same as giving the criterion a negative worth, which
+    /// still works too. With the flag the worth can be written as a positive
+    /// number, so it's clear what the test is checking for.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// let crit = CriterionBuilder::new("Has compiler warnings")
+    ///     .worth(5)
+    ///     .penalty(true)
+    ///     .build();
+    /// assert_eq!(crit.weighted_worth(), -5);
+    /// ```
+    pub fn penalty(mut self, penalty: bool) -> Self {
+        self.penalty = penalty;
+        self
+    }
+
+    /// Runs the test again, up to `retries` more times, if it fails.
+    /// The criterion passes as soon as one run passes.
+    ///
+    /// This is for tests that fail now and then for reasons that aren't
+    /// the student's fault, like a slow network. Criteria that needed a retry
+    /// to pass are listed by [`Rubric::flaky`](crate::rubric::Rubric::flaky).
+    /// Penalty criteria, or anything else worth negative points, and async tests aren't retried.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// let crit = CriterionBuilder::new("Site responds")
+    ///     .retries(2)
+    ///     .build();
+    /// assert_eq!(crit.retries, 2);
+    /// ```
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Adds a tag to the criterion. Tags are just labels for grouping
+    /// criteria, like `"style"` or `"tests"`. Adding the same tag twice
+    /// does nothing.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// let crit = CriterionBuilder::new("Formatted")
+    ///     .tag("style")
+    ///     .tag("week 1")
+    ///     .build();
+    /// assert_eq!(crit.tags, vec!["style", "week 1"]);
+    /// ```
+    pub fn tag(mut self, tag: &str) -> Self {
+        if !self.tags.iter().any(|t| t == tag) {
+            self.tags.push(String::from(tag));
+        }
+        self
+    }
+
+    /// Puts the criterion in a group, a section of the rubric like `"Setup"`.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// let crit = CriterionBuilder::new("Git installed").group("Setup").build();
+    /// assert_eq!(crit.group(), Some("Setup"));
+    /// ```
+    pub fn group(mut self, group: &str) -> Self {
+        self.group = Some(String::from(group));
+        self
+    }
+
+    /// Says how expensive the criterion's test is, like whether it makes
+    /// network requests. This is only a hint for scheduling, see
+    /// [`Rubric::cost_profile`](crate::rubric::Rubric::cost_profile).
+    ///
+    /// ```rust
+    /// # use rubric::rubric::{Cost, CriterionBuilder};
+    /// let crit = CriterionBuilder::new("Site is up").cost(Cost::Network).build();
+    /// assert_eq!(crit.cost(), Cost::Network);
+    /// ```
+    pub fn cost(mut self, cost: Cost) -> Self {
+        self.cost = Some(cost);
+        self
+    }
+
+    /// Adds a key that has to be in the submission data for this criterion
+    /// to be tested.
+    ///
+    /// When grading, if any of these keys are missing, the criterion is skipped
+    /// with a "missing data" reason instead of being tested. This keeps a test
+    /// from panicking when it indexes data that isn't there.
+    ///
+    /// ```rust
+    /// # use rubric::rubric::CriterionBuilder;
+    /// # use rubric::TestData;
+    /// let crit = CriterionBuilder::new("Port is open")
+    ///     .require_data("port")
+    ///     .test(Box::new(|data: &TestData| data["port"] == "8080"))
+    ///     .build();
+    /// ```
+    pub fn require_data(mut self, key: &str) -> Self {
+        if !self.required_data.iter().any(|k| k == key) {
+            self.required_data.push(String::from(key));
+        }
+        self
+    }
+
     /// Finalizes the criterion.
     ///
     /// If a function name wasn't manually set, it will create one based on the
@@ -152,16 +524,51 @@ impl CriterionBuilder {
                 .join("_")
         });
 
+        let expect = self.expect;
+        let command = self.command.map(|(cmd, args)| CommandTest {
+            cmd,
+            args,
+            expect: expect.unwrap_or_else(|| Box::new(|r: &CommandResult| r.success()))
+        });
+
         Criterion {
             func: func,
             name: name,
             worth: self.worth,
+            worth_f64: self.worth_f64,
             messages: self.messages,
             desc: self.desc,
+            attached: self.test.is_some(),
             test: self.test.unwrap_or(Box::new(|_: &TestData| false)),
             index: self.index,
             status: None,
-            hide: self.hide
+            hide: self.hide,
+            command,
+            command_result: None,
+            condition: self.condition,
+            skipped: false,
+            skip_reason: None,
+            required_data: self.required_data,
+            required: self.required,
+            bonus: self.bonus,
+            penalty: self.penalty,
+            retries: self.retries,
+            attempts: 0,
+            run_count: 0,
+            weight: self.weight,
+            explain: self.explain,
+            explanation: None,
+            filled_desc: None,
+            tags: self.tags,
+            group: self.group,
+            cost: self.cost,
+            outcome_test: self.outcome_test,
+            named_failures: self.named_failures,
+            failed_because: None,
+            #[cfg(feature = "async")]
+            async_test: self.async_test,
+            #[cfg(feature = "async")]
+            awaited: None
         }
     }
 }
@@ -0,0 +1,188 @@
+//! Adjusting grades after they've been graded
+
+// std uses
+use std::fmt;
+
+// external uses
+use anyhow::anyhow;
+
+// internal uses
+use super::Rubric;
+use crate::{Result, Submission};
+
+
+/// A change to every grade in a set of submissions, like at the end of a term.
+///
+/// See [`Rubric::apply_curve`](crate::rubric::Rubric::apply_curve).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    /// Adds this many points. It can be negative.
+    AddPoints(i16),
+    /// Multiplies the grade by this, rounding to the nearest point.
+    /// It has to be a finite number that isn't negative.
+    Scale(f64),
+    /// Raises any grade below this up to it.
+    SetMin(i16),
+}
+
+impl Curve {
+    /// Returns an `Err` if the curve can't be applied, ie. it scales
+    /// by a negative number, infinity, or `NaN`.
+    pub fn check(&self) -> Result<()> {
+        match *self {
+            Curve::Scale(factor) if !factor.is_finite() || factor < 0.0 =>
+                Err(anyhow!("Can't scale grades by {}, it has to be a finite number that isn't negative", factor)),
+            _ => Ok(())
+        }
+    }
+
+    // The grade after the curve, before it's clamped
+    fn apply(&self, grade: isize) -> isize {
+        match *self {
+            Curve::AddPoints(points) => grade + points as isize,
+            Curve::Scale(factor) => (grade as f64 * factor).round() as isize,
+            Curve::SetMin(min) => grade.max(min as isize),
+        }
+    }
+}
+
+impl fmt::Display for Curve {
+    /// Like `"+5 points"`, `"scaled by 1.1"`, or `"raised to at least 60"`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Curve::AddPoints(points) => write!(f, "{:+} points", points),
+            Curve::Scale(factor) => write!(f, "scaled by {}", factor),
+            Curve::SetMin(min) => write!(f, "raised to at least {}", min),
+        }
+    }
+}
+
+
+impl Rubric {
+    /// Curves the grades of submissions that were graded against this rubric.
+    ///
+    /// Curved grades are kept between 0 and the rubric's
+    /// [`total_points`](crate::rubric::Rubric::total_points). A grade that was already
+    /// over the total (from bonus points) isn't lowered by that, but a curve won't
+    /// raise it any more.
+    ///
+    /// Each submission whose grade changed gets the curve recorded in its
+    /// [`override_reason`](crate::dropbox::submission::Submission::override_reason),
+    /// after any reason that was already there, like `"Curve: +5 points"`.
+    ///
+    /// Returns an `Err`, without changing any grades, if the curve doesn't pass
+    /// [`Curve::check`](crate::rubric::Curve::check).
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission};
+    /// # use rubric::rubric::{Criterion, Curve};
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Everything").worth(100).build());
+    ///
+    /// let mut subs = vec![Submission::new(), Submission::new()];
+    /// subs[0].grade = 70;
+    /// subs[1].grade = 98;
+    ///
+    /// rubric.apply_curve(&mut subs, Curve::AddPoints(5)).unwrap();
+    /// assert_eq!(subs[0].grade, 75);
+    /// assert_eq!(subs[1].grade, 100);
+    /// assert_eq!(subs[0].override_reason.as_deref(), Some("Curve: +5 points"));
+    /// ```
+    pub fn apply_curve(&self, submissions: &mut [Submission], curve: Curve) -> Result<()> {
+        curve.check()?;
+        let max = self.total_points();
+        for sub in submissions {
+            let ceiling = max.max(sub.grade);
+            let curved = curve.apply(sub.grade).min(ceiling).max(0);
+            if curved == sub.grade {
+                continue;
+            }
+
+            let reason = match &sub.override_reason {
+                Some(reason) => format!("{}; Curve: {}", reason, curve),
+                None => format!("Curve: {}", curve),
+            };
+            sub.override_grade(curved, &reason);
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rubric::Criterion;
+
+    fn rubric() -> Rubric {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("First").worth(60).build());
+        rubric.add(Criterion::new("Second").worth(40).build());
+        rubric
+    }
+
+    fn submissions(grades: &[isize]) -> Vec<Submission> {
+        grades.iter().map(|grade| {
+            let mut sub = Submission::new();
+            sub.grade = *grade;
+            sub
+        }).collect()
+    }
+
+    fn grades(subs: &[Submission]) -> Vec<isize> {
+        subs.iter().map(|s| s.grade).collect()
+    }
+
+    #[test]
+    fn test_add_points() {
+        let mut subs = submissions(&[0, 50, 97]);
+        rubric().apply_curve(&mut subs, Curve::AddPoints(5)).unwrap();
+        assert_eq!(grades(&subs), vec![5, 55, 100]);
+
+        rubric().apply_curve(&mut subs, Curve::AddPoints(-10)).unwrap();
+        assert_eq!(grades(&subs), vec![0, 45, 90]);
+        assert_eq!(subs[1].override_reason.as_deref(), Some("Curve: +5 points; Curve: -10 points"));
+    }
+
+    #[test]
+    fn test_scale() {
+        let mut subs = submissions(&[0, 45, 95]);
+        rubric().apply_curve(&mut subs, Curve::Scale(1.1)).unwrap();
+        // 49.5 rounds up
+        assert_eq!(grades(&subs), vec![0, 50, 100]);
+        assert!(subs[0].override_reason.is_none());
+        assert_eq!(subs[1].override_reason.as_deref(), Some("Curve: scaled by 1.1"));
+    }
+
+    #[test]
+    fn test_bad_scale() {
+        let mut subs = submissions(&[45]);
+        for factor in &[f64::NAN, -1.0, f64::INFINITY] {
+            assert!(rubric().apply_curve(&mut subs, Curve::Scale(*factor)).is_err());
+        }
+        assert_eq!(grades(&subs), vec![45]);
+        assert!(subs[0].override_reason.is_none());
+        assert!(Curve::Scale(0.0).check().is_ok());
+    }
+
+    #[test]
+    fn test_set_min() {
+        let mut subs = submissions(&[20, 60, 80]);
+        subs[0].override_grade(20, "Late work");
+        rubric().apply_curve(&mut subs, Curve::SetMin(60)).unwrap();
+        assert_eq!(grades(&subs), vec![60, 60, 80]);
+        assert_eq!(subs[0].override_reason.as_deref(), Some("Late work; Curve: raised to at least 60"));
+        // Nothing changed for the others
+        assert!(subs[1].override_reason.is_none());
+        assert!(subs[2].override_reason.is_none());
+    }
+
+    #[test]
+    fn test_curve_keeps_bonus() {
+        // 110 is from bonus points, it isn't taken away
+        let mut subs = submissions(&[110]);
+        rubric().apply_curve(&mut subs, Curve::AddPoints(5)).unwrap();
+        assert_eq!(grades(&subs), vec![110]);
+        assert!(subs[0].override_reason.is_none());
+    }
+}
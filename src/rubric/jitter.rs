@@ -0,0 +1,177 @@
+//! Changing criteria worths a little for each student
+
+// std uses
+use std::collections::HashMap;
+
+// internal uses
+use super::{seeded_hash, Rubric};
+use super::criterion::Criterion;
+
+
+// Only plain criteria are moved. Bonus criteria and penalties aren't part of
+// the total, and fractional worths are already off the whole points.
+fn can_jitter(crit: &Criterion) -> bool {
+    !crit.bonus && !crit.penalty && crit.worth_f64.is_none() && crit.weighted_worth() > 0
+}
+
+
+impl Rubric {
+    /// The worth each criterion is graded with for a seed, by `func`. Each one is
+    /// moved by up to [`worth_jitter`](crate::rubric::Rubric::worth_jitter) points
+    /// from its [`weighted_worth`](crate::rubric::Criterion::weighted_worth), then
+    /// they're evened out so they add up to the same
+    /// [`required_total`](crate::rubric::Rubric::required_total). This is empty if
+    /// `worth_jitter` isn't set.
+    ///
+    /// How far each worth moves comes from the seed and the criterion's `func`, so the
+    /// same seed always gives the same worths. Keep the seed to explain a grade later.
+    /// Worths never go below 1. Bonus criteria, penalties, and criteria with a fractional
+    /// worth aren't in the map, they're graded with their usual worth.
+    ///
+    /// These are used when grading if the rubric has a `worth_jitter`, with the seed in
+    /// the submission's data (see [`SEED_KEY`](crate::rubric::SEED_KEY)). Give each
+    /// student their own with [`seed_for`](crate::rubric::Rubric::seed_for). The criteria's
+    /// own worths aren't changed, the worths a submission was graded with are the
+    /// `possible` points in its [`results`](crate::dropbox::submission::Submission::results).
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, yaml};
+    /// let mut rubric = Rubric::from_yaml(yaml!("../../test_data/test_rubric.yml").unwrap()).unwrap();
+    /// rubric.worth_jitter = Some(2);
+    ///
+    /// let worths = rubric.perturbed_worths(rubric.seed_for("1234").unwrap_or(1234));
+    /// assert_eq!(worths.values().sum::<isize>(), rubric.required_total());
+    /// ```
+    pub fn perturbed_worths(&self, seed: u64) -> HashMap<String, isize> {
+        let jitter = match self.worth_jitter {
+            Some(jitter) if jitter > 0 => jitter as isize,
+            _ => return HashMap::new()
+        };
+
+        let mut criteria: Vec<&Criterion> = self.criteria.iter()
+            .filter(|crit| can_jitter(crit))
+            .collect();
+        // The order the leftover points are evened out in
+        criteria.sort_by_cached_key(|crit| (seeded_hash(seed, &crit.func), crit.func.clone()));
+
+        let mut worths = Vec::with_capacity(criteria.len());
+        let mut bounds = Vec::with_capacity(criteria.len());
+        let mut drift = 0;
+        for crit in &criteria {
+            let base = crit.weighted_worth();
+            let (low, high) = ((base - jitter).max(1), base + jitter);
+            let offset = (seeded_hash(seed, &crit.func) % (2 * jitter as u64 + 1)) as isize - jitter;
+            let worth = (base + offset).max(low);
+            drift += worth - base;
+            worths.push(worth);
+            bounds.push((low, high));
+        }
+
+        // Every original worth is within the bounds, so this always evens out
+        while drift != 0 {
+            for (worth, (low, high)) in worths.iter_mut().zip(&bounds) {
+                if drift > 0 && *worth > *low {
+                    *worth -= 1;
+                    drift -= 1;
+                } else if drift < 0 && *worth < *high {
+                    *worth += 1;
+                    drift += 1;
+                }
+            }
+        }
+
+        criteria.iter()
+            .map(|crit| crit.func.clone())
+            .zip(worths)
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data, Submission, TestData};
+    use crate::rubric::SEED_KEY;
+
+    const YAML: &str = "name: Jitter\nworth_jitter: 3\ncriteria:\n  One:\n    func: one\n    worth: 10\n  Two:\n    func: two\n    worth: 2\n  Three:\n    func: three\n    worth: 25\n  Four:\n    func: four\n    worth: 8\n  Extra:\n    func: extra\n    worth: 5\n    bonus: true\n  Warnings:\n    func: warnings\n    worth: -4\n";
+
+    // The perturbed worths in order, so they can be compared
+    fn sorted(worths: &HashMap<String, isize>) -> Vec<(String, isize)> {
+        let mut worths: Vec<(String, isize)> = worths.iter()
+            .map(|(func, worth)| (func.clone(), *worth))
+            .collect();
+        worths.sort();
+        worths
+    }
+
+    #[test]
+    fn test_same_seed_same_worths() {
+        let rubric = Rubric::from_yaml(YAML).unwrap();
+        assert_eq!(rubric.worth_jitter, Some(3));
+        let required = rubric.required_total();
+
+        let mut seen = Vec::new();
+        for seed in 0..20 {
+            let perturbed = rubric.perturbed_worths(seed);
+            // Not the bonus or the penalty
+            assert_eq!(perturbed.len(), 4);
+            assert_eq!(perturbed.values().sum::<isize>(), required);
+            for (func, after) in &perturbed {
+                let before = rubric.get(func).unwrap().weighted_worth();
+                assert!((before - after).abs() <= 3, "{} moved from {} to {}", func, before, after);
+                assert!(*after >= 1);
+            }
+
+            // A fresh copy of the rubric gives the same worths
+            let again = Rubric::from_yaml(YAML).unwrap();
+            assert_eq!(again.perturbed_worths(seed), perturbed);
+            seen.push(sorted(&perturbed));
+        }
+        seen.dedup();
+        assert!(seen.len() > 1);
+
+        let mut off = Rubric::from_yaml(YAML).unwrap();
+        off.worth_jitter = None;
+        assert!(off.perturbed_worths(7).is_empty());
+    }
+
+    #[test]
+    fn test_weighted_total_kept() {
+        let yaml = YAML.replace("worth: 25\n", "worth: 25\n    weight: 0.5\n");
+        let rubric = Rubric::from_yaml(&yaml).unwrap();
+        assert_eq!(rubric.get("three").unwrap().weighted_worth(), 13);
+
+        for seed in 0..20 {
+            let perturbed = rubric.perturbed_worths(seed);
+            assert_eq!(perturbed.values().sum::<isize>(), rubric.required_total());
+        }
+    }
+
+    #[test]
+    fn test_grading_uses_perturbed_worths() {
+        fn pass(_: &TestData) -> bool { true }
+        let mut rubric = Rubric::from_yaml(YAML).unwrap();
+        for func in ["one", "two", "three", "four"] {
+            rubric.get_mut(func).unwrap().attach(Box::new(pass));
+        }
+        let fingerprint = rubric.fingerprint();
+        let summary = rubric.to_summary_json();
+
+        let mut sub = Submission::from_data(data! { SEED_KEY => "7" });
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.grade, 45);
+
+        // The results have the worths this student was graded with
+        let perturbed = rubric.perturbed_worths(7);
+        for (func, worth) in &perturbed {
+            let result = sub.results.iter().find(|r| &r.func == func).unwrap();
+            assert_eq!((result.awarded, result.possible), (*worth, *worth));
+        }
+
+        // The rubric itself isn't changed
+        assert_eq!(rubric.get("one").unwrap().worth, 10);
+        assert_eq!(rubric.fingerprint(), fingerprint);
+        assert_eq!(rubric.to_summary_json(), summary);
+    }
+}
@@ -28,24 +28,53 @@
 // Re exports to be available from this module
 pub mod criterion;
 pub mod criterion_builder;
+pub mod cache;
+pub mod validation;
+pub mod curve;
+pub mod registry;
+pub mod jitter;
+pub mod cost;
 
-pub use criterion::Criterion;
+pub use criterion::{Criterion, CriterionOutcome, TestFn};
 pub use criterion_builder::CriterionBuilder;
+pub use cache::ResultCache;
+pub use validation::ValidationError;
+pub use curve::Curve;
+pub use cost::{Cost, CostProfile};
 
 
 // std uses
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::default::Default;
+use std::ops::{Index, IndexMut};
+use std::time::Duration;
 
 // external uses
 use chrono::{DateTime, Local};
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use paris::Logger;
+use sha2::{Digest, Sha256};
+use serde_json::{json, Value};
 
 // internal uses
-use crate::{Result, yaml::RubricYaml};
+use crate::{Result, Submission, dropbox::{CriterionResult, SubmissionDiff}, yaml::{RubricYaml, CriteriaYaml, DEFAULT_FAILURE_TEMPLATE}};
+use crate::report::{self, GradeReport, WorthFormat};
 
 
+/// The key a rubric's seed is stored under in a submission's data.
+///
+/// See [`Rubric::seed`](crate::rubric::Rubric::seed).
+pub const SEED_KEY: &str = "__seed";
+
+/// How many characters of a command's output are kept in a submission's
+/// data, unless the rubric sets its own [`output_limit`](crate::rubric::Rubric::output_limit).
+pub const DEFAULT_OUTPUT_LIMIT: usize = 1000;
 
 /// A collection of criteria, meant to be serialized from `yaml`.
 ///
@@ -63,30 +92,146 @@ use crate::{Result, yaml::RubricYaml};
 pub struct Rubric {
     pub name: String,
     pub desc: Option<String>,
+    /// The version of the rubric, if you keep track of one.
+    ///
+    /// This is recorded on each submission when it's graded, see
+    /// [`Submission::graded_with_older`](crate::dropbox::submission::Submission::graded_with_older).
+    pub version: Option<String>,
     pub criteria: Vec<Criterion>,
     pub total: isize,
+    /// The `total` written in the yaml, if there was one.
+    ///
+    /// This isn't used for grading, see [`validate`](crate::rubric::Rubric::validate).
+    pub expected_total: Option<isize>,
     pub deadline: Option<DateTime<Local>>,
     pub final_deadline: Option<DateTime<Local>>,
     pub allow_late: bool,
     pub late_penalty: isize,
-    pub daily_penalty: isize
+    pub daily_penalty: isize,
+    /// The grade percentage (0 - 100) a submission needs to pass the rubric.
+    ///
+    /// See [`Submission::passed_rubric`](crate::dropbox::submission::Submission::passed_rubric).
+    pub pass_threshold: Option<f64>,
+    /// A seed for criteria that use randomness, like sampling test cases.
+    ///
+    /// When a submission is graded, this is put in its data under
+    /// [`SEED_KEY`](crate::rubric::SEED_KEY) (`"__seed"`), unless the data already
+    /// has a seed. Criteria can read it to seed their RNG, so grading the same
+    /// submission twice gives the same result. Use
+    /// [`seed_for`](crate::rubric::Rubric::seed_for) to give each student their own seed.
+    pub seed: Option<u64>,
+    /// How far each criterion's worth can move from what's in the yaml, in
+    /// points, so each student's rubric is a little different.
+    ///
+    /// See [`perturbed_worths`](crate::rubric::Rubric::perturbed_worths).
+    pub worth_jitter: Option<u32>,
+    /// How many characters of each command criterion's stdout and stderr are kept in
+    /// a submission's data when it's graded. Anything past that is cut off.
+    ///
+    /// See [`CriterionBuilder::command`](crate::rubric::CriterionBuilder::command).
+    /// Defaults to [`DEFAULT_OUTPUT_LIMIT`](crate::rubric::DEFAULT_OUTPUT_LIMIT).
+    pub output_limit: usize,
+    /// Stages of the rubric that are graded separately, each with their own deadline.
+    ///
+    /// See [`grade_checkpoint`](crate::rubric::Rubric::grade_checkpoint).
+    pub checkpoints: Vec<Checkpoint>,
+    /// The width that criterion descriptions are wrapped to in the long report,
+    /// in characters. `None`, the default, doesn't wrap them.
+    ///
+    /// See [`Criterion::desc_display`](crate::rubric::Criterion::desc_display).
+    pub wrap_width: Option<usize>,
+    // Where each func is in `criteria`, so lookups don't have to search.
+    // `criteria` is public and can change under this, so every position
+    // is checked when it's used, and it's rebuilt when one is wrong.
+    positions: RefCell<HashMap<String, usize>>
+}
+
+/// The result of grading with a time budget, from
+/// [`Rubric::grade_within`](crate::rubric::Rubric::grade_within).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetedRun {
+    /// The names of criteria that weren't run because the budget ran out
+    pub not_run: Vec<String>,
+}
+
+impl BudgetedRun {
+    /// Returns true if the budget ran out before every criterion was run
+    pub fn truncated(&self) -> bool {
+        !self.not_run.is_empty()
+    }
+}
+
+/// A named stage of a rubric, like one week of a multi-week project.
+///
+/// A checkpoint is a subset of the rubric's criteria, referred to by
+/// their `func`, with its own deadline. Grade one with
+/// [`Rubric::grade_checkpoint`](crate::rubric::Rubric::grade_checkpoint).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub name: String,
+    /// Replaces the rubric's `deadline` when grading this checkpoint.
+    /// Without one, the rubric's `deadline` is used.
+    pub deadline: Option<DateTime<Local>>,
+    /// The `func` of each criterion in this checkpoint
+    pub criteria: Vec<String>,
+}
+
+/// The changes between two versions of a rubric, from
+/// [`Rubric::diff`](crate::rubric::Rubric::diff).
+///
+/// Criteria are matched up by their `func`, and referred to by it here.
+#[derive(Debug, PartialEq)]
+pub struct RubricDiff {
+    /// Criteria only in the new rubric
+    pub added: Vec<String>,
+    /// Criteria only in the old rubric
+    pub removed: Vec<String>,
+    /// Criteria in both rubrics, but with a different worth or messages
+    pub modified: Vec<CriterionChange>,
+}
+
+impl RubricDiff {
+    /// Returns true if nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// How a single criterion changed between two versions of a rubric.
+///
+/// Each field is `Some((old, new))` if it changed, `None` otherwise.
+#[derive(Debug, PartialEq)]
+pub struct CriterionChange {
+    pub func: String,
+    pub worth: Option<(isize, isize)>,
+    pub messages: Option<((String, String), (String, String))>,
 }
 
 impl Default for Rubric {
-    /// This is only used for testing and examples.
-    /// You shouldn't use this to create a new Rubric, instead
-    /// use `from_yaml()`.
+    /// An empty rubric with no criteria.
+    ///
+    /// You should usually create a rubric with `from_yaml()`, but you can
+    /// start with this and [`add`](crate::rubric::Rubric::add) criteria yourself.
     fn default() -> Rubric {
         Rubric {
             name: String::new(),
             desc: None,
+            version: None,
             criteria: Vec::new(),
             total: 0,
+            expected_total: None,
             deadline: None,
             final_deadline: None,
             allow_late: true,
             late_penalty: 0,
-            daily_penalty: 0
+            daily_penalty: 0,
+            pass_threshold: None,
+            seed: None,
+            worth_jitter: None,
+            output_limit: DEFAULT_OUTPUT_LIMIT,
+            checkpoints: Vec::new(),
+            wrap_width: None,
+            positions: RefCell::new(HashMap::new())
         }
     }
 }
@@ -113,21 +258,226 @@ impl Rubric {
         yaml.parse::<Self>().context("Couldn't parse YAML into rubric")
     }
 
+    /// Builds a rubric from criteria made in code, like with
+    /// [`Criterion::new`](crate::rubric::Criterion::new).
+    ///
+    /// The total is the worth of the criteria, not counting bonus criteria.
+    /// Everything else is the [default](crate::rubric::Rubric::default), and can
+    /// be set on the rubric afterwards.
+    ///
+    /// ```rust
+    /// # use rubric::Rubric;
+    /// # use rubric::rubric::Criterion;
+    /// let rubric = Rubric::from_criteria("Lab 1", vec![
+    ///     Criterion::new("Git installed").worth(10).build(),
+    ///     Criterion::new("Repo cloned").worth(15).build(),
+    /// ]);
+    /// assert_eq!(rubric.total, 25);
+    /// ```
+    pub fn from_criteria(name: &str, criteria: Vec<Criterion>) -> Self {
+        let total = criteria_total(&criteria);
+        Rubric {
+            name: String::from(name),
+            criteria,
+            total,
+            ..Rubric::default()
+        }
+    }
+
+    /// Loads every `.yml` or `.yaml` file in a directory and merges
+    /// them into one `Rubric`. Other files are skipped.
+    ///
+    /// Rubric-level fields (name, description, deadlines, penalties) come from
+    /// the base file only. The base file is `base.yml` or `base.yaml` if there is
+    /// one, otherwise it's the first YAML file in alphabetical order. The base file
+    /// must be a complete rubric. The other files only need a `criteria` section;
+    /// anything else in them is ignored.
+    ///
+    /// The criteria from every file are combined. If two criteria have the same `func`,
+    /// this returns an `Err`, since there would be no way to tell which one to attach a test to.
+    ///
+    /// ```no_run
+    /// # use rubric::Rubric;
+    /// // rubrics/base.yml, rubrics/lab_1.yml, ...
+    /// let rubric = Rubric::from_dir("rubrics/").expect("Couldn't load rubrics");
+    /// ```
+    pub fn from_dir<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut files: Vec<PathBuf> = fs::read_dir(path)
+            .context(format!("Couldn't read rubric directory {}", path.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .filter(|p| match p.extension().and_then(|e| e.to_str()) {
+                Some(ext) => ext == "yml" || ext == "yaml",
+                None => false
+            })
+            .collect();
+        files.sort();
+
+        if files.is_empty() {
+            return Err(anyhow!("No YAML files found in {}", path.display()));
+        }
+
+        // Move the base file to the front, if there is one
+        let base_pos = files.iter().position(|p| {
+            p.file_stem().and_then(|s| s.to_str()) == Some("base")
+        });
+        if let Some(pos) = base_pos {
+            let base = files.remove(pos);
+            files.insert(0, base);
+        }
+
+        let base_yaml = fs::read_to_string(&files[0])
+            .context(format!("Couldn't read {}", files[0].display()))?;
+        let mut rubric = Rubric::from_yaml(&base_yaml)
+            .context(format!("Couldn't parse base rubric {}", files[0].display()))?;
+        // The base file's failure message applies to every file
+        let failure_template = serde_yaml::from_str::<RubricYaml>(&base_yaml)?
+            .failure_message
+            .unwrap_or_else(|| String::from(DEFAULT_FAILURE_TEMPLATE));
+
+        for file in &files[1..] {
+            let yaml = fs::read_to_string(file)
+                .context(format!("Couldn't read {}", file.display()))?;
+            let criteria_yaml = serde_yaml::from_str::<CriteriaYaml>(&yaml)
+                .context(format!("Couldn't parse criteria in {}", file.display()))?;
+
+            for (name, crit_yaml) in criteria_yaml.criteria {
+                let crit = crit_yaml.into_criterion_with_template(name, &failure_template);
+                if rubric.criteria.iter().any(|c| c.func == crit.func) {
+                    return Err(anyhow!(
+                        "Criterion func `{}` in {} is already defined in another file",
+                        crit.func,
+                        file.display()
+                    ));
+                }
+                rubric.add(crit);
+            }
+        }
+        rubric.total = criteria_total(&rubric.criteria);
+
+        Ok(rubric)
+    }
+
+    /// Compares this rubric (the old version) to another (the new version).
+    ///
+    /// Criteria are matched by `func`. The attached tests can't be compared,
+    /// so only the worth and messages are checked for changes. This is meant
+    /// to help you figure out which submissions need to be regraded after
+    /// changing a rubric.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, yaml};
+    /// # let yaml = yaml!("../../test_data/test_rubric.yml").unwrap();
+    /// let old = Rubric::from_yaml(yaml).unwrap();
+    /// let mut new = Rubric::from_yaml(yaml).unwrap();
+    /// new.get_mut("first_crit").unwrap().worth = 40;
+    ///
+    /// let diff = old.diff(&new);
+    /// assert_eq!(diff.modified[0].worth, Some((50, 40)));
+    /// ```
+    pub fn diff(&self, other: &Rubric) -> RubricDiff {
+        let mut diff = RubricDiff {
+            added: Vec::new(),
+            removed: Vec::new(),
+            modified: Vec::new(),
+        };
+
+        for old in &self.criteria {
+            let new = match other.criteria.iter().find(|c| c.func == old.func) {
+                Some(new) => new,
+                None => {
+                    diff.removed.push(old.func.clone());
+                    continue;
+                }
+            };
+
+            let change = CriterionChange {
+                func: old.func.clone(),
+                worth: if old.worth != new.worth { Some((old.worth, new.worth)) } else { None },
+                messages: if old.messages != new.messages {
+                    Some((old.messages.clone(), new.messages.clone()))
+                } else {
+                    None
+                },
+            };
+            if change.worth.is_some() || change.messages.is_some() {
+                diff.modified.push(change);
+            }
+        }
+
+        for new in &other.criteria {
+            if !self.criteria.iter().any(|c| c.func == new.func) {
+                diff.added.push(new.func.clone());
+            }
+        }
+
+        diff
+    }
+
     /// Searches for a criterion with the given func,
     /// returning None if it couldn't be found
     ///
     /// ```rust
     /// # use rubric::{Rubric, yaml};
     /// # let yaml = yaml!("../../test_data/test_rubric.yml").expect("Couldn't load yaml");
-    /// # let mut rubric = Rubric::from_yaml(yaml).expect("Bad yaml!");
+    /// # let rubric = Rubric::from_yaml(yaml).expect("Bad yaml!");
     /// // `rubric` contains a criterion with the func 'first_crit`
     /// let criterion = rubric.get("first_crit");
     /// assert!(criterion.is_some());
     /// let not_criterion = rubric.get("doesnt-exist");
     /// assert!(not_criterion.is_none());
     /// ```
-    pub fn get(&mut self, func: &str) -> Option<&mut Criterion> {
-        self.criteria.iter_mut().find(|c| c.func == func)
+    pub fn get(&self, func: &str) -> Option<&Criterion> {
+        self.position(func).map(|i| &self.criteria[i])
+    }
+
+    /// Same as [`get`](crate::rubric::Rubric::get), but the criterion is mutable.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, yaml};
+    /// # let yaml = yaml!("../../test_data/test_rubric.yml").expect("Couldn't load yaml");
+    /// # let mut rubric = Rubric::from_yaml(yaml).expect("Bad yaml!");
+    /// rubric.get_mut("first_crit").unwrap().worth = 40;
+    /// assert_eq!(rubric.get("first_crit").unwrap().worth, 40);
+    /// ```
+    pub fn get_mut(&mut self, func: &str) -> Option<&mut Criterion> {
+        self.position(func).map(move |i| &mut self.criteria[i])
+    }
+
+    // Where the criterion with this func is in `criteria`. This doesn't search
+    // unless the criteria were changed since the last lookup, or it isn't there.
+    fn position(&self, func: &str) -> Option<usize> {
+        let cached = self.positions.borrow().get(func).copied();
+        if let Some(i) = cached {
+            if matches!(self.criteria.get(i), Some(c) if c.func == func) {
+                return Some(i);
+            }
+        }
+
+        let mut positions = self.positions.borrow_mut();
+        positions.clear();
+        for (i, crit) in self.criteria.iter().enumerate() {
+            // The first criterion with a func is the one that's found
+            positions.entry(crit.func.clone()).or_insert(i);
+        }
+        positions.get(func).copied()
+    }
+
+    /// Same as [`get`](crate::rubric::Rubric::get), but returns an `Err` if the
+    /// criterion couldn't be found. Use this when the criterion should always exist.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, yaml};
+    /// # let yaml = yaml!("../../test_data/test_rubric.yml").expect("Couldn't load yaml");
+    /// # let rubric = Rubric::from_yaml(yaml).expect("Bad yaml!");
+    /// let criterion = rubric.get_or_err("first_crit").unwrap();
+    /// assert_eq!(criterion.func, "first_crit");
+    /// assert!(rubric.get_or_err("doesnt-exist").is_err());
+    /// ```
+    pub fn get_or_err(&self, func: &str) -> Result<&Criterion> {
+        self.get(func)
+            .ok_or_else(|| anyhow!("No criterion with func `{}` in the rubric", func))
     }
 
     /// Adds a criterion to the rubric.
@@ -135,19 +485,291 @@ impl Rubric {
     /// You probably shouldn't use this, instead define all
     /// your criteria in yaml.
     pub fn add(&mut self, criterion: Criterion) {
+        self.positions.get_mut()
+            .entry(criterion.func.clone())
+            .or_insert(self.criteria.len());
         self.criteria.push(criterion);
     }
 
+    /// Attaches plain functions to criteria, by the criteria's `func`.
+    ///
+    /// Every func is checked before anything is attached. If any of them aren't
+    /// in the rubric, this returns an `Err` naming all of them and nothing is attached.
+    /// The [`attach_funcs!`](../macro.attach_funcs.html) macro is an easier way to call this.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, TestData, yaml};
+    /// # let mut rubric = Rubric::from_yaml(yaml!("../../test_data/test_rubric.yml").unwrap()).unwrap();
+    /// fn always(_: &TestData) -> bool { true }
+    ///
+    /// rubric.attach_funcs(&[("first_crit", always), ("second_crit", always)]).unwrap();
+    /// assert!(rubric.attach_funcs(&[("typo", always)]).is_err());
+    /// ```
+    pub fn attach_funcs(&mut self, funcs: &[(&str, TestFn)]) -> Result<()> {
+        let missing: Vec<String> = funcs.iter()
+            .filter(|(func, _)| self.get(func).is_none())
+            .map(|(func, _)| format!("`{}`", func))
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!("No criterion with func {} in the rubric, nothing was attached", missing.join(", ")));
+        }
+
+        for (func, test) in funcs {
+            if let Some(crit) = self.get_mut(func) {
+                crit.attach(Box::new(*test));
+            }
+        }
+        Ok(())
+    }
+
+    /// Resets every criterion so it's like the rubric was never graded.
+    ///
+    /// Do this between grading submissions with the same rubric.
+    pub fn reset(&mut self) {
+        for crit in &mut self.criteria {
+            crit.reset();
+        }
+    }
+
     /// Returns the criteria as a `&mut Vec<Criterion>`, sorted
     /// by the criterion's index.
     ///
-    /// Criteria with the same index/no index will not have guaranteed order.
+    /// Criteria with the same index (or no index) are sorted by their `func`,
+    /// then their name, alphabetically. The yaml map doesn't keep criteria in
+    /// order, so this is what keeps reports the same from run to run.
+    ///
+    /// ```rust
+    /// # use rubric::Rubric;
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Second").func("b_crit").index(1).build());
+    /// rubric.add(Criterion::new("First").func("a_crit").index(1).build());
+    ///
+    /// assert_eq!(rubric.sorted()[0].name, "First");
+    /// ```
     pub fn sorted(&mut self) -> &mut Vec<Criterion> {
         let sorted = &mut self.criteria;
-        sorted.sort_by(|a, b| a.index.cmp(&b.index));
+        sorted.sort_by(grading_order);
         sorted
     }
 
+    /// The names of the criteria, in the order they're graded.
+    ///
+    /// This is the same order as [`sorted`](crate::rubric::Rubric::sorted),
+    /// but doesn't need `&mut self`.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, yaml};
+    /// # let yaml = yaml!("../../test_data/test_rubric.yml").unwrap();
+    /// let rubric = Rubric::from_yaml(yaml).unwrap();
+    /// assert_eq!(rubric.names(), vec!["First Criterion", "Second Criterion"]);
+    /// ```
+    pub fn names(&self) -> Vec<&str> {
+        self.in_grading_order().iter().map(|c| c.name.as_str()).collect()
+    }
+
+    /// The groups the criteria are in, each one once, in the order they're first
+    /// graded. Criteria without a group are left out.
+    ///
+    /// ```rust
+    /// # use rubric::Rubric;
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Git installed").index(1).group("Setup").build());
+    /// rubric.add(Criterion::new("Tests pass").index(3).group("Code").build());
+    /// rubric.add(Criterion::new("Repo cloned").index(2).group("Setup").build());
+    /// rubric.add(Criterion::new("Extra credit").index(4).build());
+    ///
+    /// assert_eq!(rubric.groups(), vec!["Setup", "Code"]);
+    /// ```
+    pub fn groups(&self) -> Vec<String> {
+        let mut groups: Vec<String> = Vec::new();
+        for group in self.in_grading_order().iter().filter_map(|c| c.group()) {
+            if !groups.iter().any(|g| g == group) {
+                groups.push(group.to_string());
+            }
+        }
+        groups
+    }
+
+    /// The funcs of the criteria, in the order they're graded.
+    ///
+    /// See [`names`](crate::rubric::Rubric::names).
+    pub fn funcs(&self) -> Vec<&str> {
+        self.in_grading_order().iter().map(|c| c.func.as_str()).collect()
+    }
+
+    /// The rubric's structure as JSON, for showing it somewhere else (like a
+    /// web page) before anything is graded.
+    ///
+    /// The criteria are in the order they're graded. Hidden criteria are
+    /// included with `"hide": true`, so leave those out if students will see it.
+    /// There's nothing from grading in here, and nothing is changed.
+    ///
+    /// A criterion's `worth` is a whole number, unless it was given a
+    /// fraction (see [`Criterion::worth_f64`](crate::rubric::Criterion::worth_f64)),
+    /// then it's that, like `2.5`. The totals are always whole numbers.
+    ///
+    /// ```rust
+    /// # use rubric::Rubric;
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.name = String::from("Lab 1");
+    /// rubric.add(Criterion::new("Compiles").worth(10).tag("build").build());
+    ///
+    /// let json = rubric.to_summary_json();
+    /// assert_eq!(json["name"], "Lab 1");
+    /// assert_eq!(json["total"], 10);
+    /// assert_eq!(json["criteria"][0]["func"], "compiles");
+    /// assert_eq!(json["criteria"][0]["tags"][0], "build");
+    /// ```
+    pub fn to_summary_json(&self) -> Value {
+        let criteria: Vec<Value> = self.in_grading_order().iter().map(|c| json!({
+            "func": c.func,
+            "name": c.name,
+            "worth": c.worth_json(),
+            "desc": c.desc,
+            "messages": [c.success_message(), c.failure_message()],
+            "hide": c.hide,
+            "tags": c.tags,
+            "group": c.group,
+            "required": c.required,
+            "bonus": c.bonus,
+            "penalty": c.penalty,
+        })).collect();
+
+        json!({
+            "name": self.name,
+            "desc": self.desc,
+            "version": self.version,
+            "total": self.total_points(),
+            "bonus": self.bonus_total(),
+            "criteria": criteria,
+        })
+    }
+
+    /// A SHA-256 hash of the criteria's definitions, as a hex string.
+    ///
+    /// Pin this in a test to catch changes to a shared rubric. It changes if a
+    /// criterion is added, removed, renamed, reordered, or has its worth, weight,
+    /// messages, retries, named failures, required data, cost, or anything else
+    /// from the yaml changed. Tests and anything from
+    /// grading aren't part of it, and neither are the rubric's own fields, like its name.
+    ///
+    /// It's the same for the same yaml no matter what order the criteria were loaded in.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, yaml};
+    /// let yaml = yaml!("../../test_data/test_rubric.yml").unwrap();
+    /// let mut rubric = Rubric::from_yaml(yaml).unwrap();
+    /// let pinned = rubric.fingerprint();
+    /// assert_eq!(Rubric::from_yaml(yaml).unwrap().fingerprint(), pinned);
+    ///
+    /// rubric.get_mut("first_crit").unwrap().worth += 1;
+    /// assert_ne!(rubric.fingerprint(), pinned);
+    /// ```
+    pub fn fingerprint(&self) -> String {
+        let mut criteria: Vec<&Criterion> = self.criteria.iter().collect();
+        criteria.sort_by(|a, b| grading_order(a, b));
+
+        let definitions: Vec<Value> = criteria.iter().map(|c| {
+            let mut definition = json!({
+                "name": c.name,
+                "func": c.func,
+                "index": c.index,
+                "worth": c.worth_json(),
+                "weight": c.weight,
+                "desc": c.desc,
+                "messages": [&c.messages.0, &c.messages.1],
+                "hide": c.hide,
+                "tags": c.tags,
+                "required": c.required,
+                "bonus": c.bonus,
+                "penalty": c.penalty,
+            });
+            // Only when they're set, so rubrics without them keep their fingerprint
+            if let Some(group) = &c.group {
+                definition["group"] = json!(group);
+            }
+            if c.retries != 0 {
+                definition["retries"] = json!(c.retries);
+            }
+            if !c.named_failures.is_empty() {
+                definition["named_failures"] = json!(c.named_failures);
+            }
+            if !c.required_data.is_empty() {
+                definition["required_data"] = json!(c.required_data);
+            }
+            if let Some(cost) = c.cost {
+                definition["cost"] = json!(cost);
+            }
+            definition
+        }).collect();
+
+        // Object keys are always serialized in sorted order
+        let mut hasher = Sha256::new();
+        hasher.update(Value::Array(definitions).to_string());
+        let mut hex = String::new();
+        for byte in hasher.finalize() {
+            hex.push_str(&format!("{:02x}", byte));
+        }
+        hex
+    }
+
+    // The criteria in the order `sorted()` would leave them in
+    pub(crate) fn in_grading_order(&self) -> Vec<&Criterion> {
+        let mut criteria: Vec<&Criterion> = self.criteria.iter().collect();
+        criteria.sort_by(|a, b| grading_order(a, b));
+        criteria
+    }
+
+    /// Sorts the criteria by a key you provide. This is like
+    /// [`sorted`](crate::rubric::Rubric::sorted), but you choose the order.
+    ///
+    /// The sort is stable, so criteria with the same key keep their order.
+    ///
+    /// ```rust
+    /// # use rubric::Rubric;
+    /// # use rubric::rubric::Criterion;
+    /// use std::cmp::Reverse;
+    ///
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("small").worth(5).build());
+    /// rubric.add(Criterion::new("big").worth(50).build());
+    ///
+    /// // Highest worth first
+    /// rubric.sort_by_key(|c| Reverse(c.worth));
+    /// assert_eq!(rubric[0].name, "big");
+    /// ```
+    pub fn sort_by_key<K: Ord, F: FnMut(&Criterion) -> K>(&mut self, key: F) {
+        self.criteria.sort_by_key(key);
+    }
+
+    /// Returns the criteria that passed, but not on the first try, the last
+    /// time they were tested. These are the ones that need
+    /// [`retries`](crate::rubric::CriterionBuilder::retries) to pass, and are
+    /// worth looking at to make them more reliable.
+    pub fn flaky(&self) -> Vec<&Criterion> {
+        self.criteria.iter()
+            .filter(|crit| crit.status == Some(true) && crit.attempts > 1)
+            .collect()
+    }
+
+    /// Returns the failed criterion with the highest worth, if any
+    /// criteria failed.
+    ///
+    /// This is useful for telling a student where most of their
+    /// missing points are. If two failed criteria have the same worth,
+    /// the first one is returned.
+    pub fn highest_worth_failed(&self) -> Option<&Criterion> {
+        self.criteria.iter()
+            .filter(|c| c.status == Some(false))
+            .fold(None, |best: Option<&Criterion>, c| match best {
+                Some(b) if b.worth >= c.worth => Some(b),
+                _ => Some(c)
+            })
+    }
+
     /// The total points earned after grading.
     ///
     /// Each criterion stores a flag that determines if
@@ -156,28 +778,172 @@ impl Rubric {
     ///
     /// If you run this before grading, it should return 0. If it
     /// doesn't, call me lmao.
+    ///
+    /// Criteria with a `weight` add their
+    /// [`weighted_worth`](crate::rubric::Criterion::weighted_worth).
+    ///
+    /// Passed penalties (criteria with a negative worth) are subtracted,
+    /// but this can't go below zero. Use
+    /// [`points_earned`](crate::rubric::Rubric::points_earned) if you need it to.
     pub fn points(&self) -> usize {
-        let mut total: usize = 0;
-        for crit in &self.criteria {
-            if let Some(status) = crit.status {
-                if status {
-                    // Only add to the total if they've graded
-                    // and this criterion passed
-                    total += crit.worth as usize;
-                }
-            }
-        }
-        total
+        self.points_earned().max(0) as usize
+    }
+
+    /// The points earned after grading, including penalties.
+    ///
+    /// This is the sum of the [`weighted_worth`](crate::rubric::Criterion::weighted_worth)
+    /// of every criterion that passed. Unlike [`points`](crate::rubric::Rubric::points),
+    /// a passed criterion with a negative worth subtracts from the total. It's the
+    /// counterpart to [`total_points`](crate::rubric::Rubric::total_points), and is handy
+    /// for subtotals of a few criteria without going through a submission.
+    ///
+    /// Criteria that haven't been tested, failed, or were skipped don't count.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, TestData};
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Works").worth(10).test(Box::new(|_: &TestData| true)).build());
+    /// assert_eq!(rubric.points_earned(), 0);
+    ///
+    /// rubric.criteria[0].test();
+    /// assert_eq!(rubric.points_earned(), 10);
+    /// ```
+    pub fn points_earned(&self) -> isize {
+        sum_worth(self.criteria.iter().filter(|crit| crit.status == Some(true)))
     }
 
     /// Returns the total worth of all criteria, ie. the
-    /// maximum number of points possible.
+    /// maximum number of points possible. This is the grade's denominator.
+    ///
+    /// This is the [`required_total`](crate::rubric::Rubric::required_total) plus
+    /// any penalties (criteria with a negative worth). Bonus criteria aren't counted,
+    /// see [`total_with_bonus`](crate::rubric::Rubric::total_with_bonus).
+    ///
+    /// Criteria that were skipped during grading (see
+    /// [`CriterionBuilder::when`](crate::rubric::CriterionBuilder::when)) don't count.
+    /// Criteria with a `weight` count their
+    /// [`weighted_worth`](crate::rubric::Criterion::weighted_worth).
+    ///
+    /// Fractional worths (see [`Criterion::worth_f64`](crate::rubric::Criterion::worth_f64))
+    /// are added up before rounding, so two criteria worth `2.5` make `5`.
     pub fn total_points(&self) -> isize {
-        let mut total: isize = 0;
-        for crit in &self.criteria {
-            total += crit.worth as isize;
+        sum_worth(self.criteria.iter().filter(|crit| !crit.skipped && !crit.bonus))
+    }
+
+    /// The total worth of the criteria that aren't bonus and aren't
+    /// penalties, ie. everything with a positive worth that a submission
+    /// is expected to pass.
+    ///
+    /// Like [`total_points`](crate::rubric::Rubric::total_points), skipped
+    /// criteria don't count and weights are applied.
+    ///
+    /// ```rust
+    /// # use rubric::Rubric;
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Compiles").worth(10).build());
+    /// rubric.add(Criterion::new("Extra credit").worth(5).bonus(true).build());
+    /// rubric.add(Criterion::new("Has warnings").worth(-2).build());
+    ///
+    /// assert_eq!(rubric.required_total(), 10);
+    /// assert_eq!(rubric.bonus_total(), 5);
+    /// assert_eq!(rubric.total_with_bonus(), 15);
+    /// assert_eq!(rubric.total_points(), 8);
+    /// ```
+    pub fn required_total(&self) -> isize {
+        sum_worth(self.criteria.iter()
+            .filter(|crit| !crit.skipped && !crit.bonus)
+            .filter(|crit| crit.exact_worth() > 0.0))
+    }
+
+    /// The total worth of the bonus criteria. These add points when they
+    /// pass, but aren't part of [`total_points`](crate::rubric::Rubric::total_points).
+    ///
+    /// Skipped criteria don't count and weights are applied.
+    pub fn bonus_total(&self) -> isize {
+        sum_worth(self.criteria.iter()
+            .filter(|crit| !crit.skipped && crit.bonus)
+            .filter(|crit| crit.exact_worth() > 0.0))
+    }
+
+    /// The most points a submission can earn: the
+    /// [`required_total`](crate::rubric::Rubric::required_total) plus the
+    /// [`bonus_total`](crate::rubric::Rubric::bonus_total). Penalties aren't counted.
+    pub fn total_with_bonus(&self) -> isize {
+        self.required_total() + self.bonus_total()
+    }
+
+    /// Same as [`total_points`](crate::rubric::Rubric::total_points), but
+    /// as an `f64`. Use this for any math that needs floating point.
+    pub fn total_points_f64(&self) -> f64 {
+        self.total_points() as f64
+    }
+
+    /// The points earned as a percentage of the total points, from `0.0` to `100.0`.
+    /// Bonus points can take it over `100.0`.
+    ///
+    /// If the rubric has a total of zero (no criteria, or criteria that are
+    /// all worth 0), this returns `0.0` instead of `NaN`.
+    ///
+    /// ```rust
+    /// # use rubric::Rubric;
+    /// let rubric = Rubric::default();
+    /// assert_eq!(rubric.grade_percentage(), 0.0);
+    /// ```
+    pub fn grade_percentage(&self) -> f64 {
+        let total = self.total_points_f64();
+        if total == 0.0 {
+            return 0.0;
         }
-        total
+        self.points() as f64 / total * 100.0
+    }
+
+    /// Derives a seed for one student from the rubric's `seed` and
+    /// something that identifies them, like their student id.
+    ///
+    /// The same seed and id always give the same result, and different
+    /// ids give different seeds. Returns `None` if the rubric has no seed.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, data};
+    /// # use rubric::rubric::SEED_KEY;
+    /// let mut rubric = Rubric::default();
+    /// rubric.seed = Some(42);
+    ///
+    /// let mut sub = Submission::from_data(data! { "id" => "1234" });
+    /// let seed = rubric.seed_for(&sub.data["id"]).unwrap();
+    /// sub.data.insert(SEED_KEY.to_string(), seed.to_string());
+    ///
+    /// // The submission's own seed is kept when grading
+    /// sub.grade_against(&mut rubric);
+    /// assert_eq!(sub.data[SEED_KEY], seed.to_string());
+    /// ```
+    pub fn seed_for(&self, id: &str) -> Option<u64> {
+        self.seed.map(|seed| seeded_hash(seed, id))
+    }
+
+    /// Returns the criteria in a shuffled order, for showing to a student.
+    ///
+    /// The same seed always gives the same order, so each student can see
+    /// their own order by using a seed from [`seed_for`](crate::rubric::Rubric::seed_for).
+    /// This is only for display. Grading still goes by index, and the grade
+    /// is the same no matter the order.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, yaml};
+    /// let mut rubric = Rubric::from_yaml(yaml!("../../test_data/test_rubric.yml").unwrap()).unwrap();
+    /// rubric.seed = Some(42);
+    ///
+    /// let seed = rubric.seed_for("1234").unwrap();
+    /// for crit in rubric.display_order(seed) {
+    ///     println!("{}", crit);
+    /// }
+    /// ```
+    pub fn display_order(&self, seed: u64) -> Vec<&Criterion> {
+        let mut criteria: Vec<&Criterion> = self.criteria.iter().collect();
+        criteria.sort_by_cached_key(|crit| (seeded_hash(seed, &crit.func), crit.index));
+        criteria
     }
 
     /// Returns a reference to a `Vec` of the criteria. This
@@ -187,29 +953,483 @@ impl Rubric {
         &self.criteria
     }
 
+    /// Returns a mutable reference to the criteria, in the order they were added.
+    ///
+    /// This is useful for changing criteria after loading a rubric from yaml,
+    /// like attaching tests or adding criteria made in code. The rubric's
+    /// `total` isn't updated.
+    pub fn criteria_mut(&mut self) -> &mut Vec<Criterion> {
+        &mut self.criteria
+    }
+
     /// Returns the amount of criteria in the rubric
     pub fn len(&self) -> usize {
         self.criteria.len()
     }
 
     pub fn past_due(&self) -> bool {
+        self.past_due_at(Local::now())
+    }
+
+    // If something done at `time` is past the deadline
+    pub(crate) fn past_due_at(&self, time: DateTime<Local>) -> bool {
         if let Some(deadline) = self.deadline {
-            return deadline.timestamp() < Local::now().timestamp();
+            return deadline.timestamp() < time.timestamp();
         }
         false
     }
 
-    pub fn past_final_deadline(&self) -> bool {
-        if let Some(final_deadline) = self.final_deadline {
-            return final_deadline.timestamp() < Local::now().timestamp();
+    /// Grades a submission, but stops when the time budget runs out.
+    ///
+    /// This is like [`Submission::grade_against`](crate::dropbox::submission::Submission::grade_against),
+    /// but before each criterion is tested, it checks how long grading has taken so far.
+    /// Once that's over the budget, the rest of the criteria aren't run. They're skipped
+    /// because they were `"not run (time budget exceeded)"`, so they aren't passed or failed.
+    /// Their results in the submission say the same, with `not_run` set, and they're
+    /// listed in the returned [`BudgetedRun`](crate::rubric::BudgetedRun).
+    ///
+    /// A criterion that's already running isn't stopped, so grading can take a bit longer
+    /// than the budget. Use this when you'd rather have partial results than none, like
+    /// in CI with a hard timeout.
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use rubric::{Rubric, Submission, TestData};
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Quick").worth(10).test(Box::new(|_: &TestData| true)).build());
+    ///
+    /// let mut sub = Submission::new();
+    /// let run = rubric.grade_within(&mut sub, Duration::from_secs(10));
+    /// assert!(!run.truncated());
+    /// assert_eq!(sub.grade, 10);
+    /// ```
+    pub fn grade_within(&mut self, submission: &mut Submission, budget: Duration) -> BudgetedRun {
+        BudgetedRun {
+            not_run: submission.grade_with(self, Local::now(), Some(budget), None, None, None),
         }
-        false
     }
 
-}
+    /// Grades a submission, reusing results from earlier runs with the same data.
+    ///
+    /// This is like [`Submission::grade_against`](crate::dropbox::submission::Submission::grade_against),
+    /// but each criterion's result is looked up in the cache first, by its `func` and the
+    /// submission's [`data_hash`](crate::dropbox::submission::Submission::data_hash) from
+    /// before grading. If it's there, the test isn't run. Otherwise the test is run and
+    /// the result is cached. This is handy while writing a rubric, when you grade the same
+    /// submission over and over and some criteria are slow.
+    ///
+    /// Since a cached criterion isn't run, nothing it would do happens. It has no
+    /// `command_result` or `explanation`, and a command criterion's output isn't added
+    /// to the submission's data.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, TestData, data};
+    /// # use rubric::rubric::{Criterion, ResultCache};
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Slow").worth(10).test(Box::new(|_: &TestData| true)).build());
+    /// let mut cache = ResultCache::new();
+    ///
+    /// let mut sub = Submission::from_data(data! { "name" => "luke" });
+    /// rubric.grade_cached(&mut sub, &mut cache);
+    /// assert_eq!(cache.len(), 1);
+    /// ```
+    pub fn grade_cached(&mut self, submission: &mut Submission, cache: &mut ResultCache) {
+        submission.grade_with(self, Local::now(), None, Some(cache), None, None);
+    }
 
+    /// Grades a submission, calling `on_progress` before each criterion is tested.
+    ///
+    /// This is like [`Submission::grade_against`](crate::dropbox::submission::Submission::grade_against),
+    /// and grades the same way. The callback gets the criterion's position
+    /// (starting at 0), the number of criteria, and the criterion, in the order
+    /// they're graded. It's called for every criterion, even ones that end up
+    /// skipped. It isn't called at all if the submission is past a deadline that
+    /// stops grading.
+    ///
+    /// Use it to show progress when there are a lot of slow criteria.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, TestData};
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Site responds").worth(10).test(Box::new(|_: &TestData| true)).build());
+    ///
+    /// let mut sub = Submission::new();
+    /// rubric.grade_with_progress(&mut sub, |i, total, crit| {
+    ///     eprintln!("[{}/{}] {}", i + 1, total, crit.name);
+    /// });
+    /// assert_eq!(sub.grade, 10);
+    /// ```
+    pub fn grade_with_progress<F>(&mut self, submission: &mut Submission, mut on_progress: F)
+    where
+        F: FnMut(usize, usize, &Criterion)
+    {
+        submission.grade_with(self, Local::now(), None, None, Some(&mut on_progress), None);
+    }
 
-impl FromStr for Rubric {
+    /// Grades a submission and returns a report of how it went.
+    ///
+    /// The submission is graded like
+    /// [`Submission::grade_against`](crate::dropbox::submission::Submission::grade_against).
+    /// The report can be printed, or serialized to JSON. See
+    /// [`GradeReport`](crate::report::GradeReport).
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, TestData};
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Compiles").worth(10).test(Box::new(|_: &TestData| true)).build());
+    ///
+    /// let mut sub = Submission::new();
+    /// let report = rubric.grade_and_report(&mut sub);
+    /// assert_eq!(report.grade, 10);
+    /// assert_eq!(report.percentage, 100.0);
+    /// println!("{}", report);
+    /// ```
+    pub fn grade_and_report(&mut self, submission: &mut Submission) -> GradeReport {
+        submission.grade_against(self);
+        GradeReport::new(self, submission)
+    }
+
+    /// Grades a submission, but only if every criterion has a test.
+    ///
+    /// A criterion with nothing attached always fails, so forgetting to attach
+    /// a test would fail everyone. This checks first and returns an `Err` naming
+    /// the criteria without a test, and the submission isn't graded. Otherwise
+    /// it's the same as [`Submission::grade_against`](crate::dropbox::submission::Submission::grade_against).
+    ///
+    /// See [`Criterion::has_test`](crate::rubric::Criterion::has_test) for what counts as a test.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, TestData};
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Forgotten").worth(10).build());
+    ///
+    /// let mut sub = Submission::new();
+    /// assert!(rubric.strict_grade(&mut sub).is_err());
+    /// ```
+    pub fn strict_grade(&mut self, submission: &mut Submission) -> Result<()> {
+        let unattached = self.unattached();
+        if !unattached.is_empty() {
+            return Err(anyhow!(
+                "Not grading, these criteria don't have a test: {}",
+                unattached.join(", ")
+            ));
+        }
+
+        submission.grade_against(self);
+        Ok(())
+    }
+
+    // The criteria without a test, like "Compiles (compiles)"
+    pub(crate) fn unattached(&self) -> Vec<String> {
+        self.criteria.iter()
+            .filter(|crit| !crit.has_test())
+            .map(|crit| format!("{} ({})", crit.name, crit.func))
+            .collect()
+    }
+
+    /// Grades stored submissions again, like after fixing a criterion.
+    ///
+    /// Each submission is graded from its `data`, and its grade and results are
+    /// replaced, the same as [`Submission::grade_against`](crate::dropbox::submission::Submission::grade_against).
+    /// Lateness is judged by when the submission was made (its `time`), not when
+    /// it's regraded. Grade overrides are cleared, since they were for the old grade.
+    ///
+    /// Returns what changed for each submission, in the same order.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, TestData, data};
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Has name").worth(10).build());
+    ///
+    /// // The test was forgotten, so this fails
+    /// let mut subs = vec![Submission::from_data(data! { "name" => "luke" })];
+    /// subs[0].grade_against(&mut rubric);
+    /// assert_eq!(subs[0].grade, 0);
+    ///
+    /// rubric.get_mut("has_name").unwrap()
+    ///     .attach(Box::new(|data: &TestData| data.contains_key("name")));
+    /// let diffs = rubric.regrade(&mut subs);
+    /// assert_eq!(subs[0].grade, 10);
+    /// assert_eq!(diffs[0].grade_delta, 10);
+    /// ```
+    pub fn regrade(&mut self, submissions: &mut [Submission]) -> Vec<SubmissionDiff> {
+        submissions.iter_mut()
+            .map(|sub| {
+                let mut previous = Submission::new();
+                previous.grade = sub.grade;
+                previous.passed = sub.passed.clone();
+                previous.failed = sub.failed.clone();
+
+                self.reset();
+                sub.grade_with(self, sub.time, None, None, None, None);
+                sub.diff(&previous)
+            })
+            .collect()
+    }
+
+    /// Finishes grading a submission that was only partly graded, like after
+    /// a long grading run was interrupted.
+    ///
+    /// The submission's [`results`](crate::dropbox::submission::Submission::results)
+    /// say which criteria are done. Those keep their result and their tests aren't run
+    /// again, the rest are graded like normal. The grade is worked out again from all
+    /// of them. Lateness is judged by the submission's `time`, like
+    /// [`regrade`](crate::rubric::Rubric::regrade).
+    ///
+    /// Submissions serialize with their results, so save them and read them back
+    /// to pick up where you left off. To save each result as it's graded, use
+    /// [`resume_grade_with`](crate::rubric::Rubric::resume_grade_with).
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, TestData};
+    /// # use rubric::rubric::Criterion;
+    /// let mut first_half = Rubric::default();
+    /// first_half.add(Criterion::new("Setup").worth(10).test(Box::new(|_: &TestData| true)).build());
+    /// let mut sub = Submission::new();
+    /// sub.grade_against(&mut first_half);
+    /// let saved = serde_json::to_string(&sub).unwrap();
+    ///
+    /// let mut rubric = Rubric::default();
+    /// // Already done, so this isn't run again
+    /// rubric.add(Criterion::new("Setup").worth(10).test(Box::new(|_: &TestData| false)).build());
+    /// rubric.add(Criterion::new("Tests").worth(20).test(Box::new(|_: &TestData| true)).build());
+    ///
+    /// let mut sub: Submission = saved.parse().unwrap();
+    /// rubric.resume_grade(&mut sub);
+    /// assert_eq!(sub.grade, 30);
+    /// ```
+    pub fn resume_grade(&mut self, submission: &mut Submission) {
+        self.resume(submission, None);
+    }
+
+    /// Same as [`resume_grade`](crate::rubric::Rubric::resume_grade), but calls
+    /// `on_result` with each criterion's result as soon as it's graded.
+    ///
+    /// Saving the submission only when it's done loses everything if the run is
+    /// interrupted partway through. Save each result from `on_result` instead, and
+    /// after an interruption put the saved results in the submission's `results`
+    /// and resume. A submission without results is graded from the start, so use
+    /// this for the first run too.
+    ///
+    /// `on_result` is called for criteria that were already done as well, in the
+    /// order they're graded. Skipped criteria don't have a result.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission, TestData};
+    /// # use rubric::rubric::Criterion;
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Setup").worth(10).test(Box::new(|_: &TestData| true)).build());
+    ///
+    /// let mut saved = Vec::new();
+    /// let mut sub = Submission::new();
+    /// rubric.resume_grade_with(&mut sub, |result| saved.push(serde_json::to_string(result).unwrap()));
+    /// assert_eq!(saved.len(), 1);
+    /// ```
+    pub fn resume_grade_with<F>(&mut self, submission: &mut Submission, mut on_result: F)
+    where
+        F: FnMut(&CriterionResult)
+    {
+        self.resume(submission, Some(&mut on_result));
+    }
+
+    fn resume(&mut self, submission: &mut Submission, on_result: Option<&mut dyn FnMut(&CriterionResult)>) {
+        // Grading adds the seed before looking anything up, so
+        // the results have to be keyed on the data with it
+        if let Some(seed) = self.seed {
+            submission.data.entry(SEED_KEY.to_string()).or_insert_with(|| seed.to_string());
+        }
+
+        let hash = submission.data_hash();
+        let mut done = ResultCache::new();
+        // Criteria the time budget left out still need to be run
+        for result in submission.results.iter().filter(|r| !r.not_run) {
+            done.insert(&result.func, &hash, result.passed);
+        }
+
+        self.reset();
+        submission.grade_with(self, submission.time, None, Some(&mut done), None, on_result);
+    }
+
+    /// Returns the checkpoint with the given name, if there is one.
+    pub fn checkpoint(&self, name: &str) -> Option<&Checkpoint> {
+        self.checkpoints.iter().find(|c| c.name == name)
+    }
+
+    /// Grades a submission against only the criteria in one checkpoint.
+    ///
+    /// This works just like [`Submission::grade_against`](crate::dropbox::submission::Submission::grade_against),
+    /// but the criteria that aren't in the checkpoint are left out, and the checkpoint's
+    /// deadline is used instead of the rubric's. A checkpoint without a deadline falls
+    /// back to the rubric's. The late penalties, `allow_late`, and `final_deadline`
+    /// still come from the rubric.
+    ///
+    /// Returns an `Err` if there's no checkpoint with that name, or if it lists a
+    /// criterion that isn't in the rubric.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, Submission};
+    /// let yaml = r#"
+    ///     name: Project
+    ///     checkpoints:
+    ///         Week 1:
+    ///             deadline: 2099-01-01 23:59:59
+    ///             criteria: [setup]
+    ///     criteria:
+    ///         Setup:
+    ///             func: setup
+    ///             worth: 10
+    ///         Finished:
+    ///             func: finished
+    ///             worth: 90
+    /// "#;
+    /// let mut rubric = Rubric::from_yaml(yaml).unwrap();
+    /// let mut sub = Submission::new();
+    ///
+    /// rubric.grade_checkpoint("Week 1", &mut sub).unwrap();
+    /// assert_eq!(sub.failed.len(), 1);
+    /// assert!(rubric.grade_checkpoint("Week 2", &mut sub).is_err());
+    /// ```
+    pub fn grade_checkpoint(&mut self, name: &str, submission: &mut Submission) -> Result<()> {
+        let checkpoint = self.checkpoint(name)
+            .ok_or_else(|| anyhow!("No checkpoint named `{}` in the rubric", name))?
+            .clone();
+
+        for func in &checkpoint.criteria {
+            if !self.criteria.iter().any(|c| &c.func == func) {
+                return Err(anyhow!("Checkpoint `{}` has criterion `{}`, which isn't in the rubric", name, func));
+            }
+        }
+
+        // Only leave the checkpoint's criteria while grading
+        let (active, inactive): (Vec<Criterion>, Vec<Criterion>) = mem::take(&mut self.criteria)
+            .into_iter()
+            .partition(|c| checkpoint.criteria.contains(&c.func));
+        self.criteria = active;
+        let checkpoint_deadline = checkpoint.deadline.or(self.deadline);
+        let deadline = mem::replace(&mut self.deadline, checkpoint_deadline);
+
+        submission.grade_against(self);
+
+        self.deadline = deadline;
+        self.criteria.extend(inactive);
+        self.sorted();
+        Ok(())
+    }
+
+    pub fn past_final_deadline(&self) -> bool {
+        self.past_final_deadline_at(Local::now())
+    }
+
+    // If something done at `time` is past the final deadline
+    pub(crate) fn past_final_deadline_at(&self, time: DateTime<Local>) -> bool {
+        if let Some(final_deadline) = self.final_deadline {
+            return final_deadline.timestamp() < time.timestamp();
+        }
+        false
+    }
+
+}
+
+
+impl fmt::Display for Rubric {
+    /// Plain text with no color. The name, each criterion that isn't
+    /// hidden (in their alternate form, with a status symbol), then the grade.
+    ///
+    /// ```text
+    /// Test Rubric
+    /// ✓ First Criterion (50 / 50 pts)
+    /// ✗ Second Criterion (0 / 30 pts)
+    /// Grade: 50/80
+    /// ```
+    ///
+    /// To show percentages instead, use [`display_with`](crate::rubric::Rubric::display_with).
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_with(f, WorthFormat::Points)
+    }
+}
+
+impl Rubric {
+    /// The same plain text as the rubric's `Display`, with worth shown the way you choose.
+    ///
+    /// With [`WorthFormat::Percent`](crate::report::WorthFormat::Percent), the
+    /// points are shown as percentages of the total instead.
+    ///
+    /// ```rust
+    /// use rubric::Rubric;
+    /// use rubric::report::WorthFormat;
+    /// use rubric::rubric::Criterion;
+    ///
+    /// let mut rubric = Rubric::default();
+    /// rubric.add(Criterion::new("Compiles").worth(30).build());
+    /// rubric.add(Criterion::new("Tests pass").worth(70).build());
+    ///
+    /// assert!(rubric.display_with(WorthFormat::Percent).contains("Compiles (— / 30%)"));
+    /// ```
+    pub fn display_with(&self, format: WorthFormat) -> String {
+        DisplayWith(self, format).to_string()
+    }
+
+    fn write_with(&self, f: &mut fmt::Formatter, format: WorthFormat) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        let percent = format == WorthFormat::Percent;
+        let total = self.total_points();
+        for crit in self.criteria.iter().filter(|c| !c.hide) {
+            if percent {
+                if let Some(symbol) = crit.status_symbol() {
+                    write!(f, "{} ", symbol)?;
+                }
+                writeln!(f, "{} ({})", crit.name, crit.percent_display(total))?;
+            } else {
+                writeln!(f, "{:#}", crit)?;
+            }
+        }
+        let grade = if percent {
+            format!("{}%", report::percent_of(self.points() as isize, total))
+        } else {
+            format!("{}/{}", self.points(), total)
+        };
+        writeln!(f, "{}: {}{}", report::labels().grade, grade, report::bonus_note(self.bonus_total()))
+    }
+}
+
+// Displays a rubric with its worth in the given format
+struct DisplayWith<'a>(&'a Rubric, WorthFormat);
+
+impl fmt::Display for DisplayWith<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.write_with(f, self.1)
+    }
+}
+
+impl Index<usize> for Rubric {
+    type Output = Criterion;
+
+    /// Returns the criterion at the given position, in the order
+    /// they were added. Panics if it's out of bounds.
+    fn index(&self, index: usize) -> &Criterion {
+        let len = self.criteria.len();
+        self.criteria.get(index).unwrap_or_else(|| {
+            panic!("Criterion index out of bounds: the rubric has {} criteria but the index is {}", len, index)
+        })
+    }
+}
+
+impl IndexMut<usize> for Rubric {
+    fn index_mut(&mut self, index: usize) -> &mut Criterion {
+        let len = self.criteria.len();
+        self.criteria.get_mut(index).unwrap_or_else(|| {
+            panic!("Criterion index out of bounds: the rubric has {} criteria but the index is {}", len, index)
+        })
+    }
+}
+
+
+impl FromStr for Rubric {
     type Err = anyhow::Error;
 
 
@@ -217,15 +1437,15 @@ impl FromStr for Rubric {
         // Construct RubricYaml from yaml data
         // See yaml.rs
         let rubric_yaml = serde_yaml::from_str::<RubricYaml>(s)?;
+        let failure_template = rubric_yaml.failure_message
+            .unwrap_or_else(|| String::from(DEFAULT_FAILURE_TEMPLATE));
 
         // Pull out the criteria and count the total
-        let mut criteria_total: isize = 0;
         let mut criteria = vec![];
         for (name, crit_yaml) in rubric_yaml.criteria {
-            let crit = crit_yaml.into_criterion(name);
-            criteria_total += crit.worth as isize;
-            criteria.push(crit);
+            criteria.push(crit_yaml.into_criterion_with_template(name, &failure_template));
         }
+        let criteria_total = criteria_total(&criteria);
 
 
         if let Some(t) = rubric_yaml.total {
@@ -242,52 +1462,139 @@ impl FromStr for Rubric {
 
 
         // Parse deadline, if any
-        let mut deadline: Option<DateTime<Local>> = None;
-        if let Some(deadline_str) = rubric_yaml.deadline {
-            // Add the local timezone to the end so they don't have to specify
-            let added_timezone = format!("{} {}", deadline_str, Local::now().format("%z"));
-            // Parse what they entered + timezone into a DateTime
-            let parsed_deadline = DateTime::parse_from_str(&added_timezone, "%F %T %z").expect("Bad time format");
-            // Convert from DateTime<FixedOffset> to DateTime<Local>
-            deadline = Some(DateTime::from(parsed_deadline));
-        }
+        let deadline = rubric_yaml.deadline.as_deref().map(parse_deadline);
 
         // Parse final deadline, if any
-        let mut final_deadline: Option<DateTime<Local>> = None;
-        if let Some(final_deadline_str) = rubric_yaml.final_deadline {
-            // Add the local timezone to the end so they don't have to specify
-            let added_timezone = format!("{} {}", final_deadline_str, Local::now().format("%z"));
-            // Parse what they entered + timezone into a DateTime
-            let parsed_deadline = DateTime::parse_from_str(&added_timezone, "%F %T %z").expect("Bad time format");
-            // Convert from DateTime<FixedOffset> to DateTime<Local>
-            final_deadline = Some(DateTime::from(parsed_deadline));
-        }
+        let final_deadline = rubric_yaml.final_deadline.as_deref().map(parse_deadline);
+
+        // Checkpoints, sorted by deadline. The ones without a deadline go last.
+        let mut checkpoints: Vec<Checkpoint> = rubric_yaml.checkpoints
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, checkpoint_yaml)| Checkpoint {
+                name,
+                deadline: checkpoint_yaml.deadline.as_deref().map(parse_deadline),
+                criteria: checkpoint_yaml.criteria,
+            })
+            .collect();
+        checkpoints.sort_by(|a, b| match (a.deadline, b.deadline) {
+            (Some(x), Some(y)) => x.cmp(&y).then_with(|| a.name.cmp(&b.name)),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => a.name.cmp(&b.name),
+        });
 
         // Construct a rubric
         Ok(Rubric {
             name: rubric_yaml.name,
             desc: rubric_yaml.desc,
+            version: rubric_yaml.version,
             criteria: criteria,
             total: criteria_total,
+            expected_total: rubric_yaml.total,
             deadline: deadline,
             final_deadline: final_deadline,
             allow_late: rubric_yaml.allow_late.unwrap_or(true),
             late_penalty: rubric_yaml.late_penalty.unwrap_or(0),
-            daily_penalty: rubric_yaml.late_penalty_per_day.unwrap_or(0)
+            daily_penalty: rubric_yaml.late_penalty_per_day.unwrap_or(0),
+            pass_threshold: rubric_yaml.pass_threshold,
+            seed: rubric_yaml.seed,
+            worth_jitter: rubric_yaml.worth_jitter,
+            output_limit: rubric_yaml.output_limit.unwrap_or(DEFAULT_OUTPUT_LIMIT),
+            checkpoints,
+            wrap_width: None,
+            positions: RefCell::new(HashMap::new())
         })
     }
 }
 
+// The order criteria are graded and shown in. By index, then
+// by func and name so criteria with the same index don't move around.
+pub(crate) fn grading_order(a: &Criterion, b: &Criterion) -> std::cmp::Ordering {
+    (a.index, &a.func, &a.name).cmp(&(b.index, &b.func, &b.name))
+}
+
+// A number from hashing a seed and a string together
+fn seeded_hash(seed: u64, s: &str) -> u64 {
+    let mut hasher = Sha256::new();
+    hasher.update(seed.to_be_bytes());
+    hasher.update(s.as_bytes());
+    let hash = hasher.finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+// Adds up the worths of some criteria. Whole points are added as they are,
+// fractions are added up and rounded once at the end.
+fn sum_worth<'a, I: Iterator<Item = &'a Criterion>>(criteria: I) -> isize {
+    let (whole, fractions) = criteria.fold((0, 0.0_f64), |(whole, fractions), crit| {
+        match crit.worth_f64 {
+            Some(_) => (whole, fractions + crit.exact_worth()),
+            None => (whole + crit.weighted_worth(), fractions)
+        }
+    });
+    whole + fractions.round() as isize
+}
+
+// The total worth of some criteria, without bonus points. Bonus points are
+// extra, they aren't part of the total.
+fn criteria_total(criteria: &[Criterion]) -> isize {
+    sum_worth(criteria.iter().filter(|crit| !crit.bonus))
+}
+
+// Parses a deadline from the rubric's yaml. They're in the local timezone.
+fn parse_deadline(deadline: &str) -> DateTime<Local> {
+    // Add the local timezone to the end so they don't have to specify
+    let added_timezone = format!("{} {}", deadline, Local::now().format("%z"));
+    // Parse what they entered + timezone into a DateTime
+    let parsed_deadline = DateTime::parse_from_str(&added_timezone, "%F %T %z").expect("Bad time format");
+    // Convert from DateTime<FixedOffset> to DateTime<Local>
+    DateTime::from(parsed_deadline)
+}
+
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{yaml, attach, TestData};
+    use crate::{yaml, attach, attach_funcs, Submission, TestData};
 
     fn yaml_data() -> &'static str {
         yaml!("../../test_data/test_rubric.yml").unwrap()
     }
 
+    #[test]
+    fn test_from_criteria() {
+        let mut rubric = Rubric::from_criteria("Lab 1", vec![
+            Criterion::new("Passes").worth(10).test(Box::new(|_: &TestData| true)).build(),
+            Criterion::new("Fails").worth(5).build(),
+            Criterion::new("Extra").worth(3).bonus(true).build(),
+        ]);
+        assert_eq!(rubric.name, "Lab 1");
+        assert_eq!(rubric.total, 15);
+        assert_eq!(rubric.len(), 3);
+        assert!(rubric.allow_late);
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.grade, 10);
+    }
+
+    #[test]
+    fn test_criteria_mut() {
+        let mut rubric = Rubric::from_yaml(yaml_data()).unwrap();
+        for crit in rubric.criteria_mut().iter_mut() {
+            crit.attach(Box::new(|_: &TestData| true));
+        }
+        rubric.criteria_mut().push(Criterion::new("Added in code").worth(5).build());
+        assert_eq!(rubric.len(), 3);
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.grade, rubric.total);
+        assert!(sub.has_failed("Added in code"));
+    }
+
     #[test]
     fn test_from_yaml() {
         let rubric = Rubric::from_yaml(yaml_data()).expect("Bad yaml");
@@ -300,14 +1607,122 @@ mod tests {
         fn test_fn(_: &TestData) -> bool { true };
 
         let mut rubric = Rubric::from_yaml(yaml_data()).expect("Bad yaml");
-        assert!(!rubric.get("first_crit").unwrap().test());
+        assert!(!rubric.get_mut("first_crit").unwrap().test());
 
         attach! {
             rubric,
             "first_crit" => test_fn
         };
 
-        assert!(rubric.get("first_crit").unwrap().test());
+        assert!(rubric.get_mut("first_crit").unwrap().test());
+    }
+
+    #[test]
+    fn test_attach_funcs_macro() {
+        fn pass(_: &TestData) -> bool { true }
+        fn fail(_: &TestData) -> bool { false }
+
+        let mut rubric = Rubric::from_yaml(yaml_data()).expect("Bad yaml");
+        attach_funcs!(rubric, {
+            "first_crit" => pass,
+            "second_crit" => fail,
+        }).unwrap();
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        assert!(sub.has_passed("First Criterion"));
+        assert!(sub.has_failed("Second Criterion"));
+
+        let mut rubric = Rubric::from_yaml(yaml_data()).expect("Bad yaml");
+        let err = attach_funcs!(rubric, {
+            "first_crit" => pass,
+            "frist_crit" => pass,
+            "scond_crit" => pass
+        }).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No criterion with func `frist_crit`, `scond_crit` in the rubric, nothing was attached"
+        );
+        assert!(!rubric.get("first_crit").unwrap().has_test());
+    }
+
+    #[test]
+    fn test_get_many_criteria() {
+        let mut rubric = Rubric::default();
+        for i in 0..200 {
+            rubric.add(Criterion::new(&format!("Criterion {}", i)).func(&format!("crit_{}", i)).index(200 - i).build());
+        }
+
+        for i in (0..200).step_by(7) {
+            assert_eq!(rubric.get(&format!("crit_{}", i)).unwrap().name, format!("Criterion {}", i));
+        }
+        assert!(rubric.get("crit_200").is_none());
+
+        // Sorting reverses them, lookups still find the right ones
+        rubric.sorted();
+        assert_eq!(rubric.criteria[0].func, "crit_199");
+        assert_eq!(rubric.get("crit_0").unwrap().name, "Criterion 0");
+        rubric.get_mut("crit_42").unwrap().worth = 42;
+        assert_eq!(rubric.criteria[157].worth, 42);
+
+        // So do changes made to the criteria directly
+        rubric.criteria.remove(0);
+        assert!(rubric.get("crit_199").is_none());
+        rubric.criteria_mut()[0].func = String::from("renamed");
+        assert_eq!(rubric.get("renamed").unwrap().name, "Criterion 198");
+        assert!(rubric.get("crit_198").is_none());
+        assert_eq!(rubric.get("crit_1").unwrap().name, "Criterion 1");
+
+        // The first criterion with a func is found, like before
+        rubric.add(Criterion::new("Duplicate").func("crit_1").build());
+        assert_eq!(rubric.get("crit_1").unwrap().name, "Criterion 1");
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let yaml = r#"
+            name: Shared rubric
+            criteria:
+                First:
+                    worth: 10
+                Second:
+                    worth: 20
+                Third:
+                    worth: 30
+                    messages: ["yes", "no"]
+        "#;
+        let first = Rubric::from_yaml(yaml).unwrap();
+        let mut second = Rubric::from_yaml(yaml).unwrap();
+        assert_eq!(first.fingerprint().len(), 64);
+        assert_eq!(first.fingerprint(), second.fingerprint());
+
+        // Order in memory doesn't matter
+        second.criteria.reverse();
+        assert_eq!(first.fingerprint(), second.fingerprint());
+
+        // Nothing from grading is included
+        second.get_mut("first").unwrap().attach(Box::new(|_: &TestData| true));
+        Submission::new().grade_against(&mut second);
+        assert_eq!(first.fingerprint(), second.fingerprint());
+
+        second.criteria.pop();
+        assert_ne!(first.fingerprint(), second.fingerprint());
+
+        let renamed = Rubric::from_yaml(&yaml.replace("Third", "Fourth")).unwrap();
+        assert_ne!(first.fingerprint(), renamed.fingerprint());
+
+        // Every other key from the yaml counts too
+        let changes = [
+            "retries: 2",
+            "named_failures: { missing: Couldn't find it }",
+            "required_data: [repo]",
+            "cost: network",
+        ];
+        for change in &changes {
+            let changed = yaml.replace("worth: 30", &format!("worth: 30\n                    {}", change));
+            let changed = Rubric::from_yaml(&changed).unwrap();
+            assert_ne!(first.fingerprint(), changed.fingerprint(), "{} didn't change it", change);
+        }
     }
 
     #[test]
@@ -332,6 +1747,894 @@ mod tests {
         assert!(raw.parse::<Rubric>().is_ok());
     }
 
+    #[test]
+    fn test_grade_percentage() {
+        let mut rubric = Rubric::from_yaml(yaml_data()).unwrap();
+        assert_eq!(rubric.total_points_f64(), 80.0);
+        assert_eq!(rubric.grade_percentage(), 0.0);
+
+        rubric.get_mut("second_crit").unwrap().status = Some(true);
+        assert_eq!(rubric.grade_percentage(), 37.5);
+    }
+
+    #[test]
+    fn test_zero_total_percentage_is_not_nan() {
+        let mut rubric = Rubric::default();
+        assert!(!rubric.grade_percentage().is_nan());
+        assert_eq!(rubric.grade_percentage(), 0.0);
+
+        rubric.add(Criterion::new("bonus").worth(0).build());
+        rubric.criteria[0].status = Some(true);
+        assert_eq!(rubric.total_points_f64(), 0.0);
+        assert!(!rubric.grade_percentage().is_nan());
+        assert_eq!(rubric.grade_percentage(), 0.0);
+    }
+
+    fn write_file(dir: &Path, name: &str, content: &str) {
+        let mut path = dir.to_path_buf();
+        path.push(name);
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_from_dir() {
+        let dir = tempdir::TempDir::new("rubric_from_dir").unwrap();
+        write_file(dir.path(), "lab_1.yml", "criteria:\n  Lab Criterion:\n    func: lab_crit\n    worth: 20\n");
+        write_file(dir.path(), "base.yaml", "name: Base\ncriteria:\n  Base Criterion:\n    func: base_crit\n    worth: 10\n");
+        write_file(dir.path(), "notes.txt", "not yaml: [");
+
+        let rubric = Rubric::from_dir(dir.path()).unwrap();
+        assert_eq!(rubric.name, "Base");
+        assert_eq!(rubric.len(), 2);
+        assert_eq!(rubric.total, 30);
+        assert!(rubric.get("base_crit").is_some());
+        assert!(rubric.get("lab_crit").is_some());
+    }
+
+    #[test]
+    fn test_from_dir_func_collision() {
+        let dir = tempdir::TempDir::new("rubric_from_dir_collision").unwrap();
+        write_file(dir.path(), "a.yml", "name: A\ncriteria:\n  First:\n    func: same\n    worth: 10\n");
+        write_file(dir.path(), "b.yml", "criteria:\n  Second:\n    func: same\n    worth: 10\n");
+
+        assert!(Rubric::from_dir(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_build_and_index() {
+        let mut rubric = Rubric::default();
+        assert_eq!(rubric.len(), 0);
+
+        rubric.add(Criterion::new("first").worth(5).build());
+        rubric.add(Criterion::new("second").worth(10).build());
+
+        assert_eq!(rubric[0].name, "first");
+        assert_eq!(rubric[1].worth, 10);
+
+        rubric[1].worth = 15;
+        assert_eq!(rubric.total_points(), 20);
+    }
+
+    #[test]
+    #[should_panic(expected = "the rubric has 0 criteria but the index is 3")]
+    fn test_index_out_of_bounds() {
+        let rubric = Rubric::default();
+        let _ = &rubric[3];
+    }
+
+    #[test]
+    fn test_pass_threshold_from_yaml() {
+        let raw = r#"
+            name: Pass/fail lab
+            pass_threshold: 70.0
+            criteria:
+                Compiles:
+                    worth: 10
+                    required: true
+        "#;
+        let rubric = Rubric::from_yaml(raw).unwrap();
+        assert_eq!(rubric.pass_threshold, Some(70.0));
+        assert!(rubric[0].required);
+
+        assert!(Rubric::from_yaml(yaml_data()).unwrap().pass_threshold.is_none());
+    }
+
+    #[test]
+    fn test_points_earned() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Passes").worth(10).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Fails").worth(20).test(Box::new(|_: &TestData| false)).build());
+        rubric.add(Criterion::new("Weighted").worth(10).weight(0.5).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Penalty").worth(-3).test(Box::new(|_: &TestData| true)).build());
+        assert_eq!(rubric.points_earned(), 0);
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+
+        assert_eq!(rubric.points_earned(), 12);
+        assert_eq!(rubric.points_earned(), sub.grade);
+        assert_eq!(rubric.total_points(), 32);
+    }
+
+    #[test]
+    fn test_groups_from_yaml() {
+        let yaml = r#"
+            name: Sections
+            criteria:
+                Git installed:
+                    func: git
+                    index: 1
+                    group: Setup
+                    worth: 5
+                Tests pass:
+                    func: tests
+                    index: 3
+                    group: Code
+                    worth: 20
+                Repo cloned:
+                    func: clone
+                    index: 2
+                    group: Setup
+                    worth: 5
+                Extra credit:
+                    func: extra
+                    index: 4
+                    worth: 5
+        "#;
+        let rubric = Rubric::from_yaml(yaml).unwrap();
+        assert_eq!(rubric.groups(), vec!["Setup", "Code"]);
+        assert_eq!(rubric.get("clone").unwrap().group(), Some("Setup"));
+        assert_eq!(rubric.get("extra").unwrap().group(), None);
+        assert_eq!(rubric.to_summary_json()["criteria"][2]["group"], "Code");
+
+        assert!(Rubric::from_yaml(yaml_data()).unwrap().groups().is_empty());
+    }
+
+    #[test]
+    fn test_fractional_worth() {
+        let yaml = "name: Test\ncriteria:\n  First:\n    func: first\n    worth: 2.5\n  Second:\n    func: second\n    worth: 2.5\n  Third:\n    func: third\n    worth: 1.5\n  Fourth:\n    func: fourth\n    worth: 4\n";
+        let mut rubric = Rubric::from_yaml(yaml).unwrap();
+        assert_eq!(rubric.get("first").unwrap().worth_f64, Some(2.5));
+        assert_eq!(rubric.get("fourth").unwrap().worth_f64, None);
+        // Rounding each one would make 3 + 3 + 2 + 4
+        assert_eq!(rubric.total_points(), 11);
+        assert_eq!(rubric.total, 11);
+        // A yaml total that matches doesn't look like a mistake
+        let declared = Rubric::from_yaml(&format!("total: 11\n{}", yaml)).unwrap();
+        if let Err(errors) = declared.validate() {
+            assert!(!errors.iter().any(|e| matches!(e, ValidationError::TotalMismatch { .. })));
+        }
+        assert_eq!(rubric.to_summary_json()["criteria"].as_array().unwrap().iter()
+            .map(|c| c["worth"].as_f64().unwrap())
+            .sum::<f64>(), 10.5);
+
+        fn pass(_: &TestData) -> bool { true }
+        fn fail(_: &TestData) -> bool { false }
+        attach! {
+            rubric,
+            "first" => pass,
+            "second" => pass,
+            "third" => fail,
+            "fourth" => pass
+        };
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.grade, 9);
+        assert_eq!(rubric.points_earned(), 9);
+        // The points each criterion awarded add up to the grade
+        assert_eq!(sub.results.iter().map(|r| r.awarded).sum::<isize>(), 9);
+        let first = sub.results.iter().find(|r| r.func == "first").unwrap();
+        assert_eq!((first.awarded, first.possible), (3, 3));
+        let second = sub.results.iter().find(|r| r.func == "second").unwrap();
+        assert_eq!((second.awarded, second.possible), (2, 3));
+        assert!(sub.passed.contains(&String::from("Second (+2)")));
+        // Reports show the fraction, and the same awarded points
+        assert!(sub.as_table(&rubric).contains(" 2.5 "));
+        let report = GradeReport::new(&rubric, &sub);
+        assert_eq!(report.criteria.iter().map(|c| c.awarded).sum::<isize>(), 9);
+        assert_eq!(report.criteria.iter().find(|c| c.func == "first").unwrap().possible, 2.5);
+        assert_eq!(rubric.get("first").unwrap().points_display(), "2.5 / 2.5 pts");
+    }
+
+    #[test]
+    fn test_totals_with_bonus() {
+        let mut rubric = Rubric::default();
+        rubric.name = String::from("Lab 3");
+        rubric.add(Criterion::new("Compiles").worth(10).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Tests pass").worth(20).weight(0.5).test(Box::new(|_: &TestData| false)).build());
+        rubric.add(Criterion::new("Extra credit").worth(5).bonus(true).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Has warnings").worth(-3).test(Box::new(|_: &TestData| true)).build());
+
+        assert_eq!(rubric.required_total(), 20);
+        assert_eq!(rubric.bonus_total(), 5);
+        assert_eq!(rubric.total_with_bonus(), 25);
+        assert_eq!(rubric.total_points(), 17);
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.grade, 12);
+        assert!(rubric.to_string().ends_with("Grade: 12/17 (+5 bonus available)\n"));
+
+        // Skipped bonus criteria aren't available
+        rubric.get_mut("extra_credit").unwrap().skip();
+        assert_eq!(rubric.bonus_total(), 0);
+        assert!(rubric.to_string().ends_with("Grade: 7/17\n"));
+    }
+
+    #[test]
+    fn test_to_summary_json() {
+        let rubric = Rubric::from_yaml(yaml_data()).unwrap();
+        let json = rubric.to_summary_json();
+
+        assert_eq!(json["name"], rubric.name.as_str());
+        let criteria = json["criteria"].as_array().unwrap();
+        assert_eq!(criteria.len(), rubric.len());
+
+        for crit in rubric.criteria.iter().filter(|c| !c.hide) {
+            let summary = criteria.iter().find(|c| c["func"] == crit.func.as_str()).unwrap();
+            assert_eq!(summary["name"], crit.name.as_str());
+            assert_eq!(summary["worth"], crit.worth);
+            assert_eq!(summary["hide"], false);
+        }
+
+        // In grading order
+        let funcs: Vec<&str> = criteria.iter().map(|c| c["func"].as_str().unwrap()).collect();
+        assert_eq!(funcs, rubric.funcs());
+        // No results
+        assert!(criteria.iter().all(|c| c.get("status").is_none()));
+    }
+
+    #[test]
+    fn test_penalty_criteria() {
+        fn rubric(has_warnings: bool) -> Rubric {
+            let mut rubric = Rubric::default();
+            rubric.add(Criterion::new("Compiles").worth(10).test(Box::new(|_: &TestData| true)).build());
+            rubric.add(Criterion::new("Has warnings").worth(3).penalty(true)
+                .test(Box::new(move |_: &TestData| has_warnings)).build());
+            rubric
+        }
+
+        // The bad thing is there, points are taken away
+        let mut triggered = rubric(true);
+        let mut sub = Submission::new();
+        sub.grade_against(&mut triggered);
+        assert_eq!(sub.grade, 7);
+        assert_eq!(triggered.get("has_warnings").unwrap().points_display(), "-3 / -3 pts");
+
+        // It isn't, the grade is untouched
+        let mut clean = rubric(false);
+        let mut sub = Submission::new();
+        sub.grade_against(&mut clean);
+        assert_eq!(sub.grade, 10);
+        assert_eq!(clean.get("has_warnings").unwrap().points_display(), "0 / -3 pts");
+
+        // Same as a negative worth, and a negative worth with the flag is still a penalty
+        let old = Criterion::new("old").worth(-3).build();
+        let both = Criterion::new("both").worth(-3).penalty(true).build();
+        let flag = triggered.get("has_warnings").unwrap();
+        assert_eq!(old.weighted_worth(), flag.weighted_worth());
+        assert_eq!(both.weighted_worth(), flag.weighted_worth());
+        assert_eq!(triggered.total_points(), clean.total_points());
+        assert_eq!(triggered.required_total(), 10);
+    }
+
+    #[test]
+    fn test_penalty_from_yaml() {
+        let raw = r#"
+            name: Penalty rubric
+            total: 7
+            criteria:
+                Compiles:
+                    worth: 10
+                Has warnings:
+                    worth: 3
+                    penalty: true
+        "#;
+        let rubric = Rubric::from_yaml(raw).unwrap();
+        assert_eq!(rubric.total, 7);
+        assert!(rubric.get("has_warnings").unwrap().penalty);
+        assert_eq!(rubric.get("has_warnings").unwrap().weighted_worth(), -3);
+    }
+
+    #[test]
+    fn test_bonus_from_yaml() {
+        let raw = r#"
+            name: Bonus rubric
+            total: 10
+            criteria:
+                Compiles:
+                    worth: 10
+                Extra credit:
+                    worth: 5
+                    bonus: true
+        "#;
+        let rubric = Rubric::from_yaml(raw).unwrap();
+        assert_eq!(rubric.total, 10);
+        assert!(rubric.get("extra_credit").unwrap().bonus);
+        assert!(!rubric.get("compiles").unwrap().bonus);
+        assert_eq!(rubric.bonus_total(), 5);
+    }
+
+    #[test]
+    fn test_grade_checkpoints() {
+        let raw = r#"
+            name: Multi-week project
+            late_penalty: 5
+            checkpoints:
+                Week 2:
+                    deadline: 2099-01-01 23:59:59
+                    criteria: [tests_pass, docs]
+                Week 1:
+                    deadline: 2000-01-01 23:59:59
+                    criteria: [compiles]
+            criteria:
+                Compiles:
+                    func: compiles
+                    worth: 20
+                Tests pass:
+                    func: tests_pass
+                    worth: 50
+                Docs:
+                    func: docs
+                    worth: 30
+        "#;
+        let mut rubric = Rubric::from_yaml(raw).unwrap();
+        for crit in &mut rubric.criteria {
+            crit.attach(Box::new(|_: &TestData| true));
+        }
+
+        // Sorted by deadline
+        let names: Vec<&str> = rubric.checkpoints.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Week 1", "Week 2"]);
+
+        // Week 1 is past due
+        let mut week_1 = Submission::new();
+        rubric.grade_checkpoint("Week 1", &mut week_1).unwrap();
+        assert_eq!(week_1.passed, vec!["Compiles (+20)"]);
+        assert!(week_1.late);
+        assert_eq!(week_1.grade, 15);
+
+        // Week 2 isn't, and doesn't include Week 1's criteria
+        let mut week_2 = Submission::new();
+        rubric.grade_checkpoint("Week 2", &mut week_2).unwrap();
+        assert_eq!(week_2.passed.len(), 2);
+        assert!(!week_2.late);
+        assert_eq!(week_2.grade, 80);
+
+        // The rubric is put back the way it was
+        assert_eq!(rubric.len(), 3);
+        assert!(rubric.deadline.is_none());
+    }
+
+    #[test]
+    fn test_checkpoint_without_deadline() {
+        let raw = r#"
+            name: Multi-week project
+            deadline: 2000-01-01 23:59:59
+            late_penalty: 5
+            checkpoints:
+                Week 1:
+                    criteria: [compiles]
+            criteria:
+                Compiles:
+                    func: compiles
+                    worth: 20
+        "#;
+        let mut rubric = Rubric::from_yaml(raw).unwrap();
+        rubric.criteria[0].attach(Box::new(|_: &TestData| true));
+
+        // Falls back to the rubric's deadline, which has passed
+        let mut sub = Submission::new();
+        rubric.grade_checkpoint("Week 1", &mut sub).unwrap();
+        assert!(sub.late);
+        assert_eq!(sub.grade, 15);
+        assert!(rubric.deadline.is_some());
+    }
+
+    #[test]
+    fn test_grade_checkpoint_errors() {
+        let mut rubric = Rubric::from_yaml(yaml_data()).unwrap();
+        let mut sub = Submission::new();
+        assert!(rubric.grade_checkpoint("Week 1", &mut sub).is_err());
+
+        rubric.checkpoints.push(Checkpoint {
+            name: String::from("Week 1"),
+            deadline: None,
+            criteria: vec![String::from("not_a_criterion")],
+        });
+        assert!(rubric.grade_checkpoint("Week 1", &mut sub).is_err());
+        assert_eq!(rubric.len(), 2);
+    }
+
+    #[test]
+    fn test_get() {
+        let mut rubric = Rubric::from_yaml(yaml_data()).unwrap();
+
+        // Only needs a shared borrow
+        let shared = &rubric;
+        assert_eq!(shared.get("first_crit").unwrap().worth, 50);
+        assert!(shared.get("not_a_crit").is_none());
+
+        rubric.get_mut("first_crit").unwrap().worth = 10;
+        assert_eq!(rubric.get("first_crit").unwrap().worth, 10);
+        assert!(rubric.get_mut("not_a_crit").is_none());
+    }
+
+    #[test]
+    fn test_get_or_err() {
+        let rubric = Rubric::from_yaml(yaml_data()).unwrap();
+        assert_eq!(rubric.get_or_err("second_crit").unwrap().func, "second_crit");
+
+        match rubric.get_or_err("not_a_crit") {
+            Err(e) => assert!(e.to_string().contains("not_a_crit")),
+            Ok(_) => panic!("found a criterion that doesn't exist"),
+        }
+    }
+
+    #[test]
+    fn test_grade_within_truncates() {
+        let quick = |_: &TestData| {
+            std::thread::sleep(Duration::from_millis(10));
+            true
+        };
+        let slow = |_: &TestData| {
+            std::thread::sleep(Duration::from_millis(1500));
+            true
+        };
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("First").worth(10).index(0).test(Box::new(quick)).build());
+        rubric.add(Criterion::new("Second").worth(10).index(1).test(Box::new(slow)).build());
+        rubric.add(Criterion::new("Third").worth(10).index(2).test(Box::new(quick)).build());
+
+        // The second criterion takes the whole budget
+        let mut sub = Submission::new();
+        let run = rubric.grade_within(&mut sub, Duration::from_secs(1));
+
+        assert!(run.truncated());
+        assert_eq!(run.not_run, vec!["Third"]);
+        assert_eq!(sub.grade, 20);
+        assert!(!sub.has_failed("Third"));
+
+        let third = rubric.get("third").unwrap();
+        assert!(third.status.is_none());
+        assert!(third.skipped);
+        assert_eq!(third.skip_reason(), "not run (time budget exceeded)");
+
+        let result = sub.results.iter().find(|r| r.func == "third").unwrap();
+        assert!(result.not_run && !result.passed);
+        assert_eq!(result.message, "not run (time budget exceeded)");
+        assert_eq!(sub.grade_breakdown().len(), 2);
+    }
+
+    #[test]
+    fn test_messages_forms() {
+        let raw = r#"
+            name: Messages
+            criteria:
+                Both:
+                    worth: 10
+                    messages: ["yay", "boo"]
+                Success only:
+                    worth: 10
+                    messages: "compiled"
+                Neither:
+                    worth: 10
+        "#;
+        let rubric = Rubric::from_yaml(raw).unwrap();
+        assert_eq!(rubric.get("both").unwrap().messages, ("yay".to_string(), "boo".to_string()));
+        assert_eq!(rubric.get("success_only").unwrap().messages, ("compiled".to_string(), "Failed: Success only".to_string()));
+        assert_eq!(rubric.get("neither").unwrap().messages, ("passed".to_string(), "failed".to_string()));
+    }
+
+    #[test]
+    fn test_failure_message_template() {
+        let raw = r#"
+            name: Messages
+            failure_message: "{name} didn't pass"
+            criteria:
+                Compiles:
+                    worth: 10
+                    messages: "compiled"
+                Both:
+                    worth: 10
+                    messages: ["yay", "boo"]
+        "#;
+        let rubric = Rubric::from_yaml(raw).unwrap();
+        assert_eq!(rubric.get("compiles").unwrap().failure_message(), "Compiles didn't pass");
+        // The template doesn't override an explicit failure message
+        assert_eq!(rubric.get("both").unwrap().failure_message(), "boo");
+    }
+
+    #[test]
+    fn test_grade_cached_runs_once() {
+        use std::rc::Rc;
+        use std::cell::Cell;
+
+        let runs = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&runs);
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Expensive").worth(10).test(Box::new(move |data: &TestData| {
+            counter.set(counter.get() + 1);
+            data.contains_key("name")
+        })).build());
+        let mut cache = ResultCache::new();
+
+        let mut first = Submission::from_data(crate::data! { "name" => "luke" });
+        rubric.grade_cached(&mut first, &mut cache);
+        let mut second = Submission::from_data(crate::data! { "name" => "luke" });
+        rubric.grade_cached(&mut second, &mut cache);
+
+        assert_eq!(runs.get(), 1);
+        assert_eq!(first.grade, 10);
+        assert_eq!(second.grade, 10);
+        assert_eq!(rubric[0].status, Some(true));
+
+        // Different data isn't cached yet
+        let mut other = Submission::from_data(crate::data! { "id" => "1234" });
+        rubric.grade_cached(&mut other, &mut cache);
+        assert_eq!(runs.get(), 2);
+        assert_eq!(other.grade, 0);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_retries_and_flaky() {
+        use std::rc::Rc;
+        use std::cell::Cell;
+
+        // Fails the first time, then passes
+        let runs = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&runs);
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Flaky").worth(10).retries(2).test(Box::new(move |_: &TestData| {
+            counter.set(counter.get() + 1);
+            counter.get() > 1
+        })).build());
+        rubric.add(Criterion::new("Steady").worth(5).retries(2).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("Broken").worth(5).retries(2).build());
+        // Takes points away without being marked as a penalty
+        rubric.add(Criterion::new("Deduction").worth(-5).retries(2).build());
+        rubric.add(Criterion::new("Stubborn").worth(5).retries(u32::MAX).test(Box::new(|_: &TestData| true)).build());
+
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        assert_eq!(sub.grade, 20);
+        assert_eq!(runs.get(), 2);
+
+        let flaky: Vec<&str> = rubric.flaky().iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(flaky, vec!["Flaky"]);
+        assert_eq!(rubric.get("flaky").unwrap().attempts, 2);
+        assert_eq!(rubric.get("steady").unwrap().attempts, 1);
+        // Failed every time
+        assert_eq!(rubric.get("broken").unwrap().attempts, 3);
+        assert_eq!(rubric.get("deduction").unwrap().attempts, 1);
+        assert_eq!(rubric.get("stubborn").unwrap().attempts, 1);
+
+        // Passes the first time now, so it isn't flaky anymore
+        sub.grade_against(&mut rubric);
+        assert!(rubric.flaky().is_empty());
+        assert_eq!(rubric.get("flaky").unwrap().run_count, 3);
+    }
+
+    #[test]
+    fn test_retries_from_yaml() {
+        let yaml = "name: Test\ncriteria:\n  Site responds:\n    worth: 10\n    retries: 3\n";
+        let rubric = Rubric::from_yaml(yaml).unwrap();
+        assert_eq!(rubric.get("site_responds").unwrap().retries, 3);
+    }
+
+    #[test]
+    fn test_strict_grade() {
+        let mut rubric = Rubric::from_yaml(yaml_data()).unwrap();
+        rubric.get_mut("first_crit").unwrap().attach(Box::new(|_: &TestData| true));
+
+        let mut sub = Submission::new();
+        sub.grade = 3;
+        let err = rubric.strict_grade(&mut sub).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Not grading, these criteria don't have a test: Second Criterion (second_crit)"
+        );
+        // Nothing was graded
+        assert_eq!(sub.grade, 3);
+        assert!(rubric.get("first_crit").unwrap().status.is_none());
+
+        rubric.get_mut("second_crit").unwrap().attach(Box::new(|_: &TestData| true));
+        rubric.strict_grade(&mut sub).unwrap();
+        assert_eq!(sub.grade, rubric.total);
+    }
+
+    #[test]
+    fn test_regrade() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Has name").worth(10)
+            .test(Box::new(|data: &TestData| data.contains_key("name"))).build());
+        rubric.add(Criterion::new("Has id").worth(5)
+            .test(Box::new(|data: &TestData| data.contains_key("id"))).build());
+
+        let mut subs = vec![
+            Submission::from_data(crate::data! { "name" => "luke" }),
+            Submission::from_data(crate::data! { "id" => "1234" }),
+        ];
+        for sub in subs.iter_mut() {
+            sub.grade_against(&mut rubric);
+        }
+        subs[1].override_grade(15, "Appeal");
+        assert_eq!(subs[0].grade, 10);
+
+        // The id criterion had a bug, ids are in "student_id"
+        rubric.get_mut("has_id").unwrap()
+            .attach(Box::new(|data: &TestData| data.contains_key("student_id") || data.contains_key("name")));
+        let diffs = rubric.regrade(&mut subs);
+
+        assert_eq!(subs[0].grade, 15);
+        assert!(subs[0].has_passed("Has id"));
+        assert_eq!(diffs[0].newly_passed, vec!["Has id"]);
+        assert_eq!(subs[1].grade, 0);
+        assert!(subs[1].override_reason.is_none());
+        assert_eq!(diffs[1].grade_delta, -15);
+    }
+
+    #[test]
+    fn test_regrade_uses_submission_time() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Passes").worth(10).test(Box::new(|_: &TestData| true)).build());
+        rubric.deadline = Some(Local::now() - chrono::Duration::days(2));
+        rubric.late_penalty = 5;
+
+        let mut on_time = Submission::new();
+        on_time.time = Local::now() - chrono::Duration::days(3);
+        let mut late = Submission::new();
+        late.time = Local::now() - chrono::Duration::days(1);
+        let mut subs = vec![on_time, late];
+
+        rubric.regrade(&mut subs);
+        assert!(!subs[0].late);
+        assert_eq!(subs[0].grade, 10);
+        assert!(subs[1].late);
+        assert!(subs[1].grade < 10);
+    }
+
+    #[test]
+    fn test_resume_grade() {
+        let yaml = "name: Test\nseed: 4\ncriteria:\n  One:\n    func: one\n    worth: 10\n  Two:\n    func: two\n    worth: 5\n  Three:\n    func: three\n    worth: 20\n  Four:\n    func: four\n    worth: 1\n";
+        fn pass(_: &TestData) -> bool { true }
+        fn fail(_: &TestData) -> bool { false }
+        fn unreachable(_: &TestData) -> bool { panic!("this was already graded") }
+
+        // Grade half, then "crash"
+        let mut rubric = Rubric::from_yaml(yaml).unwrap();
+        rubric.criteria.retain(|crit| crit.func == "one" || crit.func == "two");
+        attach! { rubric, "one" => pass, "two" => fail };
+        let mut sub = Submission::new();
+        sub.grade_against(&mut rubric);
+        let saved = serde_json::to_string(&sub).unwrap();
+
+        let mut rubric = Rubric::from_yaml(yaml).unwrap();
+        attach! {
+            rubric,
+            "one" => unreachable,
+            "two" => unreachable,
+            "three" => pass,
+            "four" => fail
+        };
+        let mut sub: Submission = saved.parse().unwrap();
+        rubric.resume_grade(&mut sub);
+
+        assert_eq!(sub.grade, 30);
+        assert_eq!(sub.results.len(), 4);
+        assert!(sub.has_passed("One"));
+        assert!(sub.has_failed("Two"));
+        assert!(sub.has_passed("Three"));
+        assert!(sub.has_failed("Four"));
+    }
+
+    #[test]
+    fn test_resume_after_interruption() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let yaml = "name: Test\ncriteria:\n  One:\n    func: one\n    index: 1\n    worth: 10\n  Two:\n    func: two\n    index: 2\n    worth: 5\n  Three:\n    func: three\n    index: 3\n    worth: 20\n  Four:\n    func: four\n    index: 4\n    worth: 1\n";
+        fn pass(_: &TestData) -> bool { true }
+        fn fail(_: &TestData) -> bool { false }
+        fn crash(_: &TestData) -> bool { panic!("the grader crashed") }
+        fn unreachable(_: &TestData) -> bool { panic!("this was already graded") }
+
+        // Each result is saved as it's made, until the run dies on the third
+        let original = || Submission::from_data(crate::data! { "id" => "1234" });
+        let mut saved: Vec<String> = Vec::new();
+        let mut rubric = Rubric::from_yaml(yaml).unwrap();
+        attach! { rubric, "one" => pass, "two" => fail, "three" => crash, "four" => pass };
+        let mut sub = original();
+        let run = panic::catch_unwind(AssertUnwindSafe(|| {
+            rubric.resume_grade_with(&mut sub, |result| saved.push(serde_json::to_string(result).unwrap()));
+        }));
+        assert!(run.is_err());
+        assert_eq!(saved.len(), 2);
+
+        // Start again from the saved results
+        let mut rubric = Rubric::from_yaml(yaml).unwrap();
+        attach! { rubric, "one" => unreachable, "two" => unreachable, "three" => pass, "four" => fail };
+        let mut sub = original();
+        sub.results = saved.iter().map(|r| serde_json::from_str(r).unwrap()).collect();
+        let mut seen = Vec::new();
+        rubric.resume_grade_with(&mut sub, |result| seen.push(result.func.clone()));
+
+        assert_eq!(seen, vec!["one", "two", "three", "four"]);
+        assert_eq!(sub.grade, 30);
+        assert!(sub.has_passed("One"));
+        assert!(sub.has_failed("Two"));
+        assert!(sub.has_passed("Three"));
+        assert!(sub.has_failed("Four"));
+    }
+
+    #[test]
+    fn test_display_order() {
+        let mut rubric = Rubric::default();
+        for i in 0..10 {
+            rubric.add(Criterion::new(&format!("Criterion {}", i)).index(i).worth(1).build());
+        }
+        let funcs = |order: Vec<&Criterion>| -> Vec<String> {
+            order.iter().map(|c| c.func.clone()).collect()
+        };
+
+        let first = funcs(rubric.display_order(1));
+        assert_eq!(first, funcs(rubric.display_order(1)));
+        assert_ne!(first, funcs(rubric.display_order(2)));
+        assert_eq!(first.len(), 10);
+
+        // Grading order is untouched
+        let sorted: Vec<i64> = rubric.sorted().iter().map(|c| c.index).collect();
+        assert_eq!(sorted, (0..10).collect::<Vec<i64>>());
+    }
+
+    #[test]
+    fn test_grade_with_progress() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Second").index(2).worth(5).test(Box::new(|_: &TestData| true)).build());
+        rubric.add(Criterion::new("First").index(1).worth(10).test(Box::new(|_: &TestData| true)).build());
+        // Skipped criteria still get a callback
+        rubric.add(Criterion::new("Third").index(3).worth(5).when(|_| false).build());
+
+        let mut calls = Vec::new();
+        let mut sub = Submission::new();
+        rubric.grade_with_progress(&mut sub, |i, total, crit| {
+            // Called before the criterion is tested
+            assert!(crit.status.is_none());
+            calls.push((i, total, crit.name.clone()));
+        });
+
+        assert_eq!(calls, vec![
+            (0, 3, String::from("First")),
+            (1, 3, String::from("Second")),
+            (2, 3, String::from("Third")),
+        ]);
+        assert_eq!(sub.grade, 15);
+    }
+
+    #[test]
+    fn test_names_and_funcs_in_sorted_order() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Last").func("last").index(300).build());
+        rubric.add(Criterion::new("First").func("first").index(0).build());
+        rubric.add(Criterion::new("Middle A").func("middle_a").build());
+        rubric.add(Criterion::new("Middle B").func("middle_b").build());
+
+        let names = vec!["First", "Middle A", "Middle B", "Last"];
+        assert_eq!(rubric.names(), names);
+        assert_eq!(rubric.funcs(), vec!["first", "middle_a", "middle_b", "last"]);
+
+        // Same order grading uses
+        let sorted: Vec<String> = rubric.sorted().iter().map(|c| c.name.clone()).collect();
+        assert_eq!(sorted, names);
+    }
+
+    #[test]
+    fn test_index_ties_sorted_by_func() {
+        let raw = "name: Ties\ncriteria:\n  Delta:\n    func: delta\n    index: 1\n    worth: 1\n  Alpha:\n    func: alpha\n    index: 1\n    worth: 1\n  Charlie:\n    func: charlie\n    worth: 1\n  Bravo:\n    func: bravo\n    worth: 1\n  Echo:\n    func: echo\n    index: 0\n    worth: 1\n";
+        let expected = vec!["echo", "alpha", "delta", "bravo", "charlie"];
+
+        for _ in 0..20 {
+            let mut rubric = Rubric::from_yaml(raw).unwrap();
+            assert_eq!(rubric.funcs(), expected);
+            let sorted: Vec<String> = rubric.sorted().iter().map(|c| c.func.clone()).collect();
+            assert_eq!(sorted, expected);
+        }
+    }
+
+    #[test]
+    fn test_seed_from_yaml() {
+        let raw = r#"
+            name: Randomized lab
+            seed: 1234
+            criteria:
+                Sorts:
+                    worth: 10
+        "#;
+        let rubric = Rubric::from_yaml(raw).unwrap();
+        assert_eq!(rubric.seed, Some(1234));
+        assert!(Rubric::from_yaml(yaml_data()).unwrap().seed.is_none());
+    }
+
+    #[test]
+    fn test_seed_for() {
+        let mut rubric = Rubric::default();
+        assert!(rubric.seed_for("1234").is_none());
+
+        rubric.seed = Some(42);
+        assert_eq!(rubric.seed_for("1234"), rubric.seed_for("1234"));
+        assert_ne!(rubric.seed_for("1234"), rubric.seed_for("5678"));
+
+        let other = Rubric { seed: Some(43), ..Rubric::default() };
+        assert_ne!(rubric.seed_for("1234"), other.seed_for("1234"));
+    }
+
+    #[test]
+    fn test_sort_by_worth_descending() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("middle").worth(20).build());
+        rubric.add(Criterion::new("low").worth(5).build());
+        rubric.add(Criterion::new("high").worth(50).build());
+
+        rubric.sort_by_key(|c| std::cmp::Reverse(c.worth));
+        let names: Vec<&str> = rubric.criteria().iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["high", "middle", "low"]);
+    }
+
+    #[test]
+    fn test_highest_worth_failed() {
+        let mut rubric = Rubric::default();
+        assert!(rubric.highest_worth_failed().is_none());
+
+        rubric.add(Criterion::new("passed big").worth(100).build());
+        rubric.add(Criterion::new("failed small").worth(5).build());
+        rubric.add(Criterion::new("failed big").worth(30).build());
+        rubric.add(Criterion::new("untested").worth(60).build());
+        rubric[0].status = Some(true);
+        rubric[1].status = Some(false);
+        rubric[2].status = Some(false);
+
+        assert_eq!(rubric.highest_worth_failed().unwrap().name, "failed big");
+    }
+
+    #[test]
+    fn test_display() {
+        let mut rubric = Rubric::from_yaml(yaml_data()).unwrap();
+        rubric.get_mut("first_crit").unwrap().status = Some(true);
+        rubric.get_mut("second_crit").unwrap().status = Some(false);
+        rubric.add(Criterion::new("Hidden").hide(true).build());
+
+        let text = format!("{}", rubric);
+        assert!(text.starts_with("Test Rubric\n"));
+        assert!(text.contains("✓ First Criterion (50 / 50 pts)\n"));
+        assert!(text.contains("✗ Second Criterion (0 / 30 pts)\n"));
+        assert!(!text.contains("Hidden"));
+        assert!(text.ends_with("Grade: 50/80\n"));
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = Rubric::from_yaml(yaml_data()).unwrap();
+        assert!(old.diff(&Rubric::from_yaml(yaml_data()).unwrap()).is_empty());
+
+        let mut new = Rubric::from_yaml(yaml_data()).unwrap();
+        new.get_mut("first_crit").unwrap().worth = 40;
+        new.get_mut("first_crit").unwrap().messages = ("yay".to_string(), "boo".to_string());
+        new.criteria.retain(|c| c.func != "second_crit");
+        new.add(Criterion::new("Third").func("third_crit").worth(30).build());
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, vec!["third_crit"]);
+        assert_eq!(diff.removed, vec!["second_crit"]);
+        assert_eq!(diff.modified, vec![CriterionChange {
+            func: "first_crit".to_string(),
+            worth: Some((50, 40)),
+            messages: Some((
+                ("success".to_string(), "failure".to_string()),
+                ("yay".to_string(), "boo".to_string())
+            )),
+        }]);
+    }
+
     #[test]
     fn test_rubric_past_due() {
         let ok_rubric = Rubric::from_yaml(yaml_data()).unwrap();
@@ -0,0 +1,139 @@
+//! A registry of test functions, so criteria can find their tests by `func`
+//!
+//! Register your functions once when your grader starts, then any rubric can
+//! use them without attaching them itself. This is handy when the rubrics and
+//! the functions live in different crates.
+
+// std uses
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+// external uses
+use anyhow::anyhow;
+
+// internal uses
+use crate::{Result, Submission};
+use super::{Rubric, TestFn};
+
+
+static REGISTRY: Mutex<BTreeMap<String, TestFn>> = Mutex::new(BTreeMap::new());
+
+
+/// Registers a test function under a name, for every rubric in the program.
+///
+/// The name should match a criterion's `func`. Registering a name again
+/// replaces the function that was there. Closures that don't capture
+/// anything work too.
+///
+/// ```rust
+/// # use rubric::TestData;
+/// use rubric::rubric::registry::{register_func, registered_func};
+///
+/// register_func("repo_exists", |data: &TestData| data.contains_key("repo"));
+/// assert!(registered_func("repo_exists").is_some());
+/// ```
+pub fn register_func(name: &str, func: TestFn) {
+    registry().insert(name.to_string(), func);
+}
+
+/// The function registered under a name, if there is one.
+pub fn registered_func(name: &str) -> Option<TestFn> {
+    registry().get(name).copied()
+}
+
+// A panic while holding the lock can't leave the map half changed,
+// so it's fine to keep using it
+fn registry() -> std::sync::MutexGuard<'static, BTreeMap<String, TestFn>> {
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+
+impl Rubric {
+    /// Attaches registered functions (see [`register_func`](crate::rubric::registry::register_func))
+    /// to every criterion that doesn't have a test yet, by the criterion's `func`.
+    ///
+    /// Criteria that already have a test keep it. If any of the rest don't have a
+    /// registered function, this returns an `Err` naming all of them and nothing is attached.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, TestData, yaml};
+    /// use rubric::rubric::registry::register_func;
+    ///
+    /// let mut rubric = Rubric::from_yaml(yaml!("../../test_data/test_rubric.yml").unwrap()).unwrap();
+    /// // Nothing is registered for these yet
+    /// assert!(rubric.attach_registered().is_err());
+    ///
+    /// for func in rubric.funcs().iter().map(|f| f.to_string()).collect::<Vec<_>>() {
+    ///     register_func(&func, |_: &TestData| true);
+    /// }
+    /// rubric.attach_registered().unwrap();
+    /// ```
+    pub fn attach_registered(&mut self) -> Result<()> {
+        let registry = registry();
+        let missing: Vec<String> = self.criteria.iter()
+            .filter(|crit| !crit.has_test() && !registry.contains_key(&crit.func))
+            .map(|crit| format!("`{}`", crit.func))
+            .collect();
+        if !missing.is_empty() {
+            return Err(anyhow!("No function is registered for func {}, nothing was attached", missing.join(", ")));
+        }
+
+        for crit in self.criteria.iter_mut().filter(|crit| !crit.has_test()) {
+            if let Some(test) = registry.get(&crit.func) {
+                crit.attach(Box::new(*test));
+            }
+        }
+        Ok(())
+    }
+
+    /// Attaches registered functions with [`attach_registered`](crate::rubric::Rubric::attach_registered),
+    /// then grades the submission.
+    ///
+    /// If a criterion's `func` isn't registered, this returns an `Err`
+    /// and the submission isn't graded.
+    pub fn grade_registered(&mut self, submission: &mut Submission) -> Result<()> {
+        self.attach_registered()?;
+        submission.grade_against(self);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data, TestData};
+
+    // The registry is shared by every test, so these use names nothing else does
+    const YAML: &str = "name: Registry\ncriteria:\n  Has repo:\n    func: registry_test_repo\n    worth: 10\n  Has readme:\n    func: registry_test_readme\n    worth: 5\n";
+
+    #[test]
+    fn test_grade_registered() {
+        register_func("registry_test_repo", |data: &TestData| data.contains_key("repo"));
+        register_func("registry_test_readme", |data: &TestData| data.contains_key("readme"));
+
+        let mut rubric = Rubric::from_yaml(YAML).unwrap();
+        let mut sub = Submission::from_data(data! { "repo" => "lab_grader" });
+        rubric.grade_registered(&mut sub).unwrap();
+
+        assert_eq!(sub.grade, 10);
+        assert!(sub.has_passed("Has repo"));
+        assert!(sub.has_failed("Has readme"));
+    }
+
+    #[test]
+    fn test_unregistered_func() {
+        let yaml = "name: Registry\ncriteria:\n  Missing:\n    func: registry_test_missing\n    worth: 10\n  Attached:\n    func: registry_test_attached\n    worth: 5\n";
+        let mut rubric = Rubric::from_yaml(yaml).unwrap();
+        // Criteria that already have a test don't need to be registered
+        rubric.get_mut("registry_test_attached").unwrap().attach(Box::new(|_: &TestData| true));
+
+        let mut sub = Submission::new();
+        let err = rubric.grade_registered(&mut sub).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No function is registered for func `registry_test_missing`, nothing was attached"
+        );
+        assert!(!sub.is_graded());
+    }
+}
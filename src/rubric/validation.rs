@@ -0,0 +1,212 @@
+//! Checking a rubric for mistakes before it's used
+
+// std uses
+use std::collections::HashMap;
+use std::fmt;
+
+// internal uses
+use super::{criteria_total, Rubric};
+use super::criterion_builder::DEFAULT_INDEX;
+
+
+/// Names longer than this (in characters) don't fit well in reports.
+pub const MAX_NAME_LENGTH: usize = 80;
+
+
+/// A problem with a rubric, found by [`Rubric::validate`](crate::rubric::Rubric::validate).
+///
+/// Each one names the criterion it's about, except for a total mismatch,
+/// which is about the whole rubric.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// More than one criterion has this `func`. Tests are attached by
+    /// `func`, so only the first one could get a test.
+    DuplicateFunc { name: String, func: String },
+    /// More than one criterion has this index, so they're ordered by `func` instead.
+    DuplicateIndex { name: String, index: i64 },
+    /// The criterion's `func` is empty.
+    EmptyFunc { name: String },
+    /// The criterion has no test attached, no command, and no async test.
+    /// It will always fail.
+    Unattached { name: String, func: String },
+    /// The criterion's name is longer than [`MAX_NAME_LENGTH`](crate::rubric::validation::MAX_NAME_LENGTH).
+    NameTooLong { name: String, len: usize },
+    /// The criterion isn't worth any points.
+    ZeroWorth { name: String },
+    /// The rubric's `total` doesn't match the criteria. Bonus criteria aren't counted.
+    TotalMismatch { expected: isize, actual: isize },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ValidationError::*;
+        match self {
+            DuplicateFunc { name, func } =>
+                write!(f, "{}: func '{}' is used by another criterion", name, func),
+            DuplicateIndex { name, index } =>
+                write!(f, "{}: index {} is used by another criterion", name, index),
+            EmptyFunc { name } =>
+                write!(f, "{}: func is empty", name),
+            Unattached { name, func } =>
+                write!(f, "{}: no test is attached to '{}'", name, func),
+            NameTooLong { name, len } =>
+                write!(f, "{}: name is {} characters, the limit is {}", name, len, MAX_NAME_LENGTH),
+            ZeroWorth { name } =>
+                write!(f, "{}: worth is 0", name),
+            TotalMismatch { expected, actual } =>
+                write!(f, "Rubric total is {}, but the criteria add up to {}", expected, actual),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+
+impl Rubric {
+    /// Checks the rubric for authoring mistakes, returning every problem
+    /// it finds instead of stopping at the first one.
+    ///
+    /// This is meant to be run before grading, like in CI over your rubric files,
+    /// after all the tests have been attached. It looks for
+    /// - criteria that share a `func` or an index
+    /// - criteria with an empty `func`, or no test attached
+    /// - names longer than [`MAX_NAME_LENGTH`](crate::rubric::validation::MAX_NAME_LENGTH)
+    /// - criteria worth 0 points
+    /// - a `total` in the yaml that doesn't match the criteria
+    ///
+    /// Criteria without an index all have the default index, so that
+    /// one isn't reported as a duplicate.
+    ///
+    /// ```rust
+    /// # use rubric::{Rubric, yaml};
+    /// # use rubric::rubric::ValidationError;
+    /// let rubric = Rubric::from_yaml(yaml!("../../test_data/test_rubric.yml").unwrap()).unwrap();
+    ///
+    /// // Nothing has been attached yet
+    /// let errors = rubric.validate().unwrap_err();
+    /// assert!(errors.iter().all(|e| matches!(e, ValidationError::Unattached { .. })));
+    /// ```
+    pub fn validate(&self) -> std::result::Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        let mut funcs: HashMap<&str, usize> = HashMap::new();
+        let mut indices: HashMap<i64, usize> = HashMap::new();
+
+        for crit in &self.criteria {
+            let name = crit.name.clone();
+
+            if crit.func.is_empty() {
+                errors.push(ValidationError::EmptyFunc { name: name.clone() });
+            } else {
+                let count = funcs.entry(&crit.func).or_insert(0);
+                *count += 1;
+                if *count == 2 {
+                    errors.push(ValidationError::DuplicateFunc {
+                        name: name.clone(),
+                        func: crit.func.clone()
+                    });
+                }
+            }
+
+            if crit.index != DEFAULT_INDEX {
+                let count = indices.entry(crit.index).or_insert(0);
+                *count += 1;
+                if *count == 2 {
+                    errors.push(ValidationError::DuplicateIndex {
+                        name: name.clone(),
+                        index: crit.index
+                    });
+                }
+            }
+
+            if !crit.has_test() {
+                errors.push(ValidationError::Unattached {
+                    name: name.clone(),
+                    func: crit.func.clone()
+                });
+            }
+
+            let len = crit.name.chars().count();
+            if len > MAX_NAME_LENGTH {
+                errors.push(ValidationError::NameTooLong { name: name.clone(), len });
+            }
+
+            if crit.worth == 0 {
+                errors.push(ValidationError::ZeroWorth { name });
+            }
+        }
+
+        if let Some(expected) = self.expected_total {
+            let actual = criteria_total(&self.criteria);
+            if actual != expected {
+                errors.push(ValidationError::TotalMismatch { expected, actual });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{yaml, TestData};
+    use crate::rubric::Criterion;
+
+    fn pass(_: &TestData) -> bool { true }
+
+    #[test]
+    fn test_valid_rubric() {
+        let mut rubric = Rubric::from_yaml(yaml!("../../test_data/test_rubric.yml").unwrap()).unwrap();
+        let funcs: Vec<String> = rubric.funcs().iter().map(|f| f.to_string()).collect();
+        for func in funcs {
+            rubric.get_mut(&func).unwrap().attach(Box::new(pass));
+        }
+        assert_eq!(rubric.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_several_errors_at_once() {
+        let mut rubric = Rubric::default();
+        rubric.expected_total = Some(100);
+        rubric.add(Criterion::new("First").func("first").index(1).worth(10).test(Box::new(pass)).build());
+        rubric.add(Criterion::new("Second").func("first").index(1).build());
+        let long_name = "x".repeat(MAX_NAME_LENGTH + 1);
+        rubric.add(Criterion::new(&long_name).func("long").worth(5).test(Box::new(pass)).build());
+
+        let errors = rubric.validate().unwrap_err();
+        assert_eq!(errors, vec![
+            ValidationError::DuplicateFunc { name: "Second".into(), func: "first".into() },
+            ValidationError::DuplicateIndex { name: "Second".into(), index: 1 },
+            ValidationError::Unattached { name: "Second".into(), func: "first".into() },
+            ValidationError::ZeroWorth { name: "Second".into() },
+            ValidationError::NameTooLong { name: long_name, len: MAX_NAME_LENGTH + 1 },
+            ValidationError::TotalMismatch { expected: 100, actual: 15 },
+        ]);
+    }
+
+    #[test]
+    fn test_total_from_yaml() {
+        let yaml = "name: Test\ntotal: 100\ncriteria:\n  First:\n    worth: 10\n";
+        let rubric = Rubric::from_yaml(yaml).unwrap();
+        assert_eq!(rubric.expected_total, Some(100));
+        let errors = rubric.validate().unwrap_err();
+        assert!(errors.contains(&ValidationError::TotalMismatch { expected: 100, actual: 10 }));
+    }
+
+    #[test]
+    fn test_empty_func_and_commands() {
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("").worth(1).test(Box::new(pass)).build());
+        // A command counts as a test
+        rubric.add(Criterion::new("Runs").worth(1).command("true", &[]).build());
+
+        let errors = rubric.validate().unwrap_err();
+        assert_eq!(errors, vec![ValidationError::EmptyFunc { name: "".into() }]);
+        assert_eq!(errors[0].to_string(), ": func is empty");
+    }
+}
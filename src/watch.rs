@@ -0,0 +1,178 @@
+//! Regrades a submission whenever files change.
+//!
+//! This is meant for writing a rubric. Edit a student's files (or the
+//! submission itself) and the report updates on its own.
+//!
+//! This is only available with the `watch` feature.
+//!
+//! ```toml
+//! [dependencies]
+//! rubric = { version = "*", features = [ "watch" ] }
+//! ```
+// std uses
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+// external uses
+use anyhow::Context;
+use notify::{Event, RecursiveMode, Watcher};
+
+// internal uses
+use crate::{Rubric, Submission, Result};
+use crate::report;
+
+
+/// How long things have to be quiet after a change before regrading.
+///
+/// Saving a file usually causes a few events at once, so this
+/// keeps it from regrading for every one of them.
+pub const DEBOUNCE: Duration = Duration::from_millis(250);
+
+// Clears the terminal and moves the cursor to the top
+const CLEAR_SCREEN: &str = "\x1B[2J\x1B[1;1H";
+
+
+/// Grades a submission, then grades it again every time one of the
+/// watched paths changes, clearing the screen and printing a fresh report.
+///
+/// `submission_source` is a submission in JSON, like the dropbox receives.
+/// It's read again for every regrade and it's watched too, so changes to its
+/// data are picked up. Directories in `watch_paths` are watched recursively.
+///
+/// This runs until the program is stopped. It only returns if the watcher
+/// couldn't be started, or stops on its own.
+///
+/// ```no_run
+/// # use std::path::{Path, PathBuf};
+/// # use rubric::{Rubric, yaml};
+/// # let mut rubric = Rubric::from_yaml(yaml!("../test_data/test_rubric.yml").unwrap()).unwrap();
+/// rubric::watch::watch_and_grade(
+///     &mut rubric,
+///     Path::new("submission.json"),
+///     &[PathBuf::from("student/src")]
+/// ).unwrap();
+/// ```
+pub fn watch_and_grade(rubric: &mut Rubric, submission_source: &Path, watch_paths: &[PathBuf]) -> Result<()> {
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        // Reading files while grading shouldn't trigger another regrade
+        if let Ok(event) = event {
+            if !event.kind.is_access() {
+                tx.send(()).ok();
+            }
+        }
+    }).context("Couldn't start watching for changes")?;
+
+    watcher.watch(submission_source, RecursiveMode::NonRecursive)
+        .context(format!("Couldn't watch {}", submission_source.display()))?;
+    for path in watch_paths {
+        watcher.watch(path, RecursiveMode::Recursive)
+            .context(format!("Couldn't watch {}", path.display()))?;
+    }
+
+    print_regrade(rubric, submission_source);
+    watch_loop(&rx, DEBOUNCE, || print_regrade(rubric, submission_source));
+    Ok(())
+}
+
+// Reads the submission and grades it against a fresh rubric
+fn regrade(rubric: &mut Rubric, submission_source: &Path) -> Result<Submission> {
+    let content = fs::read_to_string(submission_source)
+        .context(format!("Couldn't read submission from {}", submission_source.display()))?;
+    let mut sub: Submission = content.parse()?;
+    rubric.reset();
+    sub.grade_against(rubric);
+    Ok(sub)
+}
+
+fn print_regrade(rubric: &mut Rubric, submission_source: &Path) {
+    print!("{}", CLEAR_SCREEN);
+    match regrade(rubric, submission_source) {
+        Ok(_) => report::short(rubric),
+        // Keep watching, it might be fixed by the next change
+        Err(e) => eprintln!("Error: {:#}", e),
+    }
+}
+
+// Calls `on_change` once for each burst of events, after things have been
+// quiet for `debounce`. Returns when there can't be any more events.
+fn watch_loop<F: FnMut()>(events: &Receiver<()>, debounce: Duration, mut on_change: F) {
+    while events.recv().is_ok() {
+        // Wait for the rest of the burst
+        while events.recv_timeout(debounce).is_ok() {}
+        on_change();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use crate::{data, TestData};
+    use crate::rubric::Criterion;
+
+    #[test]
+    fn test_burst_regrades_once() {
+        let (tx, rx) = channel();
+        for _ in 0..5 {
+            tx.send(()).unwrap();
+        }
+        drop(tx);
+
+        let mut cycles = 0;
+        watch_loop(&rx, Duration::from_millis(20), || cycles += 1);
+        assert_eq!(cycles, 1);
+    }
+
+    #[test]
+    fn test_separate_changes_regrade_separately() {
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            tx.send(()).unwrap();
+            tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(200));
+            tx.send(()).unwrap();
+        });
+
+        let mut cycles = 0;
+        watch_loop(&rx, Duration::from_millis(20), || cycles += 1);
+        assert_eq!(cycles, 2);
+    }
+
+    #[test]
+    fn test_regrade_reads_submission_again() {
+        let dir = tempdir::TempDir::new("watch_regrade").unwrap();
+        let source = dir.path().join("submission.json");
+
+        let mut rubric = Rubric::default();
+        rubric.add(Criterion::new("Has name")
+            .worth(10)
+            .test(Box::new(|data: &TestData| matches!(data.get("name"), Some(n) if !n.is_empty())))
+            .build());
+
+        let sub = Submission::from_data(data! { "name" => "" });
+        fs::write(&source, serde_json::to_string(&sub).unwrap()).unwrap();
+        assert_eq!(regrade(&mut rubric, &source).unwrap().grade, 0);
+
+        // A change to the submission shows up in the next regrade
+        let sub = Submission::from_data(data! { "name" => "luke" });
+        fs::write(&source, serde_json::to_string(&sub).unwrap()).unwrap();
+        let regraded = regrade(&mut rubric, &source).unwrap();
+        assert_eq!(regraded.grade, 10);
+        assert!(regraded.has_passed("Has name"));
+    }
+
+    #[test]
+    fn test_regrade_bad_submission() {
+        let dir = tempdir::TempDir::new("watch_bad").unwrap();
+        let source = dir.path().join("submission.json");
+        let mut rubric = Rubric::default();
+
+        assert!(regrade(&mut rubric, &source).is_err());
+        fs::write(&source, "not json").unwrap();
+        assert!(regrade(&mut rubric, &source).is_err());
+    }
+}
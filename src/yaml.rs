@@ -19,7 +19,13 @@ use std::collections::HashMap;
 use serde::Deserialize;
 
 // internal uses
-use crate::rubric::Criterion;
+use crate::rubric::{Cost, Criterion};
+
+
+/// The failure message used when a criterion only has a success message,
+/// and the rubric doesn't set `failure_message`. `{name}` is replaced with
+/// the criterion's name.
+pub const DEFAULT_FAILURE_TEMPLATE: &str = "Failed: {name}";
 
 
 /// A yaml representation of a [`Rubric`](crate::rubric::Rubric).
@@ -30,6 +36,7 @@ use crate::rubric::Criterion;
 pub struct RubricYaml {
     pub name: String,
     pub desc: Option<String>,
+    pub version: Option<String>,
     pub criteria: HashMap<String, CriterionYaml>,
     pub total: Option<isize>,
     pub deadline: Option<String>,
@@ -37,6 +44,30 @@ pub struct RubricYaml {
     pub allow_late: Option<bool>,
     pub late_penalty: Option<isize>,
     pub late_penalty_per_day: Option<isize>,
+    pub pass_threshold: Option<f64>,
+    pub seed: Option<u64>,
+    pub worth_jitter: Option<u32>,
+    pub output_limit: Option<usize>,
+    pub checkpoints: Option<HashMap<String, CheckpointYaml>>,
+    pub failure_message: Option<String>,
+}
+
+/// A yaml representation of a [`Checkpoint`](crate::rubric::Checkpoint).
+///
+/// The checkpoint's name is its key in the rubric's `checkpoints`.
+#[derive(Deserialize)]
+pub struct CheckpointYaml {
+    pub deadline: Option<String>,
+    pub criteria: Vec<String>,
+}
+
+/// Just the criteria from a rubric's yaml. Everything else in the file is ignored.
+///
+/// This is used by [`Rubric::from_dir`](crate::rubric::Rubric::from_dir) to pull
+/// criteria out of files that aren't the base rubric file.
+#[derive(Deserialize)]
+pub struct CriteriaYaml {
+    pub criteria: HashMap<String, CriterionYaml>,
 }
 
 /// A yaml representation of [`Criterion`](crate::criterion::Criterion)
@@ -48,20 +79,58 @@ pub struct CriterionYaml {
     func: Option<String>,
     index: Option<i64>,
     desc: Option<String>,
-    worth: isize,
-    messages: Option<(String, String)>,
+    worth: f64,
+    messages: Option<MessagesYaml>,
     hide: Option<bool>,
+    required: Option<bool>,
+    bonus: Option<bool>,
+    penalty: Option<bool>,
+    retries: Option<u32>,
+    named_failures: Option<HashMap<String, String>>,
+    weight: Option<f64>,
+    tags: Option<Vec<String>>,
+    group: Option<String>,
+    cost: Option<Cost>,
+    required_data: Option<Vec<String>>,
+}
+
+/// A criterion's messages, either both `[success, failure]`
+/// or just the success message as a string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum MessagesYaml {
+    Both(String, String),
+    Success(String),
 }
 
 impl CriterionYaml {
     // Normally I would implement FromStr but I can't because I can't attach the `name`,
     // just because of the yaml format. Kinda fucky, I know.
     pub fn into_criterion(self, name: String) -> Criterion {
+        self.into_criterion_with_template(name, DEFAULT_FAILURE_TEMPLATE)
+    }
+
+    /// Same as `into_criterion`, but if the criterion only has a success message,
+    /// the failure message is made from `failure_template`. `{name}` in the template
+    /// is replaced with the criterion's name.
+    pub fn into_criterion_with_template(self, name: String, failure_template: &str) -> Criterion {
         // The two required fields
-        let mut builder = Criterion::new(&name).worth(self.worth);
+        let mut builder = Criterion::new(&name);
+        builder = if self.worth.fract() == 0.0 {
+            builder.worth(self.worth as isize)
+        } else {
+            builder.worth_f64(self.worth)
+        };
 
-        if let Some(msg) = self.messages {
-            builder = builder.messages(&msg.0, &msg.1)
+        match self.messages {
+            Some(MessagesYaml::Both(success, failure)) => {
+                builder = builder.messages(&success, &failure)
+            },
+            Some(MessagesYaml::Success(success)) => {
+                let failure = failure_template.replace("{name}", &name);
+                builder = builder.messages(&success, &failure)
+            },
+            None => {}
         }
         if let Some(func) = self.func {
             builder = builder.func(&func)
@@ -75,6 +144,36 @@ impl CriterionYaml {
         if let Some(index) = self.index {
             builder = builder.index(index);
         }
+        if let Some(required) = self.required {
+            builder = builder.required(required);
+        }
+        if let Some(bonus) = self.bonus {
+            builder = builder.bonus(bonus);
+        }
+        if let Some(penalty) = self.penalty {
+            builder = builder.penalty(penalty);
+        }
+        if let Some(retries) = self.retries {
+            builder = builder.retries(retries);
+        }
+        if let Some(weight) = self.weight {
+            builder = builder.weight(weight);
+        }
+        if let Some(group) = self.group {
+            builder = builder.group(&group);
+        }
+        if let Some(cost) = self.cost {
+            builder = builder.cost(cost);
+        }
+        for tag in self.tags.unwrap_or_default() {
+            builder = builder.tag(&tag);
+        }
+        for key in self.required_data.unwrap_or_default() {
+            builder = builder.require_data(&key);
+        }
+        for (name, message) in self.named_failures.unwrap_or_default() {
+            builder = builder.named_failure(&name, &message);
+        }
 
         builder.build()
     }
@@ -0,0 +1,42 @@
+// Labels are shared by every thread, so this is in its own
+// test binary where it can't change what other tests print.
+use rubric::Rubric;
+use rubric::report::{self, Labels};
+use rubric::rubric::Criterion;
+
+#[test]
+fn test_labels() {
+    assert_eq!(report::labels(), Labels::english());
+
+    let mut rubric = Rubric::default();
+    rubric.name = String::from("Laboratorio");
+    let mut crit = Criterion::new("Compila").worth(10).build();
+    crit.status = Some(true);
+    rubric.add(crit);
+    rubric.add(Criterion::new("Extra").worth(5).bonus(true).build());
+    let mut skipped = Criterion::new("Opcional").build();
+    skipped.skip();
+
+    report::set_labels(Labels {
+        grade: String::from("Nota"),
+        points: String::from("ptos"),
+        skipped: String::from("omitido"),
+        not_tested: String::from("Sin probar"),
+        feedback: String::from("Comentarios"),
+        bonus_available: String::from("extra disponible"),
+        not_applicable: String::from("no aplica"),
+    });
+    let text = rubric.to_string();
+    assert!(text.contains("Compila (10 / 10 ptos)"));
+    assert!(text.ends_with("Nota: 10/10 (+5 extra disponible)\n"));
+    // Names and messages aren't touched
+    assert!(text.starts_with("Laboratorio\n"));
+    assert_eq!(skipped.skip_reason(), "no aplica");
+
+    // Other threads use them too
+    let grade = std::thread::spawn(|| report::labels().grade).join().unwrap();
+    assert_eq!(grade, "Nota");
+
+    report::set_labels(Labels::default());
+    assert_eq!(skipped.skip_reason(), "not applicable");
+}
@@ -0,0 +1,28 @@
+// Symbols are shared by every thread, so this is in its own
+// test binary where it can't change what other tests print.
+use rubric::report::{self, Symbols};
+use rubric::rubric::Criterion;
+
+#[test]
+fn test_symbols() {
+    assert_eq!(report::symbols(), Symbols::unicode());
+
+    let mut crit = Criterion::new("Passes").worth(10).build();
+    crit.status = Some(true);
+    assert_eq!(format!("{:#}", crit), "✓ Passes (10 / 10 pts)");
+
+    report::set_symbols(Symbols::ascii());
+    assert_eq!(format!("{:#}", crit), "[P] Passes (10 / 10 pts)");
+    crit.status = Some(false);
+    assert_eq!(format!("{:#}", crit), "[F] Passes (0 / 10 pts)");
+    crit.skip();
+    assert_eq!(format!("{:#}", crit), "[S] Passes (— / 10 pts)");
+    // The normal display doesn't have symbols
+    assert_eq!(format!("{}", crit), "Passes (— / 10 pts)");
+
+    // Other threads use them too
+    let pass = std::thread::spawn(|| report::symbols().pass).join().unwrap();
+    assert_eq!(pass, "[P]");
+
+    report::set_symbols(Symbols::default());
+}